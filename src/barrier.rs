@@ -0,0 +1,43 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use anyhow::Result;
+use parquet::file::metadata::ParquetMetaData;
+use rayon::prelude::*;
+
+use crate::{
+    catch_writer_panic, ParallelDatasetWriter, ParquetTableWriter, SeqWriter, StructArrayBuilder,
+};
+
+impl<Builder: Default + StructArrayBuilder + Send>
+    ParallelDatasetWriter<ParquetTableWriter<Builder>>
+{
+    /// Flushes all underlying writers, then closes their current file and opens a new
+    /// one (see [`ParquetTableWriter::rotate`]), and returns the metadata of the closed
+    /// files.
+    ///
+    /// Writers whose current file has no rows keep it open, so no empty file is
+    /// produced.
+    ///
+    /// This produces a consistent checkpoint: the files closed so far contain exactly
+    /// the rows written before this call. No explicit synchronization is needed, as this
+    /// takes `&mut self`, so no thread may hold a writer returned by
+    /// [`get_thread_writer`](Self::get_thread_writer) while it runs; threads writing to
+    /// the dataset need to be joined first (eg. by ending a
+    /// [`rayon::scope`] or parallel iterator).
+    pub fn barrier(&mut self) -> Result<Vec<ParquetMetaData>> {
+        let parallel_chunk_size = self.parallel_chunk_size.unwrap_or(1);
+        let closed_files = self
+            .writers_in_flush_order()
+            .into_par_iter()
+            .with_min_len(parallel_chunk_size)
+            .map(|SeqWriter { path, writer }| {
+                let writer = writer.get_mut().unwrap_or_else(|e| e.into_inner());
+                catch_writer_panic(path, "rotating", || writer.rotate())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(closed_files.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use arrow::array::StructArray;
+use parquet::arrow::arrow_reader::{ArrowReaderOptions, ParquetRecordBatchReaderBuilder};
+
+use crate::{
+    closed_file_path, remove_data_file, ParallelDatasetWriter, ParquetTableWriter,
+    StructArrayBuilder, StructArrayTableWriter, TableWriter,
+};
+
+impl<Builder: Default + StructArrayBuilder + Send>
+    ParallelDatasetWriter<ParquetTableWriter<Builder>>
+{
+    /// Closes all underlying writers, then rewrites the files they wrote into fewer
+    /// files of about `target_file_bytes` each, and deletes the original files.
+    ///
+    /// Files are grouped in the order of their paths, each group being closed as soon
+    /// as the sum of its files' sizes reaches `target_file_bytes`; so the size of
+    /// compacted files is only approximate. Files which are in a group of their own
+    /// (eg. because they are already larger than `target_file_bytes`) are left as-is.
+    /// Compacted files are written at the root of the dataset, and named
+    /// `compacted-<n>.parquet` (or `<prefix>-compacted-<n>.parquet` if
+    /// [`file_name_prefix`](Self::file_name_prefix) is set).
+    ///
+    /// Each group is rewritten by a [`ParquetTableWriter`] with the schema and
+    /// [`config`](Self::config) of the dataset, so compacted files have the same
+    /// properties and sidecars (eg. bitmap indexes) as the original ones, and the
+    /// `on_file_opened` and `on_file_closed` hooks are called on them too. Original
+    /// files are deleted along with their sidecars.
+    ///
+    /// If [`best_effort`](Self::best_effort) is set, files of writers which could not
+    /// be closed are left as-is: they are neither compacted nor returned.
+    ///
+    /// Returns the paths of all data files of the dataset after compaction.
+    pub fn close_and_compact(mut self, target_file_bytes: u64) -> Result<Vec<PathBuf>> {
+        let paths: Vec<PathBuf> = self
+            .close_with(|writer| writer.created_files().to_vec())?
+            .into_iter()
+            .flat_map(|(paths, _metadata)| paths)
+            .collect();
+        let schema = self.schema.clone();
+        let config = self.config.clone();
+        let dataset_path = self.path.clone();
+        let file_name_prefix = self.file_name_prefix.clone();
+        // Already closed by close_with()
+        drop(self);
+
+        // The last file of each writer may have been renamed when closing it
+        let mut paths = paths
+            .iter()
+            .map(|path| closed_file_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        paths.sort();
+
+        let mut groups = Vec::new();
+        let mut group = Vec::new();
+        let mut group_bytes = 0;
+        for path in paths {
+            group_bytes += std::fs::metadata(&path)
+                .with_context(|| format!("Could not get size of {}", path.display()))?
+                .len();
+            group.push(path);
+            if group_bytes >= target_file_bytes {
+                groups.push(std::mem::take(&mut group));
+                group_bytes = 0;
+            }
+        }
+        if !group.is_empty() {
+            groups.push(group);
+        }
+
+        let mut compacted_paths = Vec::new();
+        let mut num_compacted_files = 0;
+        for group in groups {
+            if group.len() == 1 {
+                compacted_paths.extend(group);
+                continue;
+            }
+            let file_name = match &file_name_prefix {
+                None => format!("compacted-{}", num_compacted_files),
+                Some(prefix) => format!("{}-compacted-{}", prefix, num_compacted_files),
+            };
+            num_compacted_files += 1;
+
+            let mut writer = ParquetTableWriter::<Builder>::new(
+                dataset_path.join(file_name),
+                schema.clone(),
+                config.clone(),
+            )?;
+            for path in &group {
+                let file = File::open(path)
+                    .with_context(|| format!("Could not open {}", path.display()))?;
+                // Reads with the dataset's schema, as files may not embed it (see
+                // skip_arrow_schema)
+                let options = ArrowReaderOptions::new().with_schema(schema.0.clone());
+                let reader = ParquetRecordBatchReaderBuilder::try_new_with_options(file, options)
+                    .with_context(|| format!("Could not read {}", path.display()))?
+                    .build()
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+                for batch in reader {
+                    let batch =
+                        batch.with_context(|| format!("Could not read {}", path.display()))?;
+                    writer.write_struct_array(StructArray::from(batch))?;
+                }
+            }
+            let new_paths = writer.created_files().to_vec();
+            writer.close()?;
+
+            for path in &group {
+                remove_data_file(path, &config.bitmap_index_columns)?;
+            }
+            for path in new_paths {
+                compacted_paths.push(closed_file_path(&path)?);
+            }
+        }
+
+        Ok(compacted_paths)
+    }
+}
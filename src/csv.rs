@@ -3,39 +3,250 @@
 // License: GNU General Public License version 3, or any later version
 // See top-level LICENSE file for more information
 
-use std::fs::File;
+use std::io::Write;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 
-use crate::TableWriter;
+use crate::{TableWriter, ZstCloseResult, ZstFile};
 
-pub type CsvZstTableWriter<'a> = csv::Writer<zstd::stream::AutoFinishEncoder<'a, File>>;
+#[derive(Debug, Clone)]
+pub struct CsvZstTableWriterConfig {
+    /// Defaults to [`csv::QuoteStyle::Necessary`]
+    pub quote_style: csv::QuoteStyle,
+    /// Whether quotes in fields are escaped by doubling them (as in RFC 4180),
+    /// instead of using a backslash. Defaults to `true`.
+    pub double_quote: bool,
+    /// Defaults to [`csv::Terminator::CRLF`]
+    pub terminator: csv::Terminator,
+    /// If `true`, the number of uncompressed bytes written to each file is written
+    /// to a `<file>.size` sidecar file when closing it.
+    pub write_size_sidecar: bool,
+    /// If `true`, a UTF-8 byte order mark is written at the start of each file (inside
+    /// the compressed stream), for consumers which need it to detect UTF-8, such as
+    /// Excel.
+    pub write_bom: bool,
+    /// If `true`, the header is not written to the compressed file, but to an
+    /// uncompressed `<file>.header` sidecar file, so column names can be read without
+    /// decompressing the file.
+    ///
+    /// The header is the one derived from the first record passed to
+    /// [`CsvZstTableWriter::serialize`], or the one passed to
+    /// [`CsvZstTableWriter::write_header`].
+    pub header_sidecar: bool,
+    /// If `true`, [`CsvZstTableWriter::write_record`] and
+    /// [`CsvZstTableWriter::write_byte_record`] return an error (and write nothing) if
+    /// a field is not valid UTF-8.
+    pub validate_utf8: bool,
+    /// If `true`, each zstd frame ends with a checksum of its content, which decoders
+    /// verify when decompressing. Defaults to `false`.
+    pub include_checksum: bool,
+}
+
+impl Default for CsvZstTableWriterConfig {
+    fn default() -> Self {
+        CsvZstTableWriterConfig {
+            quote_style: csv::QuoteStyle::Necessary,
+            double_quote: true,
+            terminator: csv::Terminator::CRLF,
+            write_size_sidecar: false,
+            write_bom: false,
+            header_sidecar: false,
+            validate_utf8: false,
+            include_checksum: false,
+        }
+    }
+}
+
+/// Returned by [`CsvZstTableWriter`] when closing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvZstCloseResult {
+    /// Number of records written, not including the header
+    pub rows_written: u64,
+    /// Size of the file
+    pub compressed_bytes: u64,
+    /// Number of bytes written to the file, before compression
+    pub uncompressed_bytes: u64,
+}
+
+/// Writer to a .csv.zst file, usable with [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
+///
+/// This wraps a [`csv::Writer`] to count records.
+pub struct CsvZstTableWriter<'a> {
+    writer: csv::Writer<ZstFile<'a>>,
+    num_written_rows: u64,
+    config: CsvZstTableWriterConfig,
+    /// Path of the header sidecar, if [`CsvZstTableWriterConfig::header_sidecar`] is
+    /// `true`
+    header_sidecar_path: Option<PathBuf>,
+    header_written: bool,
+}
+
+/// Returns a [`csv::WriterBuilder`] with the format options of the config
+fn writer_builder(config: &CsvZstTableWriterConfig) -> csv::WriterBuilder {
+    let mut builder = csv::WriterBuilder::new();
+    builder
+        .quote_style(config.quote_style)
+        .double_quote(config.double_quote)
+        .terminator(config.terminator);
+    builder
+}
+
+impl<'a> CsvZstTableWriter<'a> {
+    /// Returns the wrapped [`csv::Writer`], eg. to use methods not exposed by this
+    /// writer, such as [`write_field`](csv::Writer::write_field).
+    ///
+    /// Records written through it are not counted in
+    /// [`CsvZstCloseResult::rows_written`], and bypass
+    /// [`validate_utf8`](CsvZstTableWriterConfig::validate_utf8) and
+    /// [`header_sidecar`](CsvZstTableWriterConfig::header_sidecar).
+    pub fn inner(&mut self) -> &mut csv::Writer<ZstFile<'a>> {
+        &mut self.writer
+    }
+
+    /// Writes the header, which is not counted as a record.
+    ///
+    /// This is only needed when writing records with
+    /// [`write_record`](Self::write_record) or
+    /// [`write_byte_record`](Self::write_byte_record), as
+    /// [`serialize`](Self::serialize) writes the header automatically.
+    pub fn write_header<I, T>(&mut self, header: I) -> csv::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        match &self.header_sidecar_path {
+            None => self.writer.write_record(header)?,
+            Some(header_sidecar_path) => {
+                let mut header_writer = writer_builder(&self.config)
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                header_writer.write_record(header)?;
+                let header = header_writer.into_inner().map_err(|e| e.into_error())?;
+                std::fs::write(header_sidecar_path, header)?;
+            }
+        }
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// See [`csv::Writer::write_record`]
+    pub fn write_record<I, T>(&mut self, record: I) -> csv::Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        if self.config.validate_utf8 {
+            return self.write_byte_record(&record.into_iter().collect());
+        }
+        self.writer.write_record(record)?;
+        self.num_written_rows += 1;
+        Ok(())
+    }
+
+    /// See [`csv::Writer::write_byte_record`]
+    pub fn write_byte_record(&mut self, record: &csv::ByteRecord) -> csv::Result<()> {
+        if self.config.validate_utf8 {
+            if let Some(column) = record
+                .iter()
+                .position(|field| std::str::from_utf8(field).is_err())
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid UTF-8 in column {} of row {} of {}",
+                        column,
+                        self.num_written_rows,
+                        self.writer.get_ref().path().display()
+                    ),
+                )
+                .into());
+            }
+        }
+        self.writer.write_byte_record(record)?;
+        self.num_written_rows += 1;
+        Ok(())
+    }
+
+    /// See [`csv::Writer::serialize`]
+    pub fn serialize<S: serde::Serialize>(&mut self, record: S) -> csv::Result<()> {
+        if let Some(header_sidecar_path) = &self.header_sidecar_path {
+            if !self.header_written {
+                // The csv crate does not expose the header it derives from a record, so
+                // serialize it with and without header, and keep the difference
+                let mut with_header = writer_builder(&self.config)
+                    .has_headers(true)
+                    .from_writer(Vec::new());
+                with_header.serialize(&record)?;
+                let with_header = with_header.into_inner().map_err(|e| e.into_error())?;
+                let mut without_header = writer_builder(&self.config)
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                without_header.serialize(&record)?;
+                let without_header = without_header.into_inner().map_err(|e| e.into_error())?;
+                let header = &with_header[..with_header.len() - without_header.len()];
+                if !header.is_empty() {
+                    std::fs::write(header_sidecar_path, header)?;
+                }
+                self.header_written = true;
+            }
+        }
+        self.writer.serialize(record)?;
+        self.num_written_rows += 1;
+        Ok(())
+    }
+}
 
 impl TableWriter for CsvZstTableWriter<'_> {
     type Schema = ();
-    type CloseResult = ();
-    type Config = ();
+    type CloseResult = CsvZstCloseResult;
+    type Config = CsvZstTableWriterConfig;
 
-    fn new(mut path: PathBuf, _schema: Self::Schema, _config: ()) -> Result<Self> {
+    fn new(mut path: PathBuf, _schema: Self::Schema, config: Self::Config) -> Result<Self> {
         path.set_extension("csv.zst");
-        let file =
-            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
         let compression_level = 3;
-        let zstd_encoder = zstd::stream::write::Encoder::new(file, compression_level)
-            .with_context(|| format!("Could not create ZSTD encoder for {}", path.display()))?
-            .auto_finish();
-        Ok(csv::WriterBuilder::new()
-            .has_headers(true)
-            .terminator(csv::Terminator::CRLF)
-            .from_writer(zstd_encoder))
+        let mut zstd_file = ZstFile::create(path, compression_level, config.write_size_sidecar)?
+            .with_checksum(config.include_checksum)?;
+        if config.write_bom {
+            zstd_file
+                .write_all("\u{feff}".as_bytes())
+                .with_context(|| format!("Could not write to {}", zstd_file.path().display()))?;
+        }
+        let header_sidecar_path = config.header_sidecar.then(|| {
+            let mut header_sidecar_path = zstd_file.path().to_owned().into_os_string();
+            header_sidecar_path.push(".header");
+            PathBuf::from(header_sidecar_path)
+        });
+        let writer = writer_builder(&config)
+            .has_headers(!config.header_sidecar)
+            .from_writer(zstd_file);
+        Ok(CsvZstTableWriter {
+            writer,
+            num_written_rows: 0,
+            config,
+            header_sidecar_path,
+            header_written: false,
+        })
     }
 
     fn flush(&mut self) -> Result<()> {
-        self.flush().context("Could not flush CsvZst writer")
+        self.writer.flush().context("Could not flush CsvZst writer")
     }
 
-    fn close(mut self) -> Result<()> {
-        self.flush().context("Could not close CsvZst writer")
+    fn close(self) -> Result<CsvZstCloseResult> {
+        let ZstCloseResult {
+            compressed_bytes,
+            uncompressed_bytes,
+        } = self
+            .writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context("Could not close CsvZst writer")?
+            .finalize()?;
+        Ok(CsvZstCloseResult {
+            rows_written: self.num_written_rows,
+            compressed_bytes,
+            uncompressed_bytes,
+        })
     }
 }
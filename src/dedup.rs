@@ -0,0 +1,225 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arrow::array::{Array, ArrayRef, AsArray, StructArray, UInt32Array, UInt64Array};
+use arrow::compute::{concat, take};
+use arrow::datatypes::{DataType, Field};
+use arrow::row::{RowConverter, SortField};
+
+use crate::{PartitionLayout, StructArrayBuilder, StructArrayTableWriter, TableWriter};
+
+#[derive(Debug, Default, Clone)]
+pub struct DedupConsecutiveTableWriterConfig<InnerConfig> {
+    /// Configuration passed to the wrapped writer
+    pub inner: InnerConfig,
+    /// Automatically flushes the builder to disk when its length (in number of rows)
+    /// reaches the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+    /// If set, a non-nullable `UInt64` column with this name is appended to each row,
+    /// with the number of consecutive identical rows it replaces. The schema of the
+    /// wrapped writer must include it.
+    pub count_column: Option<String>,
+}
+
+/// Wraps a [`StructArrayTableWriter`] and collapses consecutive identical rows into
+/// one before writing them.
+///
+/// Rows are compared with [`arrow::row`], so two rows are identical if all their
+/// values are (with nulls equal to each other).
+///
+/// The last row of each flush is held back until the next flush (or close), as
+/// the next rows may be identical to it.
+pub struct DedupConsecutiveTableWriter<
+    Builder: Default + StructArrayBuilder,
+    W: StructArrayTableWriter,
+> {
+    builder: Builder,
+    inner: Option<W>, // None only between .close() call and Drop
+    count_column: Option<String>,
+    /// Last row of the previous flush, and how many times it was repeated
+    pending_row: Option<(StructArray, u64)>,
+    pub flush_threshold: usize,
+}
+
+impl<Builder: Default + StructArrayBuilder, W: StructArrayTableWriter> TableWriter
+    for DedupConsecutiveTableWriter<Builder, W>
+{
+    type Schema = W::Schema;
+    type CloseResult = W::CloseResult;
+    type Config = DedupConsecutiveTableWriterConfig<W::Config>;
+
+    fn new(path: PathBuf, schema: Self::Schema, config: Self::Config) -> Result<Self> {
+        Ok(DedupConsecutiveTableWriter {
+            builder: Builder::default(),
+            inner: Some(W::new(path, schema, config.inner)?),
+            count_column: config.count_column,
+            pending_row: None,
+            flush_threshold: config.flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+        })
+    }
+
+    fn schema_with_metadata(schema: Self::Schema, key: &str, value: &str) -> Self::Schema {
+        W::schema_with_metadata(schema, key, value)
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        schema: &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        W::write_common_metadata(path, schema, &config.inner)
+    }
+
+    fn partitioning(schema: &Self::Schema) -> Vec<PartitionLayout> {
+        W::partitioning(schema)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let num_bytes = self.builder.buffer_size();
+        let struct_array = self.builder.finish()?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+        if struct_array.is_empty() {
+            return Ok(());
+        }
+
+        // Prepend the row held back by the previous flush
+        let (struct_array, first_row_count) = match self.pending_row.take() {
+            None => (struct_array, 1),
+            Some((pending_row, count)) => (
+                concat(&[&pending_row, &struct_array])
+                    .context("Could not concatenate arrays")?
+                    .as_struct()
+                    .clone(),
+                count,
+            ),
+        };
+
+        let converter = RowConverter::new(
+            struct_array
+                .fields()
+                .iter()
+                .map(|field| SortField::new(field.data_type().clone()))
+                .collect(),
+        )
+        .context("Could not create row converter")?;
+        let rows = converter
+            .convert_columns(struct_array.columns())
+            .context("Could not convert rows")?;
+
+        let mut run_starts = vec![0u32];
+        let mut run_counts = vec![first_row_count];
+        for i in 1..rows.num_rows() {
+            if rows.row(i) == rows.row(i - 1) {
+                *run_counts.last_mut().unwrap() += 1;
+            } else {
+                run_starts.push(u32::try_from(i).expect("row id overflowed u32"));
+                run_counts.push(1);
+            }
+        }
+
+        // The last run may continue in the next flush
+        let last_run_start = run_starts.pop().unwrap();
+        let last_run_count = run_counts.pop().unwrap();
+        self.pending_row = Some((
+            struct_array.slice(usize::try_from(last_run_start).unwrap(), 1),
+            last_run_count,
+        ));
+
+        if run_starts.is_empty() {
+            return Ok(());
+        }
+        let deduplicated = take(&struct_array, &UInt32Array::from(run_starts), None)
+            .context("Could not select rows")?
+            .as_struct()
+            .clone();
+        self.write(deduplicated, run_counts)
+    }
+
+    fn close(mut self) -> Result<Self::CloseResult> {
+        self.flush()?;
+        if let Some((pending_row, count)) = self.pending_row.take() {
+            self.write(pending_row, vec![count])?;
+        }
+        self.inner
+            .take()
+            .expect("Inner writer is unexpectedly None")
+            .close()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.written_rows())
+            .unwrap_or(0)
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.written_bytes())
+            .unwrap_or(0)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        if let Some(inner) = &mut self.inner {
+            inner.set_file_metadata(key, value);
+        }
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder, W: StructArrayTableWriter>
+    DedupConsecutiveTableWriter<Builder, W>
+{
+    /// Flushes the internal buffer is too large, then returns the array builder.
+    pub fn builder(&mut self) -> Result<&mut Builder> {
+        if self.builder.len() >= self.flush_threshold {
+            self.flush()?;
+        }
+
+        Ok(&mut self.builder)
+    }
+
+    /// Writes deduplicated rows, adding the count column if configured
+    fn write(&mut self, struct_array: StructArray, counts: Vec<u64>) -> Result<()> {
+        let struct_array = match &self.count_column {
+            None => struct_array,
+            Some(count_column) => {
+                let (fields, mut columns, nulls) = struct_array.into_parts();
+                let mut fields: Vec<_> = fields.iter().cloned().collect();
+                fields.push(Arc::new(Field::new(count_column, DataType::UInt64, false)));
+                columns.push(Arc::new(UInt64Array::from(counts)) as ArrayRef);
+                StructArray::try_new(fields.into(), columns, nulls)
+                    .context("Could not add count column")?
+            }
+        };
+        self.inner
+            .as_mut()
+            .expect("Inner writer is unexpectedly None")
+            .write_struct_array(struct_array)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder, W: StructArrayTableWriter> Drop
+    for DedupConsecutiveTableWriter<Builder, W>
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            self.flush().unwrap();
+            if let Some((pending_row, count)) = self.pending_row.take() {
+                self.write(pending_row, vec![count]).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use arrow::array::{Array, AsArray, StructArray, UInt32Array};
+
+use crate::{
+    PartitionLayout, PartitionedTableWriterConfig, StructArrayBuilder, StructArrayTableWriter,
+    TableWriter, Utf8PartitionedTableWriter,
+};
+
+/// Returns the partition key of each row of an array
+pub type PartitionKeysFn = fn(&StructArray) -> Vec<String>;
+
+#[derive(Debug, Default, Clone)]
+pub struct DerivedPartitionWriterConfig<PartitionConfig> {
+    /// Automatically flushes the builder to disk when its length (in number of rows)
+    /// reaches the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+    pub partitioned: PartitionedTableWriterConfig<PartitionConfig>,
+}
+
+/// Writer which partitions rows based on their content, to
+/// `base/<partition_column>=<partition_key>/x.parquet`, like
+/// [`Utf8PartitionedTableWriter`].
+///
+/// Unlike [`Utf8PartitionedTableWriter`], callers write to a single builder, and each
+/// flush splits the built array by partition key (as returned by a [`PartitionKeysFn`])
+/// and writes every slice to the writer of its partition.
+pub struct DerivedPartitionWriter<
+    Builder: Default + StructArrayBuilder,
+    PartitionWriter: StructArrayTableWriter + Send,
+> {
+    builder: Builder,
+    partition_keys: PartitionKeysFn,
+    partitions: Option<Utf8PartitionedTableWriter<PartitionWriter>>, // None only between .close() call and Drop
+    pub flush_threshold: usize,
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send>
+    TableWriter for DerivedPartitionWriter<Builder, PartitionWriter>
+{
+    /// `(partition_column, partition_keys, underlying_schema)`
+    type Schema = (String, PartitionKeysFn, PartitionWriter::Schema);
+    type CloseResult = Vec<PartitionWriter::CloseResult>;
+    type Config = DerivedPartitionWriterConfig<PartitionWriter::Config>;
+
+    fn new(
+        path: PathBuf,
+        (partition_column, partition_keys, schema): Self::Schema,
+        config: Self::Config,
+    ) -> Result<Self> {
+        Ok(DerivedPartitionWriter {
+            builder: Builder::default(),
+            partition_keys,
+            partitions: Some(Utf8PartitionedTableWriter::new(
+                path,
+                (partition_column, schema),
+                config.partitioned,
+            )?),
+            flush_threshold: config.flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+        })
+    }
+
+    fn schema_with_metadata(
+        (partition_column, partition_keys, schema): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        (
+            partition_column,
+            partition_keys,
+            PartitionWriter::schema_with_metadata(schema, key, value),
+        )
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        (_partition_column, _partition_keys, schema): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        PartitionWriter::write_common_metadata(path, schema, &config.partitioned.partition_config)
+    }
+
+    fn partitioning(
+        (partition_column, _partition_keys, schema): &Self::Schema,
+    ) -> Vec<PartitionLayout> {
+        let mut partitioning = vec![PartitionLayout {
+            column: partition_column.clone(),
+            key_type: "utf8",
+        }];
+        partitioning.extend(PartitionWriter::partitioning(schema));
+        partitioning
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let num_bytes = self.builder.buffer_size();
+        let struct_array = self.builder.finish()?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+
+        let keys = (self.partition_keys)(&struct_array);
+        write_rows_by_key(
+            self.partitions
+                .as_mut()
+                .expect("Partitions are unexpectedly None"),
+            &struct_array,
+            keys,
+        )
+    }
+
+    fn close(mut self) -> Result<Self::CloseResult> {
+        self.flush()?;
+        self.partitions
+            .take()
+            .expect("Partitions are unexpectedly None")
+            .close()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.partitions
+            .as_ref()
+            .map(|partitions| partitions.written_rows())
+            .unwrap_or(0)
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.partitions
+            .as_ref()
+            .map(|partitions| partitions.written_bytes())
+            .unwrap_or(0)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+            + self
+                .partitions
+                .as_ref()
+                .map(|partitions| partitions.buffer_size())
+                .unwrap_or(0)
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        if let Some(partitions) = &mut self.partitions {
+            partitions.set_file_metadata(key, value);
+        }
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send>
+    DerivedPartitionWriter<Builder, PartitionWriter>
+{
+    /// Flushes the internal buffer is too large, then returns the array builder.
+    pub fn builder(&mut self) -> Result<&mut Builder> {
+        if self.builder.len() >= self.flush_threshold {
+            self.flush()?;
+        }
+
+        Ok(&mut self.builder)
+    }
+}
+
+/// Writes each row of `struct_array` to the partition whose key is at the same index
+/// in `keys`
+pub(crate) fn write_rows_by_key<PartitionWriter: StructArrayTableWriter + Send>(
+    partitions: &mut Utf8PartitionedTableWriter<PartitionWriter>,
+    struct_array: &StructArray,
+    keys: Vec<String>,
+) -> Result<()> {
+    ensure!(
+        keys.len() == struct_array.len(),
+        "Got {} partition keys for {} rows",
+        keys.len(),
+        struct_array.len()
+    );
+    let mut rows_by_key: HashMap<String, Vec<u32>> = HashMap::new();
+    for (row, key) in keys.into_iter().enumerate() {
+        rows_by_key
+            .entry(key)
+            .or_default()
+            .push(u32::try_from(row).expect("row id overflowed u32"));
+    }
+
+    for (key, rows) in rows_by_key {
+        let partition_array = arrow::compute::take(struct_array, &UInt32Array::from(rows), None)
+            .with_context(|| format!("Could not select rows of partition {}", key))?
+            .as_struct()
+            .clone();
+        partitions
+            .partition(key)?
+            .write_struct_array(partition_array)?;
+    }
+
+    Ok(())
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send> Drop
+    for DerivedPartitionWriter<Builder, PartitionWriter>
+{
+    fn drop(&mut self) {
+        if self.partitions.is_some() {
+            self.flush().unwrap();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::{PartitionLayout, TableWriter};
+
+#[derive(Debug, Default, Clone)]
+pub struct FaultInjectingTableWriterConfig<InnerConfig> {
+    /// Configuration passed to the wrapped writer
+    pub inner: InnerConfig,
+    /// Indices (starting from 0) of the calls to [`TableWriter::flush`] which fail
+    pub failing_flushes: Vec<u64>,
+    /// Whether [`TableWriter::close`] fails
+    pub fail_close: bool,
+}
+
+/// Wraps a [`TableWriter`] and makes some of its operations fail, according to its
+/// configuration.
+///
+/// This is meant to test error handling, without needing actual I/O errors. Failing
+/// operations return an error without calling the wrapped writer.
+pub struct FaultInjectingTableWriter<W: TableWriter> {
+    inner: W,
+    failing_flushes: Vec<u64>,
+    fail_close: bool,
+    num_flushes: u64,
+}
+
+impl<W: TableWriter> TableWriter for FaultInjectingTableWriter<W> {
+    type Schema = W::Schema;
+    type CloseResult = W::CloseResult;
+    type Config = FaultInjectingTableWriterConfig<W::Config>;
+
+    fn new(path: PathBuf, schema: Self::Schema, config: Self::Config) -> Result<Self> {
+        Ok(FaultInjectingTableWriter {
+            inner: W::new(path, schema, config.inner)?,
+            failing_flushes: config.failing_flushes,
+            fail_close: config.fail_close,
+            num_flushes: 0,
+        })
+    }
+
+    fn schema_with_metadata(schema: Self::Schema, key: &str, value: &str) -> Self::Schema {
+        W::schema_with_metadata(schema, key, value)
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        schema: &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        W::write_common_metadata(path, schema, &config.inner)
+    }
+
+    fn partitioning(schema: &Self::Schema) -> Vec<PartitionLayout> {
+        W::partitioning(schema)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let flush_id = self.num_flushes;
+        self.num_flushes += 1;
+        if self.failing_flushes.contains(&flush_id) {
+            bail!("Injected failure of flush #{}", flush_id);
+        }
+        self.inner.flush()
+    }
+
+    fn close(self) -> Result<Self::CloseResult> {
+        if self.fail_close {
+            bail!("Injected failure of close");
+        }
+        self.inner.close()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.inner.written_rows()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.inner.written_bytes()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.inner.buffer_size()
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        self.inner.set_file_metadata(key, value)
+    }
+}
+
+impl<W: TableWriter> FaultInjectingTableWriter<W> {
+    /// Returns the wrapped writer
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
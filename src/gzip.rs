@@ -0,0 +1,107 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::TableWriter;
+
+#[derive(Debug, Clone)]
+pub struct GzipTableWriterConfig {
+    pub extension: String,
+    /// From 0 (no compression) to 9 (best compression)
+    pub compression_level: u32,
+}
+
+impl Default for GzipTableWriterConfig {
+    fn default() -> Self {
+        GzipTableWriterConfig {
+            extension: "gz".to_owned(),
+            compression_level: 6,
+        }
+    }
+}
+
+/// Writer to a .gz file, usable with [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
+///
+/// Every call to [`TableWriter::flush`] ends the current gzip member and starts a new one.
+/// The file is therefore a concatenation of gzip members, which decompresses in a single
+/// pass with standard tools, but can also be processed incrementally one member at a time.
+pub struct GzipTableWriter {
+    path: PathBuf,
+    compression: Compression,
+    encoder: Option<GzEncoder<File>>, // None only while starting a new member
+    /// Whether anything was written to the current member
+    member_is_empty: bool,
+}
+
+impl TableWriter for GzipTableWriter {
+    type Schema = ();
+    type CloseResult = ();
+    type Config = GzipTableWriterConfig;
+
+    fn new(mut path: PathBuf, _schema: Self::Schema, config: Self::Config) -> Result<Self> {
+        path.set_extension(&config.extension);
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let compression = Compression::new(config.compression_level);
+        Ok(GzipTableWriter {
+            path,
+            compression,
+            encoder: Some(GzEncoder::new(file, compression)),
+            member_is_empty: true,
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.member_is_empty {
+            // Avoid writing empty members
+            return Ok(());
+        }
+        let file = self
+            .encoder
+            .take()
+            .expect("Gzip encoder is unexpectedly None")
+            .finish()
+            .with_context(|| format!("Could not flush to {}", self.path.display()))?;
+        self.encoder = Some(GzEncoder::new(file, self.compression));
+        self.member_is_empty = true;
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.encoder
+            .take()
+            .expect("Gzip encoder is unexpectedly None")
+            .finish()
+            .with_context(|| format!("Could not close {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl Write for GzipTableWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !buf.is_empty() {
+            self.member_is_empty = false;
+        }
+        self.encoder
+            .as_mut()
+            .expect("Gzip encoder is unexpectedly None")
+            .write(buf)
+    }
+
+    /// Flushes the compressed stream without ending the current member
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder
+            .as_mut()
+            .expect("Gzip encoder is unexpectedly None")
+            .flush()
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use parquet::file::metadata::ParquetMetaData;
+use serde::Serialize;
+
+/// Subset of the fields of an
+/// [Iceberg `data_file`](https://iceberg.apache.org/spec/#data-file-fields) which can be
+/// derived from a Parquet file and its metadata.
+///
+/// Maps are keyed by field id. Columns without a field id in the Parquet schema
+/// use their leaf column index plus one instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IcebergDataFile {
+    /// Always 0 (data)
+    pub content: i32,
+    pub file_path: String,
+    /// Always `PARQUET`
+    pub file_format: String,
+    pub record_count: i64,
+    pub file_size_in_bytes: i64,
+    pub column_sizes: BTreeMap<i32, i64>,
+    pub value_counts: BTreeMap<i32, i64>,
+    /// Only contains columns for which all row groups have a null count in their
+    /// statistics
+    pub null_value_counts: BTreeMap<i32, i64>,
+    /// Offsets of the row groups
+    pub split_offsets: Vec<i64>,
+}
+
+/// Returns the Iceberg data file entries of Parquet files, given the metadata returned
+/// by [`ParquetTableWriter`](crate::ParquetTableWriter) on close and the path of each
+/// file.
+pub fn iceberg_data_files(
+    footers: &[ParquetMetaData],
+    paths: &[PathBuf],
+) -> Result<Vec<IcebergDataFile>> {
+    ensure!(
+        footers.len() == paths.len(),
+        "Got {} footers but {} paths",
+        footers.len(),
+        paths.len()
+    );
+    footers
+        .iter()
+        .zip(paths)
+        .map(|(footer, path)| {
+            let file_size_in_bytes = std::fs::metadata(path)
+                .with_context(|| format!("Could not get size of {}", path.display()))?
+                .len();
+            let field_ids: Vec<i32> = footer
+                .file_metadata()
+                .schema_descr()
+                .columns()
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    let info = column.self_type().get_basic_info();
+                    if info.has_id() {
+                        info.id()
+                    } else {
+                        i32::try_from(i + 1).expect("column index overflowed i32")
+                    }
+                })
+                .collect();
+
+            let mut column_sizes = BTreeMap::new();
+            let mut value_counts = BTreeMap::new();
+            let mut null_value_counts = BTreeMap::new();
+            let mut columns_without_null_count = Vec::new();
+            for row_group in footer.row_groups() {
+                for (column, &field_id) in row_group.columns().iter().zip(&field_ids) {
+                    *column_sizes.entry(field_id).or_insert(0) += column.compressed_size();
+                    *value_counts.entry(field_id).or_insert(0) += column.num_values();
+                    match column.statistics().and_then(|stats| stats.null_count_opt()) {
+                        Some(null_count) => {
+                            *null_value_counts.entry(field_id).or_insert(0) +=
+                                i64::try_from(null_count).expect("null count overflowed i64")
+                        }
+                        None => columns_without_null_count.push(field_id),
+                    }
+                }
+            }
+            for field_id in columns_without_null_count {
+                null_value_counts.remove(&field_id);
+            }
+
+            Ok(IcebergDataFile {
+                content: 0,
+                file_path: path.display().to_string(),
+                file_format: "PARQUET".to_owned(),
+                record_count: footer.file_metadata().num_rows(),
+                file_size_in_bytes: i64::try_from(file_size_in_bytes)
+                    .expect("file size overflowed i64"),
+                column_sizes,
+                value_counts,
+                null_value_counts,
+                split_offsets: footer
+                    .row_groups()
+                    .iter()
+                    .filter_map(|row_group| row_group.file_offset())
+                    .collect(),
+            })
+        })
+        .collect()
+}
@@ -4,14 +4,143 @@
 // See top-level LICENSE file for more information
 
 use std::fs::File;
+use std::io::Write;
 use std::path::PathBuf;
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 
+use arrow::array::{Array, RecordBatch, StructArray};
 use arrow::datatypes::Schema;
-use arrow::ipc::writer::FileWriter;
+use arrow::error::ArrowError;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
 
-use super::{StructArrayBuilder, TableWriter};
+use super::{filter_rows, RowFilter, StructArrayBuilder, StructArrayTableWriter, TableWriter};
+
+#[derive(Default, Clone)]
+pub struct ArrowTableWriterConfig {
+    /// Automatically flushes the builder to disk when its length (in number of rows)
+    /// reaches the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+    /// Never automatically flushes the builder to disk while it has fewer rows than
+    /// this value, to avoid writing tiny record batches.
+    ///
+    /// This does not apply to explicit flushes, nor when closing the writer.
+    pub min_flush_rows: Option<usize>,
+    /// If set, the whole file is compressed with zstd at this level, and written with
+    /// the `.arrow.zst` extension instead of `.arrow`.
+    ///
+    /// Such files need to be decompressed before they can be read (or memory-mapped).
+    /// Arrow's own IPC compression, which compresses each buffer independently, does not
+    /// allow choosing a compression level.
+    pub compression_level: Option<i32>,
+    /// If `true`, [`ArrowTableWriter::builder`] never flushes automatically; data is
+    /// only written on explicit flushes and when closing.
+    ///
+    /// Combined with [`ArrowTableWriter::abort`], this allows discarding everything
+    /// written so far.
+    pub manual_commit: bool,
+    /// If `true`, dictionaries of dictionary-encoded columns are written anew on each
+    /// flush and replace the previous ones, instead of accumulating in a single
+    /// dictionary per column over the whole file.
+    ///
+    /// As the Arrow IPC file format does not allow replacing dictionaries, this writes
+    /// the Arrow IPC streaming format instead, with the `.arrows` extension (or
+    /// `.arrows.zst`).
+    pub reset_dictionaries_per_flush: bool,
+    /// Hard cap on the number of rows in the builder: [`ArrowTableWriter::builder`]
+    /// returns an error if the builder holds more rows than this, for example because
+    /// a caller appended a huge number of rows to it at once.
+    ///
+    /// Unlike automatic flushes, this is checked even with
+    /// [`manual_commit`](Self::manual_commit). Unlimited if `None`.
+    pub max_builder_rows: Option<usize>,
+    /// If set, called on the rows of each flush; only rows for which it returns `true`
+    /// are written.
+    ///
+    /// This only applies to rows written with the builder, not to arrays passed to
+    /// [`write_struct_array`](StructArrayTableWriter::write_struct_array).
+    pub row_filter: Option<RowFilter>,
+}
+
+impl std::fmt::Debug for ArrowTableWriterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ArrowTableWriterConfig {
+            flush_threshold,
+            min_flush_rows,
+            compression_level,
+            manual_commit,
+            reset_dictionaries_per_flush,
+            max_builder_rows,
+            row_filter,
+        } = self;
+        f.debug_struct("ArrowTableWriterConfig")
+            .field("flush_threshold", flush_threshold)
+            .field("min_flush_rows", min_flush_rows)
+            .field("compression_level", compression_level)
+            .field("manual_commit", manual_commit)
+            .field("reset_dictionaries_per_flush", reset_dictionaries_per_flush)
+            .field("max_builder_rows", max_builder_rows)
+            .field("row_filter", &row_filter.as_ref().map(|_| "<callback>"))
+            .finish()
+    }
+}
+
+/// Writer of either Arrow IPC format
+enum IpcWriter {
+    File(FileWriter<OutputFile>),
+    Stream(StreamWriter<OutputFile>),
+}
+
+impl IpcWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        match self {
+            IpcWriter::File(writer) => writer.write(batch),
+            IpcWriter::Stream(writer) => writer.write(batch),
+        }
+    }
+
+    /// Writes the footer (or end-of-stream marker) and returns the file
+    fn into_inner(self) -> Result<OutputFile, ArrowError> {
+        match self {
+            IpcWriter::File(writer) => writer.into_inner(),
+            IpcWriter::Stream(writer) => writer.into_inner(),
+        }
+    }
+}
+
+/// File written by [`ArrowTableWriter`]
+enum OutputFile {
+    Plain(File),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+impl OutputFile {
+    /// Ends the zstd stream, if any
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Zstd(encoder) => encoder.finish().map(|_file| ()),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
 
 /// Writer to a .arrow file, usable with [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
 ///
@@ -19,21 +148,63 @@ use super::{StructArrayBuilder, TableWriter};
 /// [`arrow::builder`](https://docs.rs/arrow/latest/arrow/array/builder/index.html)
 pub struct ArrowTableWriter<Builder: Default + StructArrayBuilder> {
     path: PathBuf,
-    file_writer: FileWriter<File>,
+    file_writer: Option<IpcWriter>, // None only between .close() call and Drop
     builder: Builder,
+    num_written_rows: u64,
+    /// Set when a write fails, so `Drop` does not try to write to the file again
+    poisoned: bool,
     pub flush_threshold: usize,
+    /// See [`ArrowTableWriterConfig::min_flush_rows`]
+    pub min_flush_rows: usize,
+    /// See [`ArrowTableWriterConfig::manual_commit`]
+    pub manual_commit: bool,
+    /// See [`ArrowTableWriterConfig::max_builder_rows`]
+    pub max_builder_rows: Option<usize>,
+    row_filter: Option<RowFilter>,
 }
 
 impl<Builder: Default + StructArrayBuilder> TableWriter for ArrowTableWriter<Builder> {
     type Schema = Schema;
     type CloseResult = ();
-    type Config = Option<usize>;
+    type Config = ArrowTableWriterConfig;
 
-    fn new(mut path: PathBuf, schema: Self::Schema, config: Option<usize>) -> Result<Self> {
-        path.set_extension("arrow");
+    fn new(
+        mut path: PathBuf,
+        schema: Self::Schema,
+        ArrowTableWriterConfig {
+            flush_threshold,
+            min_flush_rows,
+            compression_level,
+            manual_commit,
+            reset_dictionaries_per_flush,
+            max_builder_rows,
+            row_filter,
+        }: Self::Config,
+    ) -> Result<Self> {
+        path.set_extension(
+            match (reset_dictionaries_per_flush, compression_level.is_some()) {
+                (false, false) => "arrow",
+                (false, true) => "arrow.zst",
+                (true, false) => "arrows",
+                (true, true) => "arrows.zst",
+            },
+        );
         let file =
             File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
-        let file_writer = FileWriter::try_new(file, &schema).with_context(|| {
+        let file = match compression_level {
+            Some(compression_level) => OutputFile::Zstd(
+                zstd::stream::write::Encoder::new(file, compression_level).with_context(|| {
+                    format!("Could not create ZSTD encoder for {}", path.display())
+                })?,
+            ),
+            None => OutputFile::Plain(file),
+        };
+        let file_writer = if reset_dictionaries_per_flush {
+            StreamWriter::try_new(file, &schema).map(IpcWriter::Stream)
+        } else {
+            FileWriter::try_new(file, &schema).map(IpcWriter::File)
+        }
+        .with_context(|| {
             format!(
                 "Could not create writer for {} with schema {}",
                 path.display(),
@@ -43,46 +214,143 @@ impl<Builder: Default + StructArrayBuilder> TableWriter for ArrowTableWriter<Bui
 
         Ok(ArrowTableWriter {
             path,
-            file_writer,
-            flush_threshold: config.unwrap_or(1024 * 1024), // Arbitrary
+            file_writer: Some(file_writer),
+            flush_threshold: flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+            min_flush_rows: min_flush_rows.unwrap_or(0),
+            manual_commit,
+            max_builder_rows,
+            row_filter,
             builder: Builder::default(),
+            num_written_rows: 0,
+            poisoned: false,
         })
     }
 
+    fn schema_with_metadata(mut schema: Self::Schema, key: &str, value: &str) -> Self::Schema {
+        schema.metadata.insert(key.to_owned(), value.to_owned());
+        schema
+    }
+
     fn flush(&mut self) -> Result<()> {
         let mut tmp = Builder::default();
         std::mem::swap(&mut tmp, &mut self.builder);
-        let struct_array = tmp.finish()?;
-        self.file_writer
-            .write(&struct_array.into())
-            .with_context(|| format!("Could not write to {}", self.path.display()))
+        let num_bytes = tmp.buffer_size();
+        let struct_array = tmp.finish().inspect_err(|_| self.poisoned = true)?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+        let struct_array = match &self.row_filter {
+            None => struct_array,
+            Some(row_filter) => {
+                filter_rows(row_filter, struct_array).inspect_err(|_| self.poisoned = true)?
+            }
+        };
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
     }
 
     fn close(mut self) -> Result<()> {
         self.flush()?;
+        self.finish_file()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.num_written_rows
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+    }
+
+    /// The metadata is written to the footer's custom metadata. This does nothing with
+    /// [`reset_dictionaries_per_flush`](ArrowTableWriterConfig::reset_dictionaries_per_flush),
+    /// as the streaming format has no footer.
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        if let Some(IpcWriter::File(file_writer)) = &mut self.file_writer {
+            file_writer.write_metadata(key, value);
+        }
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> StructArrayTableWriter for ArrowTableWriter<Builder> {
+    fn write_struct_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if self.builder.len() > 0 {
+            self.flush()?;
+        }
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> ArrowTableWriter<Builder> {
+    fn write_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if struct_array.is_empty() {
+            // Nothing to write, and the builder may return an array with a different
+            // schema when it is empty
+            return Ok(());
+        }
+        let num_rows = struct_array.len();
+        self.file_writer
+            .as_mut()
+            .expect("File writer is unexpectedly None")
+            .write(&struct_array.into())
+            .with_context(|| format!("Could not write to {}", self.path.display()))?;
+        self.num_written_rows += u64::try_from(num_rows).expect("usize overflowed u64");
+        Ok(())
+    }
+
+    /// Writes the footer and closes the file
+    fn finish_file(&mut self) -> Result<()> {
         self.file_writer
+            .take()
+            .expect("File writer is unexpectedly None")
+            .into_inner()
+            .with_context(|| format!("Could not close {}", self.path.display()))?
             .finish()
             .with_context(|| format!("Could not close {}", self.path.display()))
     }
-}
 
-impl<Builder: Default + StructArrayBuilder> ArrowTableWriter<Builder> {
     /// Flushes the internal buffer is too large, then returns the array builder.
     pub fn builder(&mut self) -> Result<&mut Builder> {
-        if self.builder.len() >= self.flush_threshold {
+        if let Some(max_builder_rows) = self.max_builder_rows {
+            ensure!(
+                self.builder.len() <= max_builder_rows,
+                "Builder for {} has {} rows, {} more than max_builder_rows ({})",
+                self.path.display(),
+                self.builder.len(),
+                self.builder.len() - max_builder_rows,
+                max_builder_rows
+            );
+        }
+        if !self.manual_commit
+            && self.builder.len() >= self.flush_threshold.max(self.min_flush_rows)
+        {
             self.flush()?;
         }
 
         Ok(&mut self.builder)
     }
+
+    /// Discards buffered rows, and deletes the file (without writing its footer).
+    pub fn abort(mut self) -> Result<()> {
+        // Done before anything which may fail, so Drop never writes the discarded rows
+        self.file_writer = None;
+        std::mem::take(&mut self.builder);
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Could not remove {}", self.path.display()))
+    }
 }
 
 impl<Builder: Default + StructArrayBuilder> Drop for ArrowTableWriter<Builder> {
     fn drop(&mut self) {
-        self.flush().unwrap();
-        self.file_writer
-            .finish()
-            .with_context(|| format!("Could not close {}", self.path.display()))
-            .unwrap();
+        if self.poisoned {
+            tracing::warn!(
+                "Not closing {} on drop, as a previous write to it failed",
+                self.path.display()
+            );
+            return;
+        }
+        if self.file_writer.is_some() {
+            self.flush().unwrap();
+            self.finish_file().unwrap();
+        }
     }
 }
@@ -0,0 +1,270 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+
+use arrow::array::{Array, AsArray, GenericStringArray, OffsetSizeTrait, RecordBatch, StructArray};
+use arrow::datatypes::DataType;
+use arrow::json::writer::LineDelimited;
+use arrow::json::{Writer, WriterBuilder};
+
+use super::{filter_rows, RowFilter, StructArrayBuilder, StructArrayTableWriter, TableWriter};
+
+/// How [`JsonlTableWriter`] renders null values
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NullMode {
+    /// Keys with a null value are omitted from objects
+    #[default]
+    Omit,
+    /// Keys with a null value are written, with `null` as value
+    Explicit,
+}
+
+#[derive(Default, Clone)]
+pub struct JsonlTableWriterConfig {
+    /// Automatically flushes the builder to disk when its length (in number of rows)
+    /// reaches the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+    /// If set, only these columns are written, in this order.
+    pub project_columns: Option<Vec<String>>,
+    /// How null values are rendered, see [`NullMode`]. Defaults to omitting them.
+    pub render_nulls: NullMode,
+    /// If `true`, flushing returns an error (and writes nothing) if a value of a
+    /// top-level string column is not valid UTF-8, which may happen with arrays built
+    /// with unchecked constructors.
+    pub validate_utf8: bool,
+    /// If set, called on the rows of each flush; only rows for which it returns `true`
+    /// are written.
+    ///
+    /// This only applies to rows written with the builder, not to arrays passed to
+    /// [`write_struct_array`](StructArrayTableWriter::write_struct_array).
+    pub row_filter: Option<RowFilter>,
+}
+
+impl std::fmt::Debug for JsonlTableWriterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let JsonlTableWriterConfig {
+            flush_threshold,
+            project_columns,
+            render_nulls,
+            validate_utf8,
+            row_filter,
+        } = self;
+        f.debug_struct("JsonlTableWriterConfig")
+            .field("flush_threshold", flush_threshold)
+            .field("project_columns", project_columns)
+            .field("render_nulls", render_nulls)
+            .field("validate_utf8", validate_utf8)
+            .field("row_filter", &row_filter.as_ref().map(|_| "<callback>"))
+            .finish()
+    }
+}
+
+/// Writer to a .jsonl file (one JSON object per line), usable with
+/// [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
+///
+/// `Builder` should follow the pattern documented by
+/// [`arrow::builder`](https://docs.rs/arrow/latest/arrow/array/builder/index.html)
+pub struct JsonlTableWriter<Builder: Default + StructArrayBuilder> {
+    path: PathBuf,
+    file_writer: Option<Writer<File, LineDelimited>>, // None only between .close() call and Drop
+    builder: Builder,
+    project_columns: Option<Vec<String>>,
+    validate_utf8: bool,
+    row_filter: Option<RowFilter>,
+    num_written_rows: u64,
+    /// Set when a write fails, so `Drop` does not try to write to the file again
+    poisoned: bool,
+    pub flush_threshold: usize,
+}
+
+impl<Builder: Default + StructArrayBuilder> TableWriter for JsonlTableWriter<Builder> {
+    type Schema = ();
+    type CloseResult = ();
+    type Config = JsonlTableWriterConfig;
+
+    fn new(
+        mut path: PathBuf,
+        _schema: Self::Schema,
+        JsonlTableWriterConfig {
+            flush_threshold,
+            project_columns,
+            render_nulls,
+            validate_utf8,
+            row_filter,
+        }: Self::Config,
+    ) -> Result<Self> {
+        path.set_extension("jsonl");
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let file_writer = WriterBuilder::new()
+            .with_explicit_nulls(render_nulls == NullMode::Explicit)
+            .build::<_, LineDelimited>(file);
+
+        Ok(JsonlTableWriter {
+            path,
+            file_writer: Some(file_writer),
+            builder: Builder::default(),
+            project_columns,
+            validate_utf8,
+            row_filter,
+            num_written_rows: 0,
+            poisoned: false,
+            flush_threshold: flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let num_bytes = self.builder.buffer_size();
+        let struct_array = self
+            .builder
+            .finish()
+            .inspect_err(|_| self.poisoned = true)?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+        let struct_array = match &self.row_filter {
+            None => struct_array,
+            Some(row_filter) => {
+                filter_rows(row_filter, struct_array).inspect_err(|_| self.poisoned = true)?
+            }
+        };
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.flush()?;
+        self.finish_file()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.num_written_rows
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> StructArrayTableWriter for JsonlTableWriter<Builder> {
+    fn write_struct_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if self.builder.len() > 0 {
+            self.flush()?;
+        }
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> JsonlTableWriter<Builder> {
+    fn write_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if struct_array.is_empty() {
+            // Nothing to write, and its schema may not have the projected columns, eg.
+            // when flushing an unused builder after calling write_struct_array
+            return Ok(());
+        }
+        if self.validate_utf8 {
+            validate_utf8(&struct_array)
+                .with_context(|| format!("Could not write to {}", self.path.display()))?;
+        }
+        let num_rows = struct_array.len();
+        let mut batch = RecordBatch::from(struct_array);
+        if let Some(project_columns) = &self.project_columns {
+            let indices = project_columns
+                .iter()
+                .map(|column| {
+                    batch
+                        .schema_ref()
+                        .index_of(column)
+                        .with_context(|| format!("Unknown column {}", column))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            batch = batch
+                .project(&indices)
+                .context("Could not project columns")?;
+        }
+
+        self.file_writer
+            .as_mut()
+            .expect("File writer is unexpectedly None")
+            .write(&batch)
+            .with_context(|| format!("Could not write to {}", self.path.display()))?;
+        self.num_written_rows += u64::try_from(num_rows).expect("usize overflowed u64");
+        Ok(())
+    }
+
+    /// Closes the file
+    fn finish_file(&mut self) -> Result<()> {
+        self.file_writer
+            .take()
+            .expect("File writer is unexpectedly None")
+            .finish()
+            .with_context(|| format!("Could not close {}", self.path.display()))
+    }
+
+    /// Flushes the internal buffer is too large, then returns the array builder.
+    pub fn builder(&mut self) -> Result<&mut Builder> {
+        if self.builder.len() >= self.flush_threshold {
+            self.flush()?;
+        }
+
+        Ok(&mut self.builder)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> Drop for JsonlTableWriter<Builder> {
+    fn drop(&mut self) {
+        if self.poisoned {
+            tracing::warn!(
+                "Not closing {} on drop, as a previous write to it failed",
+                self.path.display()
+            );
+            return;
+        }
+        if self.file_writer.is_some() {
+            self.flush().unwrap();
+            self.finish_file().unwrap();
+        }
+    }
+}
+
+/// Returns an error if a value of a top-level string column is not valid UTF-8
+fn validate_utf8(struct_array: &StructArray) -> Result<()> {
+    for (field, column) in struct_array.fields().iter().zip(struct_array.columns()) {
+        let invalid_row = match column.data_type() {
+            DataType::Utf8 => first_invalid_utf8(column.as_string::<i32>()),
+            DataType::LargeUtf8 => first_invalid_utf8(column.as_string::<i64>()),
+            DataType::Utf8View => {
+                column
+                    .as_string_view()
+                    .bytes_iter()
+                    .enumerate()
+                    .position(|(row, value)| {
+                        column.is_valid(row) && std::str::from_utf8(value).is_err()
+                    })
+            }
+            _ => None,
+        };
+        if let Some(row) = invalid_row {
+            bail!("Invalid UTF-8 in column {} of row {}", field.name(), row);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the first non-null value which is not valid UTF-8, if any
+fn first_invalid_utf8<O: OffsetSizeTrait>(array: &GenericStringArray<O>) -> Option<usize> {
+    let offsets = array.value_offsets();
+    let values = array.value_data();
+    (0..array.len()).find(|&row| {
+        array.is_valid(row)
+            && std::str::from_utf8(&values[offsets[row].as_usize()..offsets[row + 1].as_usize()])
+                .is_err()
+    })
+}
@@ -5,36 +5,139 @@
 
 #![cfg_attr(feature = "parquet", doc = include_str!("../README.md"))]
 
-use std::cell::{RefCell, RefMut};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::ThreadId;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+#[cfg(feature = "arrow")]
+pub use arrow;
 #[cfg(feature = "arrow")]
 use arrow::array::StructArray;
 use rayon::prelude::*;
+#[cfg(feature = "parquet")]
+pub use roaring;
 use thread_local::ThreadLocal;
-#[cfg(feature = "arrow")]
-pub use arrow;
+
+#[cfg(feature = "parquet")]
+mod barrier;
+
+#[cfg(feature = "parquet")]
+mod compact;
 
 #[cfg(feature = "csv")]
 mod csv;
 #[cfg(feature = "csv")]
 pub use csv::*;
 
+#[cfg(feature = "arrow")]
+mod dedup;
+#[cfg(feature = "arrow")]
+pub use dedup::*;
+
+#[cfg(feature = "arrow")]
+mod derived_partition;
+#[cfg(feature = "arrow")]
+pub use derived_partition::*;
+
+#[cfg(feature = "fault-injection")]
+mod fault_injection;
+#[cfg(feature = "fault-injection")]
+pub use fault_injection::*;
+
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "gzip")]
+pub use gzip::*;
+
+#[cfg(feature = "iceberg")]
+mod iceberg;
+#[cfg(feature = "iceberg")]
+pub use iceberg::*;
+
 #[cfg(feature = "arrow-ipc")]
 mod ipc;
 #[cfg(feature = "arrow-ipc")]
 pub use ipc::*;
 
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use json::*;
+
 #[cfg(feature = "parquet")]
 mod parquet_;
 #[cfg(feature = "parquet")]
 pub use parquet_::*;
 
+#[cfg(feature = "arrow")]
+mod padding;
+#[cfg(feature = "arrow")]
+pub use padding::*;
+
 mod partitioned;
 pub use partitioned::*;
 
+#[cfg(feature = "parquet")]
+mod resumable;
+#[cfg(feature = "parquet")]
+pub use resumable::*;
+
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+mod row_filter;
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+pub use row_filter::*;
+
+#[cfg(feature = "zstd")]
+mod raw_bytes;
+#[cfg(feature = "zstd")]
+pub use raw_bytes::*;
+
+#[cfg(feature = "parquet")]
+mod shared_parquet;
+#[cfg(feature = "parquet")]
+pub use shared_parquet::*;
+
+#[cfg(any(
+    feature = "arrow-ipc",
+    feature = "json",
+    feature = "parquet",
+    all(feature = "arrow", feature = "csv")
+))]
+mod single_file;
+#[cfg(any(
+    feature = "arrow-ipc",
+    feature = "json",
+    feature = "parquet",
+    all(feature = "arrow", feature = "csv")
+))]
+pub use single_file::*;
+
+#[cfg(feature = "signal")]
+mod signal;
+#[cfg(feature = "signal")]
+pub use signal::*;
+
+#[cfg(feature = "parquet")]
+mod stats_table;
+
+#[cfg(feature = "parquet")]
+mod verify;
+#[cfg(feature = "parquet")]
+pub use verify::*;
+
+#[cfg(feature = "tar")]
+mod tar;
+#[cfg(feature = "tar")]
+pub use tar::*;
+
+#[cfg(feature = "arrow")]
+mod time_bucketed;
+#[cfg(feature = "arrow")]
+pub use time_bucketed::*;
+
 #[cfg(feature = "zstd")]
 mod zstd;
 #[cfg(feature = "zstd")]
@@ -51,6 +154,81 @@ pub trait StructArrayBuilder {
     ///
     /// This method usually should not be called outside table writers.
     fn finish(&mut self) -> Result<StructArray>;
+    /// Hints that about `rows` rows, taking `bytes` bytes, will be written before the next
+    /// call to [`finish`](Self::finish).
+    ///
+    /// Called by table writers after each flush, with the size of the flushed data.
+    /// Implementors may use it to reserve capacity in their buffers to avoid reallocations.
+    fn reserve(&mut self, _rows: usize, _bytes: usize) {}
+    /// Marks the end of the current row.
+    ///
+    /// Table writers do not call this; it is for users who write rows whose optional
+    /// columns may be absent, so they do not need to append a null to each of them.
+    /// Implementors should append nulls to columns which were not written in the
+    /// current row, typically with [`pad_with_nulls`]. Does nothing by default.
+    fn finish_row(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Amount of data written by table writers
+///
+/// Only counts data from writers which implement [`TableWriter::written_rows`] and
+/// [`TableWriter::written_bytes`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+impl std::ops::Add for Statistics {
+    type Output = Statistics;
+
+    fn add(self, other: Statistics) -> Statistics {
+        Statistics {
+            rows: self.rows + other.rows,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
+impl std::iter::Sum for Statistics {
+    fn sum<I: Iterator<Item = Statistics>>(iter: I) -> Statistics {
+        iter.fold(Statistics::default(), std::ops::Add::add)
+    }
+}
+
+/// How much a thread used a writer of a [`ParallelDatasetWriter`], see
+/// [`ParallelDatasetWriter::thread_diagnostics`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadDiagnostics {
+    /// Sequential id of the writer, used to name its file (`<id>` or `<prefix>-<id>`)
+    pub writer_id: u64,
+    pub thread_id: ThreadId,
+    pub thread_name: Option<String>,
+    /// Number of calls to [`ParallelDatasetWriter::get_thread_writer`] from this thread
+    /// which returned this writer
+    pub get_thread_writer_calls: u64,
+}
+
+/// Internal counterpart of [`ThreadDiagnostics`] for a single writer, updated by
+/// [`ParallelDatasetWriter::get_thread_writer`]
+struct WriterThreadUsage {
+    writer_id: u64,
+    /// Name and number of calls of each thread which used the writer.
+    ///
+    /// Only one thread uses the writer at any time, so the lock is not contended.
+    calls_by_thread: Mutex<HashMap<ThreadId, (Option<String>, u64)>>,
+}
+
+/// A [`TableWriter`] which can also write arrays built outside of it
+#[cfg(feature = "arrow")]
+pub trait StructArrayTableWriter: TableWriter {
+    /// Writes the given rows.
+    ///
+    /// Rows in the writer's own builder, if any, are flushed first so the order of rows
+    /// is preserved.
+    fn write_struct_array(&mut self, struct_array: StructArray) -> Result<()>;
 }
 
 /// Writes a set of files (called tables here) to a directory.
@@ -58,8 +236,171 @@ pub struct ParallelDatasetWriter<W: TableWriter + Send> {
     num_files: AtomicU64,
     schema: W::Schema,
     path: PathBuf,
-    writers: ThreadLocal<RefCell<W>>,
+    writers: ThreadLocal<SeqWriter<W>>,
+    /// Only filled if `track_threads` is `true`
+    thread_usage: ThreadLocal<WriterThreadUsage>,
     pub config: W::Config,
+    /// If `true`, errors while closing table writers are logged as warnings instead of
+    /// being returned by [`close`](Self::close) (which then only returns the results of
+    /// writers which closed successfully), and `Drop` does not panic on them.
+    pub best_effort: bool,
+    /// If set, table files are spread into subdirectories (`0000/`, `0001/`, ...) each
+    /// holding up to this many files, instead of being written directly to the dataset
+    /// directory.
+    ///
+    /// This is not supported by partitioned writers (ie. those with a non-empty
+    /// [`TableWriter::partitioning`]), as these subdirectories would not be Hive-style
+    /// partitions: [`get_thread_writer`](Self::get_thread_writer) returns an error
+    /// instead.
+    pub files_per_dir: Option<u64>,
+    /// If set, table files are named `<prefix>-<id>` instead of `<id>`, so processes
+    /// writing to the same directory with different prefixes do not overwrite each
+    /// other's files. See [`unique_file_name_prefix`].
+    pub file_name_prefix: Option<String>,
+    /// If `true`, [`flush`](Self::flush) and [`flush_stats`](Self::flush_stats) start
+    /// flushing the writers with the largest [`buffer_size`](TableWriter::buffer_size)
+    /// first, to free memory sooner.
+    ///
+    /// This is only a scheduling hint, as writers are still flushed in parallel.
+    pub flush_largest_first: bool,
+    /// If `true`, [`close`](Self::close) writes a `_common_metadata` file at the root
+    /// of the dataset, with the schema of the dataset but no data (see
+    /// [`TableWriter::write_common_metadata`]), so readers such as Spark can get the
+    /// schema without opening data files.
+    ///
+    /// This does nothing for formats without a schema.
+    pub write_common_metadata: bool,
+    /// If `true`, keeps track of which thread uses each writer, and how often, to help
+    /// debug uneven file sizes. See [`thread_diagnostics`](Self::thread_diagnostics).
+    ///
+    /// Must be set before the first call to [`get_thread_writer`](Self::get_thread_writer).
+    pub track_threads: bool,
+    /// What [`close`](Self::close) does if no writer was ever created, ie. if
+    /// [`get_thread_writer`](Self::get_thread_writer) was never called.
+    pub empty_dataset_behavior: EmptyDatasetBehavior,
+    /// If `true`, [`close`](Self::close) writes a `_layout_version` JSON file at the
+    /// root of the dataset, recording the version of this crate, how the dataset is
+    /// partitioned (see [`TableWriter::partitioning`]), and how files are named, so
+    /// readers can detect layouts they do not support.
+    pub write_layout_marker: bool,
+    /// Minimum number of table writers handled by each rayon task in
+    /// [`flush`](Self::flush), [`flush_stats`](Self::flush_stats) and
+    /// [`close`](Self::close) (see [`with_min_len`](IndexedParallelIterator::with_min_len)),
+    /// to reduce scheduling overhead when there are many small writers.
+    ///
+    /// Defaults to 1 if `None`, ie. each writer may be handled by a different task.
+    pub parallel_chunk_size: Option<usize>,
+    /// If set, [`get_thread_writer`](Self::get_thread_writer) flushes the writer with the
+    /// largest [`buffer_size`](TableWriter::buffer_size) when the total buffer size of
+    /// all writers exceeds this value, even if that writer belongs to another thread.
+    ///
+    /// This makes every call to [`get_thread_writer`](Self::get_thread_writer) lock
+    /// every writer in turn, which contends with other threads calling it, so this is
+    /// best suited to callers which get their writer once per batch of rows rather
+    /// than once per row. Writers currently borrowed by their thread are skipped, both
+    /// when computing the total and when choosing which one to flush, so the limit is
+    /// only enforced on writers whose thread released them.
+    pub max_buffered_bytes: Option<usize>,
+    /// See [`with_finalizer`](Self::with_finalizer)
+    finalizer: Option<Finalizer<W::CloseResult>>,
+}
+
+/// Table writer of a [`ParallelDatasetWriter`]
+struct SeqWriter<W> {
+    /// Path the writer was created with, to identify it in errors
+    path: PathBuf,
+    /// Only locked by the thread owning the writer, except to flush it when
+    /// [`ParallelDatasetWriter::max_buffered_bytes`] is exceeded
+    writer: Mutex<W>,
+}
+
+impl<W> SeqWriter<W> {
+    /// Returns the path and the writer
+    fn into_parts(self) -> (PathBuf, W) {
+        (
+            self.path,
+            self.writer.into_inner().unwrap_or_else(|e| e.into_inner()),
+        )
+    }
+}
+
+/// Calls `f` on a table writer, turning a panic into an error naming the writer's path,
+/// as panics in rayon tasks otherwise give no clue of which file caused them.
+fn catch_writer_panic<T>(path: &Path, action: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "<unknown panic payload>".to_owned());
+        bail!(
+            "Table writer of {} panicked while {}: {}",
+            path.display(),
+            action,
+            message
+        )
+    })
+}
+
+/// Called by [`ParallelDatasetWriter::close`], see
+/// [`ParallelDatasetWriter::with_finalizer`]
+type Finalizer<CloseResult> = Box<dyn FnOnce(&[CloseResult]) -> Result<()> + Send + Sync>;
+
+/// What [`ParallelDatasetWriter::close`] does if no table was written
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyDatasetBehavior {
+    /// Leaves the dataset directory empty
+    #[default]
+    LeaveEmpty,
+    /// Returns an error
+    ErrorIfEmpty,
+    /// Creates and closes a single writer, which writes a file without rows. For
+    /// formats with a schema (such as Parquet and Arrow IPC), this file carries the
+    /// schema so readers can still infer it.
+    EmitEmptyFile,
+}
+
+/// One level of partitioning of a dataset, see [`TableWriter::partitioning`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionLayout {
+    /// Name of the partition column. Partitions are written to `<column>=<key>`
+    /// subdirectories (Hive-style).
+    pub column: String,
+    /// Type of partition keys, either `"u16"` or `"utf8"`
+    pub key_type: &'static str,
+}
+
+/// Returns the total size of all files in `path` and its subdirectories, such as a
+/// dataset written by [`ParallelDatasetWriter`] (including partitions, rotated files,
+/// and sidecar files).
+pub fn dataset_size_on_disk(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in
+        std::fs::read_dir(path).with_context(|| format!("Could not list {}", path.display()))?
+    {
+        let entry = entry.with_context(|| format!("Could not list {}", path.display()))?;
+        let metadata = entry
+            .metadata()
+            .with_context(|| format!("Could not get size of {}", entry.path().display()))?;
+        if metadata.is_dir() {
+            size += dataset_size_on_disk(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Returns a string which is unique to this process, suitable for
+/// [`ParallelDatasetWriter::file_name_prefix`]
+///
+/// It is made of the current time and the process id.
+pub fn unique_file_name_prefix() -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{:x}p{}", timestamp, std::process::id())
 }
 
 impl<W: TableWriter<Schema = ()> + Send> ParallelDatasetWriter<W>
@@ -74,7 +415,19 @@ where
             schema: (),
             path,
             writers: ThreadLocal::new(),
+            thread_usage: ThreadLocal::new(),
             config: W::Config::default(),
+            best_effort: false,
+            files_per_dir: None,
+            file_name_prefix: None,
+            flush_largest_first: false,
+            write_common_metadata: false,
+            track_threads: false,
+            empty_dataset_behavior: EmptyDatasetBehavior::default(),
+            write_layout_marker: false,
+            parallel_chunk_size: None,
+            max_buffered_bytes: None,
+            finalizer: None,
         })
     }
 }
@@ -91,53 +444,320 @@ where
             schema,
             path,
             writers: ThreadLocal::new(),
+            thread_usage: ThreadLocal::new(),
             config: W::Config::default(),
+            best_effort: false,
+            files_per_dir: None,
+            file_name_prefix: None,
+            flush_largest_first: false,
+            write_common_metadata: false,
+            track_threads: false,
+            empty_dataset_behavior: EmptyDatasetBehavior::default(),
+            write_layout_marker: false,
+            parallel_chunk_size: None,
+            max_buffered_bytes: None,
+            finalizer: None,
         })
     }
 
-    fn get_new_seq_writer(&self) -> Result<RefCell<W>> {
-        let path = self
-            .path
-            .join(self.num_files.fetch_add(1, Ordering::Relaxed).to_string());
-        Ok(RefCell::new(W::new(
+    /// Sets a function to be called at the end of [`close`](Self::close), once all
+    /// files are closed, with the results of closing each writer.
+    ///
+    /// It is not called if closing fails, nor when the dataset writer is dropped without
+    /// calling [`close`](Self::close). Its error, if any, is returned by
+    /// [`close`](Self::close).
+    pub fn with_finalizer(
+        mut self,
+        finalizer: impl FnOnce(&[W::CloseResult]) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.finalizer = Some(Box::new(finalizer));
+        self
+    }
+
+    /// Returns an error if the filesystem containing the dataset has less than
+    /// `required_bytes` bytes available.
+    ///
+    /// This is only a pre-flight check meant to be called before writing; available space
+    /// is not checked while writing.
+    pub fn check_free_space(&self, required_bytes: u64) -> Result<()> {
+        let available_bytes = fs4::available_space(&self.path).with_context(|| {
+            format!("Could not get available space for {}", self.path.display())
+        })?;
+        ensure!(
+            available_bytes >= required_bytes,
+            "{} has only {} bytes available, but {} are required",
+            self.path.display(),
+            available_bytes,
+            required_bytes
+        );
+        Ok(())
+    }
+
+    fn get_new_seq_writer(&self) -> Result<SeqWriter<W>> {
+        let id = self.num_files.fetch_add(1, Ordering::Relaxed);
+        if self.track_threads {
+            self.thread_usage.get_or(|| WriterThreadUsage {
+                writer_id: id,
+                calls_by_thread: Mutex::new(HashMap::new()),
+            });
+        }
+        let file_name = match &self.file_name_prefix {
+            None => id.to_string(),
+            Some(prefix) => format!("{}-{}", prefix, id),
+        };
+        let path = match self.files_per_dir {
+            None => self.path.join(file_name),
+            Some(files_per_dir) => {
+                ensure!(files_per_dir > 0, "files_per_dir must be positive");
+                ensure!(
+                    W::partitioning(&self.schema).is_empty(),
+                    "files_per_dir is not supported by partitioned writers"
+                );
+                let dir = self.path.join(format!("{:04}", id / files_per_dir));
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Could not create {}", dir.display()))?;
+                dir.join(file_name)
+            }
+        };
+        Ok(SeqWriter {
+            writer: Mutex::new(W::new(
+                path.clone(),
+                self.schema.clone(),
+                self.config.clone(),
+            )?),
             path,
-            self.schema.clone(),
-            self.config.clone(),
-        )?))
+        })
     }
 
     /// Returns a new sequential writer.
     ///
-    /// # Panics
+    /// If [`max_buffered_bytes`](Self::max_buffered_bytes) is exceeded, first flushes the
+    /// writer with the largest buffer.
+    ///
+    /// # Deadlocks
     ///
     /// When called from a thread holding another reference to a sequential writer
     /// of this dataset.
-    pub fn get_thread_writer(&self) -> Result<RefMut<W>> {
-        self.writers
-            .get_or_try(|| self.get_new_seq_writer())
-            .map(|writer| writer.borrow_mut())
+    pub fn get_thread_writer(&self) -> Result<MutexGuard<'_, W>> {
+        if let Some(max_buffered_bytes) = self.max_buffered_bytes {
+            self.flush_largest_if_above(max_buffered_bytes)?;
+        }
+        let writer = self
+            .writers
+            .get_or_try(|| self.get_new_seq_writer())?
+            .writer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = self.thread_usage.get() {
+            let thread = std::thread::current();
+            usage
+                .calls_by_thread
+                .lock()
+                .unwrap()
+                .entry(thread.id())
+                .or_insert_with(|| (thread.name().map(ToOwned::to_owned), 0))
+                .1 += 1;
+        }
+        Ok(writer)
+    }
+
+    /// Returns which threads used each writer, and how often, sorted by writer id.
+    ///
+    /// Only one thread uses a writer at any time, but a writer may be reused by a new
+    /// thread after the thread which used it exits.
+    ///
+    /// Empty unless [`track_threads`](Self::track_threads) is `true`.
+    pub fn thread_diagnostics(&self) -> Vec<ThreadDiagnostics> {
+        let mut diagnostics: Vec<_> = self
+            .thread_usage
+            .iter()
+            .flat_map(|usage| {
+                usage
+                    .calls_by_thread
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(thread_id, (thread_name, calls))| ThreadDiagnostics {
+                        writer_id: usage.writer_id,
+                        thread_id: *thread_id,
+                        thread_name: thread_name.clone(),
+                        get_thread_writer_calls: *calls,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        diagnostics.sort_by_key(|diagnostics| diagnostics.writer_id);
+        diagnostics
+    }
+
+    /// Flushes the writer with the largest buffer if the total buffer size of all writers
+    /// is above `max_buffered_bytes`, skipping writers currently in use.
+    fn flush_largest_if_above(&self, max_buffered_bytes: usize) -> Result<()> {
+        let mut total_buffer_size = 0;
+        let mut largest = None;
+        for writer in self.writers.iter() {
+            let Ok(guard) = writer.writer.try_lock() else {
+                continue;
+            };
+            let buffer_size = guard.buffer_size();
+            total_buffer_size += buffer_size;
+            if largest.is_none_or(|(_, largest_size)| buffer_size > largest_size) {
+                largest = Some((writer, buffer_size));
+            }
+        }
+        let Some((writer, _)) = largest else {
+            return Ok(());
+        };
+        if total_buffer_size <= max_buffered_bytes {
+            return Ok(());
+        }
+        // The writer may have been taken by its thread since we released it
+        let Ok(mut guard) = writer.writer.try_lock() else {
+            return Ok(());
+        };
+        catch_writer_panic(&writer.path, "flushing", || guard.flush())
+    }
+
+    /// Returns all underlying writers, in the order they should be flushed
+    fn writers_in_flush_order(&mut self) -> Vec<&mut SeqWriter<W>> {
+        let mut writers: Vec<_> = self.writers.iter_mut().collect();
+        if self.flush_largest_first {
+            writers.sort_by_key(|writer| {
+                std::cmp::Reverse(
+                    writer
+                        .writer
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .buffer_size(),
+                )
+            });
+        }
+        writers
     }
 
     /// Flushes all underlying writers
     pub fn flush(&mut self) -> Result<()> {
-        self.writers
-            .iter_mut()
-            .collect::<Vec<_>>()
+        let parallel_chunk_size = self.parallel_chunk_size.unwrap_or(1);
+        self.writers_in_flush_order()
             .into_par_iter()
-            .map(|writer| writer.get_mut().flush())
+            .with_min_len(parallel_chunk_size)
+            .map(|SeqWriter { path, writer }| {
+                let writer = writer.get_mut().unwrap_or_else(|e| e.into_inner());
+                catch_writer_panic(path, "flushing", || writer.flush())
+            })
             .collect::<Result<Vec<()>>>()
             .map(|_: Vec<()>| ())
     }
 
+    /// Flushes all underlying writers, and returns how much data this flush wrote.
+    pub fn flush_stats(&mut self) -> Result<Statistics> {
+        let parallel_chunk_size = self.parallel_chunk_size.unwrap_or(1);
+        self.writers_in_flush_order()
+            .into_par_iter()
+            .with_min_len(parallel_chunk_size)
+            .map(|SeqWriter { path, writer }| {
+                let writer = writer.get_mut().unwrap_or_else(|e| e.into_inner());
+                catch_writer_panic(path, "flushing", || {
+                    let (rows_before, bytes_before) =
+                        (writer.written_rows(), writer.written_bytes());
+                    writer.flush()?;
+                    Ok(Statistics {
+                        rows: writer.written_rows() - rows_before,
+                        bytes: writer.written_bytes() - bytes_before,
+                    })
+                })
+            })
+            .sum()
+    }
+
     /// Closes all underlying writers
     pub fn close(mut self) -> Result<Vec<W::CloseResult>> {
+        let results = self.close_with(|_writer| ())?;
+        Ok(results.into_iter().map(|((), result)| result).collect())
+    }
+
+    /// Same as [`close`](Self::close), but also calls `before_close` on each writer
+    /// just before closing it, and returns what it returned along with the writer's
+    /// close result.
+    ///
+    /// As with `close`, writers which could not be closed are omitted if
+    /// [`best_effort`](Self::best_effort) is set.
+    fn close_with<T: Send>(
+        &mut self,
+        before_close: impl Fn(&mut W) -> T + Sync,
+    ) -> Result<Vec<(T, W::CloseResult)>> {
         let mut tmp = ThreadLocal::new();
         std::mem::swap(&mut tmp, &mut self.writers);
-        tmp.into_iter()
-            .collect::<Vec<_>>()
+        let mut writers = tmp.into_iter().collect::<Vec<_>>();
+        if writers.is_empty() {
+            match self.empty_dataset_behavior {
+                EmptyDatasetBehavior::LeaveEmpty => {}
+                EmptyDatasetBehavior::ErrorIfEmpty => {
+                    bail!("No table was written to {}", self.path.display())
+                }
+                EmptyDatasetBehavior::EmitEmptyFile => writers.push(self.get_new_seq_writer()?),
+            }
+        }
+        let results = writers
             .into_par_iter()
-            .map(|writer| writer.into_inner().close())
-            .collect()
+            .with_min_len(self.parallel_chunk_size.unwrap_or(1))
+            .map(|writer| {
+                let (path, mut writer) = writer.into_parts();
+                let before_close_result = before_close(&mut writer);
+                catch_writer_panic(&path, "closing", || writer.close())
+                    .map(|result| (before_close_result, result))
+            });
+        let results: Vec<_> = if self.best_effort {
+            results
+                .filter_map(|result| {
+                    result
+                        .inspect_err(|e| tracing::warn!("Could not close table writer: {:#}", e))
+                        .ok()
+                })
+                .collect()
+        } else {
+            results.collect::<Result<_>>()?
+        };
+        if self.write_common_metadata {
+            W::write_common_metadata(
+                &self.path.join("_common_metadata"),
+                &self.schema,
+                &self.config,
+            )?;
+        }
+        if self.write_layout_marker {
+            self.write_layout_marker_file()?;
+        }
+        if let Some(finalizer) = self.finalizer.take() {
+            let (before_close_results, results): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+            finalizer(&results)?;
+            return Ok(before_close_results.into_iter().zip(results).collect());
+        }
+        Ok(results)
+    }
+
+    /// Writes the `_layout_version` file, see
+    /// [`write_layout_marker`](Self::write_layout_marker)
+    fn write_layout_marker_file(&self) -> Result<()> {
+        let partitioning: Vec<_> = W::partitioning(&self.schema)
+            .into_iter()
+            .map(|PartitionLayout { column, key_type }| {
+                serde_json::json!({"column": column, "key_type": key_type, "scheme": "hive"})
+            })
+            .collect();
+        let marker = serde_json::json!({
+            "crate": "dataset-writer",
+            "crate_version": env!("CARGO_PKG_VERSION"),
+            "partitioning": partitioning,
+            "file_naming": {
+                "scheme": "sequential",
+                "prefix": self.file_name_prefix,
+                "files_per_dir": self.files_per_dir,
+            },
+        });
+        let path = self.path.join("_layout_version");
+        std::fs::write(&path, marker.to_string() + "\n")
+            .with_context(|| format!("Could not write {}", path.display()))
     }
 }
 
@@ -145,11 +765,27 @@ impl<W: TableWriter + Send> Drop for ParallelDatasetWriter<W> {
     fn drop(&mut self) {
         let mut tmp = ThreadLocal::new();
         std::mem::swap(&mut tmp, &mut self.writers);
-        tmp.into_iter()
+        let writers = tmp
+            .into_iter()
             .collect::<Vec<_>>()
             .into_par_iter()
-            .try_for_each(|writer| writer.into_inner().close().map(|_| ()))
-            .expect("Could not close ParallelDatasetWriter");
+            .with_min_len(self.parallel_chunk_size.unwrap_or(1));
+        if self.best_effort {
+            writers.for_each(|writer| {
+                let (path, writer) = writer.into_parts();
+                let result = catch_writer_panic(&path, "closing", || writer.close());
+                if let Err(e) = result {
+                    tracing::warn!("Could not close table writer: {:#}", e);
+                }
+            });
+        } else {
+            writers
+                .try_for_each(|writer| {
+                    let (path, writer) = writer.into_parts();
+                    catch_writer_panic(&path, "closing", || writer.close().map(|_| ()))
+                })
+                .expect("Could not close ParallelDatasetWriter");
+        }
     }
 }
 
@@ -162,8 +798,73 @@ pub trait TableWriter {
     where
         Self: Sized;
 
+    /// Returns `schema` with `key` set to `value` in the metadata of the files written
+    /// with it.
+    ///
+    /// Returns `schema` unchanged for formats without schema metadata.
+    fn schema_with_metadata(schema: Self::Schema, _key: &str, _value: &str) -> Self::Schema
+    where
+        Self: Sized,
+    {
+        schema
+    }
+
+    /// Writes a file at `path` with the schema and metadata of the files written with
+    /// this `schema` and `config`, but no data.
+    ///
+    /// Does nothing for formats without a schema.
+    fn write_common_metadata(
+        _path: &Path,
+        _schema: &Self::Schema,
+        _config: &Self::Config,
+    ) -> Result<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
+    /// Returns how files written with this `schema` are partitioned into
+    /// subdirectories, outermost partition first.
+    ///
+    /// Empty for writers which do not partition rows.
+    fn partitioning(_schema: &Self::Schema) -> Vec<PartitionLayout>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
     /// Calls `.into()` on the internal builder, and writes its result to disk.
     fn flush(&mut self) -> Result<()>;
 
     fn close(self) -> Result<Self::CloseResult>;
+
+    /// Number of rows written to disk so far, not including rows still in the buffer.
+    ///
+    /// Returns 0 for writers which do not keep track of it.
+    fn written_rows(&self) -> u64 {
+        0
+    }
+
+    /// Number of bytes written to disk so far, not including rows still in the buffer.
+    ///
+    /// Returns 0 for writers which do not keep track of it.
+    fn written_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Sets `key` to `value` in the metadata of the file being written, if the format
+    /// allows it, and of the files this writer creates afterwards.
+    ///
+    /// Does nothing for formats without file metadata.
+    fn set_file_metadata(&mut self, _key: &str, _value: &str) {}
+
+    /// Number of bytes buffered in memory, which the next call to
+    /// [`flush`](Self::flush) writes.
+    ///
+    /// Returns 0 for writers which do not keep track of it.
+    fn buffer_size(&self) -> usize {
+        0
+    }
 }
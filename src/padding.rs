@@ -0,0 +1,87 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use anyhow::{bail, Result};
+use arrow::array::builder::*;
+use arrow::datatypes::*;
+
+/// Appends nulls to each column builder shorter than the longest one, so they all
+/// have the same length.
+///
+/// This is meant to be called by implementations of
+/// [`StructArrayBuilder::finish_row`](crate::StructArrayBuilder::finish_row), so
+/// optional columns which were not written in the current row are null, instead of
+/// shifting all following values of the column by one row (and making
+/// [`finish`](crate::StructArrayBuilder::finish) fail because of unequal lengths).
+///
+/// Supports builders of primitive types, booleans, strings, binaries (including
+/// fixed-size and views) and nulls; returns an error if a builder of another type
+/// needs padding.
+pub fn pad_with_nulls(columns: &mut [&mut dyn ArrayBuilder]) -> Result<()> {
+    let len = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+    for (i, column) in columns.iter_mut().enumerate() {
+        let missing = len - column.len();
+        if missing > 0 && !append_nulls(*column, missing) {
+            bail!("Cannot append nulls to column builder {}", i);
+        }
+    }
+    Ok(())
+}
+
+/// Appends `n` nulls to the builder. Returns `false` if its type is not supported.
+fn append_nulls(builder: &mut dyn ArrayBuilder, n: usize) -> bool {
+    let builder = builder.as_any_mut();
+    macro_rules! append_nulls_to {
+        ($($builder_type:ty),* $(,)?) => {
+            $(
+                if let Some(builder) = builder.downcast_mut::<$builder_type>() {
+                    for _ in 0..n {
+                        builder.append_null();
+                    }
+                    return true;
+                }
+            )*
+        };
+    }
+    append_nulls_to!(
+        NullBuilder,
+        BooleanBuilder,
+        PrimitiveBuilder<Int8Type>,
+        PrimitiveBuilder<Int16Type>,
+        PrimitiveBuilder<Int32Type>,
+        PrimitiveBuilder<Int64Type>,
+        PrimitiveBuilder<UInt8Type>,
+        PrimitiveBuilder<UInt16Type>,
+        PrimitiveBuilder<UInt32Type>,
+        PrimitiveBuilder<UInt64Type>,
+        PrimitiveBuilder<Float16Type>,
+        PrimitiveBuilder<Float32Type>,
+        PrimitiveBuilder<Float64Type>,
+        PrimitiveBuilder<Decimal128Type>,
+        PrimitiveBuilder<Decimal256Type>,
+        PrimitiveBuilder<Date32Type>,
+        PrimitiveBuilder<Date64Type>,
+        PrimitiveBuilder<Time32SecondType>,
+        PrimitiveBuilder<Time32MillisecondType>,
+        PrimitiveBuilder<Time64MicrosecondType>,
+        PrimitiveBuilder<Time64NanosecondType>,
+        PrimitiveBuilder<TimestampSecondType>,
+        PrimitiveBuilder<TimestampMillisecondType>,
+        PrimitiveBuilder<TimestampMicrosecondType>,
+        PrimitiveBuilder<TimestampNanosecondType>,
+        PrimitiveBuilder<DurationSecondType>,
+        PrimitiveBuilder<DurationMillisecondType>,
+        PrimitiveBuilder<DurationMicrosecondType>,
+        PrimitiveBuilder<DurationNanosecondType>,
+        StringBuilder,
+        LargeStringBuilder,
+        StringViewBuilder,
+        BinaryBuilder,
+        LargeBinaryBuilder,
+        BinaryViewBuilder,
+        FixedSizeBinaryBuilder,
+    );
+    false
+}
@@ -3,21 +3,156 @@
 // License: GNU General Public License version 3, or any later version
 // See top-level LICENSE file for more information
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 
-use arrow::datatypes::Schema;
-use parquet::arrow::ArrowWriter as ParquetWriter;
-use parquet::file::properties::WriterProperties;
-use parquet::file::metadata::ParquetMetaData;
+use arrow::array::{
+    new_empty_array, Array, ArrayRef, AsArray, RecordBatch, StringBuilder, StructArray,
+    UInt64Builder,
+};
+use arrow::compute::{
+    concat, lexsort_to_indices, partition, sort_to_indices, take, SortColumn, SortOptions,
+};
+use arrow::datatypes::{DataType, Field, Fields, Schema};
+use arrow::row::{RowConverter, SortField};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
 pub use parquet;
+use parquet::arrow::arrow_writer::ArrowWriterOptions;
+use parquet::arrow::{ArrowSchemaConverter, ArrowWriter as ParquetWriter};
+use parquet::basic::{Compression, LogicalType, Type as PhysicalType};
+use parquet::file::metadata::{KeyValue, ParquetMetaData, RowGroupMetaData, SortingColumn};
+use parquet::file::properties::{BloomFilterPosition, EnabledStatistics, WriterProperties};
+use parquet::schema::types::{ColumnPath, SchemaDescriptor, Type};
+use roaring::RoaringBitmap;
 
-use super::{StructArrayBuilder, TableWriter};
+use super::{
+    filter_rows, ParallelDatasetWriter, RowFilter, StructArrayBuilder, StructArrayTableWriter,
+    TableWriter,
+};
 
-#[derive(Debug, Default, Clone)]
+/// How [`ParquetTableWriter`] computes statistics of floating-point columns containing
+/// NaN
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NanStatsPolicy {
+    /// NaN values are ignored when computing min/max statistics, so they only reflect
+    /// other values (and are absent if all values are NaN).
+    ///
+    /// This is what the `parquet` crate does by default, so this leaves statistics
+    /// unchanged.
+    #[default]
+    Ignore,
+    /// Statistics are disabled for all floating-point columns.
+    ///
+    /// As whether a column contains NaN is not known when a file is created, this
+    /// applies even to columns without NaN.
+    DisableFloatStatistics,
+}
+
+/// Returns the compression codec of a file, given its index among the files written by
+/// the same [`ParquetTableWriter`] (0 for the first one, then 1 after it is rotated,
+/// etc.)
+pub type CompressionForFile = Arc<dyn Fn(u64) -> Compression + Send + Sync>;
+
+/// Called by [`ParquetTableWriter`] with the path of each file it creates, right after
+/// creating it
+pub type OnFileOpened = Arc<dyn Fn(&Path) + Send + Sync>;
+
+/// Called by [`ParquetTableWriter`] with the path of each file it closes (including when
+/// rotating), once the file and its sidecars are written, see
+/// [`ParquetTableWriterConfig::on_file_closed`]
+pub type OnFileClosed = Arc<dyn Fn(&Path, FileSlot) + Send + Sync>;
+
+/// Slot of a pool of file paths (see [`ParquetTableWriterConfig::file_pool_size`])
+/// occupied by a closed file. The slot is freed when this is dropped, so the writer may
+/// then overwrite the file.
+pub struct FileSlot {
+    index: u64,
+    /// `None` if there is no pool
+    pool: Option<Arc<FilePool>>,
+}
+
+impl FileSlot {
+    /// Index of the slot in the pool. Without a pool, this is the index of the file
+    /// among those written by the same writer.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+}
+
+impl std::fmt::Debug for FileSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileSlot")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for FileSlot {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            let mut slots_in_use = pool.slots_in_use.lock().unwrap_or_else(|e| e.into_inner());
+            slots_in_use[usize::try_from(self.index).expect("slot index overflowed usize")] = false;
+            pool.slot_freed.notify_all();
+        }
+    }
+}
+
+/// State of the file slots of a [`ParquetTableWriter`], see
+/// [`ParquetTableWriterConfig::file_pool_size`]
+struct FilePool {
+    slots_in_use: Mutex<Vec<bool>>,
+    slot_freed: Condvar,
+}
+
+impl FilePool {
+    /// Waits until the slot is free, then marks it as in use
+    fn acquire(&self, index: u64) {
+        let index = usize::try_from(index).expect("slot index overflowed usize");
+        let mut slots_in_use = self
+            .slot_freed
+            .wait_while(
+                self.slots_in_use.lock().unwrap_or_else(|e| e.into_inner()),
+                |slots_in_use| slots_in_use[index],
+            )
+            .unwrap_or_else(|e| e.into_inner());
+        slots_in_use[index] = true;
+    }
+}
+
+/// Prefix of the footer metadata keys written by [`ParquetTableWriter::flush_and_mark`]
+pub const MARK_METADATA_KEY_PREFIX: &str = "dataset_writer.mark.";
+
+/// Suffix appended to the path of a file to get the path of the sidecar written by
+/// [`ParquetTableWriter::flush_with_metadata`]
+pub const ROW_GROUP_METADATA_SIDECAR_SUFFIX: &str = ".row_group_metadata.tsv";
+
+/// Suffix appended to the path of the first file of a [`ParquetTableWriter`] to get the
+/// path of its index, see [`ParquetTableWriterConfig::index_column`]
+pub const INDEX_SIDECAR_SUFFIX: &str = ".index";
+
+/// Suffix appended to the path of a file to get the path of its row group sidecar, see
+/// [`ParquetTableWriterConfig::row_groups_sidecar`]
+pub const ROW_GROUPS_SIDECAR_SUFFIX: &str = ".rowgroups";
+
+/// Suffix appended to `<file>.<column>` to get the path of the bitmap index of a column
+/// of a file, see [`ParquetTableWriterConfig::bitmap_index_columns`]
+pub const BITMAP_INDEX_SIDECAR_SUFFIX: &str = ".bitmap";
+
+/// Suffix appended to the path of the first file of a [`ParquetTableWriter`] to get the
+/// path of its schema sidecar, see [`ParquetTableWriterConfig::eager_file_creation`]
+pub const SCHEMA_SIDECAR_SUFFIX: &str = ".schema";
+
+/// Parquet does not support more than 32767 row groups per file; keep a small margin.
+const MAX_ROW_GROUPS_PER_FILE: usize = (i16::MAX - 2) as usize;
+
+#[derive(Default, Clone)]
 pub struct ParquetTableWriterConfig {
     /// Automatically flushes the builder to disk when its length (in number of rows)
     /// reaches the value.
@@ -35,22 +170,403 @@ pub struct ParquetTableWriterConfig {
     ///
     /// Does not automatically flush on size if `None`
     pub autoflush_buffer_size: Option<usize>,
+    /// Never automatically flushes the builder to disk while it has fewer rows than
+    /// this value, even if [`autoflush_buffer_size`](Self::autoflush_buffer_size) is
+    /// reached, to avoid writing tiny row groups.
+    ///
+    /// This does not apply to explicit flushes, nor when closing the writer.
+    pub min_flush_rows: Option<usize>,
+    /// Maximum length of min/max values stored in column statistics, see
+    /// [`set_statistics_truncate_length`](parquet::file::properties::WriterPropertiesBuilder::set_statistics_truncate_length).
+    ///
+    /// Uses the value from the [`WriterProperties`] if `None`.
+    pub statistics_truncate_length: Option<usize>,
+    /// If `true`, [`ParquetTableWriter::builder`] never flushes automatically; data is
+    /// only written on explicit flushes and when closing.
+    ///
+    /// Combined with [`ParquetTableWriter::abort`], this allows discarding everything
+    /// written so far.
+    pub manual_commit: bool,
+    /// Columns to sort each flushed batch by, so every row group is sorted, though the
+    /// file as a whole is not.
+    ///
+    /// These columns are also recorded as the row groups'
+    /// [`sorting_columns`](WriterProperties::sorting_columns). They must be top-level
+    /// columns.
+    pub sort_within_flush: Vec<(String, SortOptions)>,
+    /// Maximum number of files each writer may write (by opening a new file when the
+    /// current one has too many row groups).
+    ///
+    /// Once the last file is full, [`ParquetTableWriter::builder`] returns an error.
+    /// Unlimited if `None`.
+    pub max_files_per_writer: Option<u64>,
+    /// Logical types to set on top-level columns of the Parquet schema, instead of the
+    /// ones derived from the Arrow schema, eg. [`LogicalType::Uuid`] for
+    /// `FixedSizeBinary(16)` columns.
+    ///
+    /// [`ParquetTableWriter::new`] returns an error if a logical type is not compatible
+    /// with the column's physical type.
+    pub column_logical_types: Vec<(String, LogicalType)>,
+    /// How statistics of floating-point columns handle NaN values
+    pub float_nan_stats: NanStatsPolicy,
+    /// If set, each flush is split into one row group per run of consecutive equal
+    /// values of this top-level column, so no row group contains two distinct values of
+    /// the column.
+    ///
+    /// This improves pruning on a sorted column, at the cost of uneven (and possibly
+    /// very small) row groups if the column has many distinct values.
+    pub row_group_boundary_column: Option<String>,
+    /// If set, called when creating each file to choose its compression codec,
+    /// overriding the one in the [`WriterProperties`].
+    pub compression_for_file: Option<CompressionForFile>,
+    /// Where bloom filters (if enabled in the [`WriterProperties`]) are written, see
+    /// [`set_bloom_filter_position`](parquet::file::properties::WriterPropertiesBuilder::set_bloom_filter_position).
+    ///
+    /// Uses the value from the [`WriterProperties`] if `None`.
+    pub bloom_filter_position: Option<BloomFilterPosition>,
+    /// Hard cap on the number of rows in the builder: [`ParquetTableWriter::builder`]
+    /// returns an error if the builder holds more rows than this, for example because
+    /// a caller appended a huge number of rows to it at once.
+    ///
+    /// Unlike automatic flushes, this is checked even with
+    /// [`manual_commit`](Self::manual_commit). Unlimited if `None`.
+    pub max_builder_rows: Option<usize>,
+    /// Statistics level of some top-level columns, overriding the one in the
+    /// [`WriterProperties`] (and [`float_nan_stats`](Self::float_nan_stats)), eg. to
+    /// disable statistics of large binary columns to keep footers small.
+    pub statistics_enabled_per_column: Vec<(String, EnabledStatistics)>,
+    /// Compression codec of some top-level columns, overriding the one in the
+    /// [`WriterProperties`] (and [`compression_for_file`](Self::compression_for_file)),
+    /// eg. to skip compression of columns of hashes, which do not compress.
+    pub column_compression: Vec<(String, Compression)>,
+    /// Number of rows the Parquet writer encodes at once within a row group, see
+    /// [`set_write_batch_size`](parquet::file::properties::WriterPropertiesBuilder::set_write_batch_size).
+    /// This bounds memory use while writing, independently of the row group size.
+    ///
+    /// Uses the value from the [`WriterProperties`] if `None`.
+    pub write_batch_size: Option<usize>,
+    /// If set, the values of this top-level column are used as keys to build an index
+    /// of all rows written by the writer, which is written when closing it, next to
+    /// the first file, with [`INDEX_SIDECAR_SUFFIX`] appended to its path.
+    ///
+    /// The index is a Parquet file sorted by key, with columns `key` (of the same type
+    /// as the indexed column), `file` (the name of the data file, which is in the same
+    /// directory as the index), `row_group` (the index of the row group in that file),
+    /// and `row_offset` (the index of the row in that row group).
+    ///
+    /// All keys are kept in memory until the writer is closed.
+    pub index_column: Option<String>,
+    /// If `true`, each file is accompanied by a sidecar with
+    /// [`ROW_GROUPS_SIDECAR_SUFFIX`] appended to its path, to which a line is appended
+    /// after each row group is written, so custom readers can read row groups of files
+    /// which are still being written (and thus have no footer yet).
+    ///
+    /// Each line is made of tab-separated integers: the index of the row group, its
+    /// number of rows, then the byte offset and length in the data file of each of its
+    /// column chunks, in the order of leaf columns of the Parquet schema.
+    ///
+    /// A line is only appended once the row group is written to the data file (though
+    /// not necessarily synced to disk), so readers can decode the pages in these byte
+    /// ranges with the schema of the dataset. Readers should ignore a last line without
+    /// a trailing newline, as it may still be being written. Once the data file is
+    /// closed, its footer has the same information and the sidecar is no longer needed.
+    pub row_groups_sidecar: bool,
+    /// If set, a new file is opened (like when a file has too many row groups) once
+    /// the estimated size of the current file's footer reaches this many bytes, to
+    /// keep footers fast to read.
+    ///
+    /// The estimate is the sum, over all column chunks of all row groups, of a fixed
+    /// overhead, the length of the column's path, and the size of its min/max
+    /// statistics. Like other rotations, this is limited by
+    /// [`max_files_per_writer`](Self::max_files_per_writer). Unlimited if `None`.
+    pub max_footer_estimate_bytes: Option<usize>,
+    /// If `true`, [`TableWriter::flush`] returns an error listing the differences if
+    /// the fields of the array returned by the builder differ from those returned by
+    /// the first flush, instead of the less helpful error Parquet returns when writing
+    /// it. This catches builders whose schema depends on their content (eg. a
+    /// nullable flag set only when a null was appended).
+    ///
+    /// Defaults to `true` in builds with debug assertions, and `false` otherwise,
+    /// if `None`.
+    pub check_schema_stability: Option<bool>,
+    /// Top-level columns to build bitmap indexes of. When closing each file, a
+    /// `<file>.<column>.bitmap` sidecar (see [`BITMAP_INDEX_SIDECAR_SUFFIX`]) is written
+    /// for each of these columns, with the positions in the file of the rows having
+    /// each distinct value of the column.
+    ///
+    /// The sidecar is a sequence of entries, sorted by value, each made of:
+    ///
+    /// * the length of the value as a little-endian `u32`, or `u32::MAX` for nulls
+    /// * the value, as formatted by [`ArrayFormatter`] (eg. `true` or `42`), in UTF-8
+    /// * the length of the bitmap as a little-endian `u32`
+    /// * a [`RoaringBitmap`] of row positions, in the portable serialization format
+    ///   (see [`RoaringBitmap::deserialize_from`])
+    ///
+    /// All bitmaps of the current file are kept in memory, so this is only suitable
+    /// for low-cardinality columns. Files may not have more than `u32::MAX` rows.
+    pub bitmap_index_columns: Vec<String>,
+    /// If `true`, each file is renamed when it is closed to include its number of rows,
+    /// so `<name>.parquet` becomes `<name>.r<rows>.parquet` (eg. `0.r12345.parquet`).
+    /// Its sidecars, if any, are named after the new path.
+    ///
+    /// [`ParquetTableWriter::created_files`] returns the new path of files once they
+    /// are closed.
+    pub encode_row_count_in_name: bool,
+    /// If set, closing the writer logs a warning listing the files it wrote whose
+    /// median number of rows per row group is below this value, as many small row
+    /// groups (eg. caused by flushing too often) make files slow to read.
+    pub warn_small_row_groups: Option<usize>,
+    /// If set, file paths are reused: the index of each file in its name (see
+    /// [`ParquetTableWriter`]) cycles through `0..file_pool_size` instead of increasing
+    /// forever, so a pipeline which uploads and deletes each file once it is closed
+    /// (using [`on_file_closed`](Self::on_file_closed)) uses a bounded amount of disk
+    /// space.
+    ///
+    /// Before creating a file, the writer blocks until the previous file with the same
+    /// path was released, ie. until the [`FileSlot`] passed to `on_file_closed` was
+    /// dropped. Files are not released until `on_file_closed` returns, if it is not set.
+    ///
+    /// [`ParquetTableWriter::created_files`] lists a path once for each time it was
+    /// used, so features which read files after closing the writer (such as
+    /// [`close_and_compact`](ParallelDatasetWriter::close_and_compact)) should not be
+    /// used if `on_file_closed` removes files.
+    pub file_pool_size: Option<u64>,
+    /// Called with the path of each file right after it is created
+    pub on_file_opened: Option<OnFileOpened>,
+    /// Called with the path of each file once it is closed, and its sidecars written,
+    /// including when the writer rotates to a new file. With
+    /// [`file_pool_size`](Self::file_pool_size), the path is only reused once the given
+    /// [`FileSlot`] is dropped, so this may move it to another thread (eg. one which
+    /// uploads then deletes the file).
+    pub on_file_closed: Option<OnFileClosed>,
+    /// If `true`, [`ParquetTableWriter::new`] writes a Parquet file with no rows and the
+    /// same schema as data files, next to the first file, with
+    /// [`SCHEMA_SIDECAR_SUFFIX`] appended to its path, so consumers polling the
+    /// directory can infer the schema before any data was written.
+    ///
+    /// The first file itself is created immediately in any case, but it has no valid
+    /// footer until it is closed, and a footer cannot be appended to. The sidecar is
+    /// removed once the first file is closed.
+    pub eager_file_creation: bool,
+    /// **Experimental**: if `true`, data files are written without the Arrow schema in
+    /// their key-value metadata (the `ARROW:schema` key), which saves footer space, and
+    /// [`TableWriter::write_common_metadata`] also writes the Arrow schema as
+    /// `schema.json`, next to `_common_metadata`, so it is only stored once per dataset
+    /// (with [`ParallelDatasetWriter::write_common_metadata`] set).
+    ///
+    /// Files are still valid Parquet files, but readers infer their Arrow schema from
+    /// the Parquet schema alone, which loses Arrow-specific types and metadata (such as
+    /// dictionaries, large or view types, timezones, and schema metadata). Readers
+    /// should therefore deserialize `schema.json` (eg. with `serde_json`, as
+    /// [`Schema`] implements `Deserialize`) and pass it to
+    /// [`ArrowReaderOptions::with_schema`](parquet::arrow::arrow_reader::ArrowReaderOptions::with_schema).
+    pub skip_arrow_schema: bool,
+    /// If set, called on the rows of each flush; only rows for which it returns `true`
+    /// are written.
+    ///
+    /// This only applies to rows written with the builder, not to arrays passed to
+    /// [`write_struct_array`](crate::StructArrayTableWriter::write_struct_array).
+    pub row_filter: Option<RowFilter>,
+    /// If set, the writer counts distinct values of each top-level non-nested column,
+    /// and enables bloom filters on columns with more distinct values than this in all
+    /// files it creates afterwards (in addition to those enabled in the
+    /// [`WriterProperties`]).
+    ///
+    /// Values are counted in all rows written so far, so the first file never gets
+    /// bloom filters from this; it is meant for writers which rotate files (eg. with
+    /// [`max_footer_estimate_bytes`](Self::max_footer_estimate_bytes)). Counting is
+    /// exact, using hashes of the values, and stops for each column once it reaches the
+    /// threshold, so memory use is bounded by the threshold.
+    pub auto_bloom_filter_cardinality: Option<usize>,
+}
+
+impl std::fmt::Debug for ParquetTableWriterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ParquetTableWriterConfig {
+            autoflush_row_group_len,
+            autoflush_buffer_size,
+            min_flush_rows,
+            statistics_truncate_length,
+            manual_commit,
+            sort_within_flush,
+            max_files_per_writer,
+            column_logical_types,
+            float_nan_stats,
+            row_group_boundary_column,
+            compression_for_file,
+            bloom_filter_position,
+            max_builder_rows,
+            statistics_enabled_per_column,
+            column_compression,
+            write_batch_size,
+            index_column,
+            row_groups_sidecar,
+            max_footer_estimate_bytes,
+            check_schema_stability,
+            bitmap_index_columns,
+            encode_row_count_in_name,
+            warn_small_row_groups,
+            file_pool_size,
+            on_file_opened,
+            on_file_closed,
+            eager_file_creation,
+            skip_arrow_schema,
+            row_filter,
+            auto_bloom_filter_cardinality,
+        } = self;
+        f.debug_struct("ParquetTableWriterConfig")
+            .field("autoflush_row_group_len", autoflush_row_group_len)
+            .field("autoflush_buffer_size", autoflush_buffer_size)
+            .field("min_flush_rows", min_flush_rows)
+            .field("statistics_truncate_length", statistics_truncate_length)
+            .field("manual_commit", manual_commit)
+            .field("sort_within_flush", sort_within_flush)
+            .field("max_files_per_writer", max_files_per_writer)
+            .field("column_logical_types", column_logical_types)
+            .field("float_nan_stats", float_nan_stats)
+            .field("row_group_boundary_column", row_group_boundary_column)
+            .field(
+                "compression_for_file",
+                &compression_for_file.as_ref().map(|_| "<callback>"),
+            )
+            .field("bloom_filter_position", bloom_filter_position)
+            .field("max_builder_rows", max_builder_rows)
+            .field(
+                "statistics_enabled_per_column",
+                statistics_enabled_per_column,
+            )
+            .field("column_compression", column_compression)
+            .field("write_batch_size", write_batch_size)
+            .field("index_column", index_column)
+            .field("row_groups_sidecar", row_groups_sidecar)
+            .field("max_footer_estimate_bytes", max_footer_estimate_bytes)
+            .field("check_schema_stability", check_schema_stability)
+            .field("bitmap_index_columns", bitmap_index_columns)
+            .field("encode_row_count_in_name", encode_row_count_in_name)
+            .field("warn_small_row_groups", warn_small_row_groups)
+            .field("file_pool_size", file_pool_size)
+            .field(
+                "on_file_opened",
+                &on_file_opened.as_ref().map(|_| "<callback>"),
+            )
+            .field(
+                "on_file_closed",
+                &on_file_closed.as_ref().map(|_| "<callback>"),
+            )
+            .field("eager_file_creation", eager_file_creation)
+            .field("skip_arrow_schema", skip_arrow_schema)
+            .field("row_filter", &row_filter.as_ref().map(|_| "<callback>"))
+            .field(
+                "auto_bloom_filter_cardinality",
+                auto_bloom_filter_cardinality,
+            )
+            .finish()
+    }
 }
 
 /// Writer to a .parquet file, usable with [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
 ///
 /// `Builder` should follow the pattern documented by
 /// [`arrow::builder`](https://docs.rs/arrow/latest/arrow/array/builder/index.html)
+///
+/// Each flush writes the content of the builder as a new row group; or as several row
+/// groups of [`max_row_group_size`](WriterProperties::max_row_group_size) rows (and
+/// a smaller last one) if the builder grew larger than that before being flushed.
 pub struct ParquetTableWriter<Builder: Default + StructArrayBuilder> {
     base_path: PathBuf,
     /// See [`ParquetTableWriterConfig::autoflush_row_group_len`]
     pub autoflush_row_group_len: usize,
     /// See [`ParquetTableWriterConfig::autoflush_buffer_size`]
     pub autoflush_buffer_size: Option<usize>,
+    /// See [`ParquetTableWriterConfig::min_flush_rows`]
+    pub min_flush_rows: usize,
+    /// See [`ParquetTableWriterConfig::manual_commit`]
+    pub manual_commit: bool,
+    sort_within_flush: Vec<(String, SortOptions)>,
+    row_group_boundary_column: Option<String>,
+    compression_for_file: Option<CompressionForFile>,
+    /// See [`ParquetTableWriterConfig::max_files_per_writer`]
+    pub max_files_per_writer: Option<u64>,
+    /// See [`ParquetTableWriterConfig::max_builder_rows`]
+    pub max_builder_rows: Option<usize>,
+    /// See [`ParquetTableWriterConfig::max_footer_estimate_bytes`]
+    pub max_footer_estimate_bytes: Option<usize>,
+    /// Estimated size of the footer of the current file, see
+    /// [`ParquetTableWriterConfig::max_footer_estimate_bytes`]
+    footer_estimate_bytes: usize,
+    /// Whether the current file is full and can't be rotated because of
+    /// `max_files_per_writer`
+    last_file_is_full: bool,
+    /// Metadata of the last file closed because it was full, see
+    /// [`rotate`](Self::rotate)
+    last_full_file_metadata: Option<ParquetMetaData>,
     schema: Arc<Schema>,
+    /// Parquet schema to use instead of the one derived from `schema`, if
+    /// `column_logical_types` is not empty
+    parquet_schema: Option<SchemaDescriptor>,
     properties: WriterProperties,
     file_writer: Option<(PathBuf, ParquetWriter<File>)>, // None only while initializing, and between .close() call and Drop
     num_written_files: u64,
+    /// Paths of all files opened by this writer, including the current one
+    created_files: Vec<PathBuf>,
+    /// Number of bytes in files which were already closed by [`Self::new_file_writer`]
+    num_bytes_in_closed_files: u64,
+    num_written_rows: u64,
+    /// Set by [`TableWriter::set_file_metadata`], and added to each new file
+    file_metadata: Vec<KeyValue>,
+    /// See [`ParquetTableWriter::flush_and_mark`]
+    marks: Vec<(String, u64)>,
+    /// Metadata to attach to row groups written by the ongoing
+    /// [`ParquetTableWriter::flush_with_metadata`] call
+    next_row_group_metadata: Vec<(String, String)>,
+    /// Metadata attached to row groups of the current file, by row group index
+    row_group_metadata: Vec<(usize, Vec<(String, String)>)>,
+    index_column: Option<String>,
+    /// Keys of each row group written so far, with the index of their file in
+    /// `created_files` and of the row group in that file. Only filled if
+    /// `index_column` is set.
+    index_entries: Vec<(ArrayRef, usize, u64)>,
+    row_groups_sidecar: bool,
+    /// See [`ParquetTableWriterConfig::check_schema_stability`]
+    pub check_schema_stability: bool,
+    /// Fields of the array returned by the builder on the first flush, if
+    /// `check_schema_stability` is `true`
+    first_flush_fields: Option<Fields>,
+    /// See [`ParquetTableWriterConfig::bitmap_index_columns`]
+    bitmap_index_columns: Vec<String>,
+    /// Row positions of each value of each column of `bitmap_index_columns`, in the
+    /// current file
+    bitmap_indexes: Vec<BTreeMap<Option<String>, RoaringBitmap>>,
+    encode_row_count_in_name: bool,
+    /// See [`ParquetTableWriterConfig::warn_small_row_groups`]
+    warn_small_row_groups: Option<usize>,
+    /// Closed files whose median row group size is below `warn_small_row_groups`, with
+    /// that median
+    files_with_small_row_groups: Vec<(PathBuf, i64)>,
+    /// See [`ParquetTableWriterConfig::file_pool_size`]
+    file_pool_size: Option<u64>,
+    /// Only set if `file_pool_size` is
+    file_pool: Option<Arc<FilePool>>,
+    /// Index of the current file in its name: the same as `num_written_files`, unless
+    /// `file_pool_size` is set
+    current_file_index: u64,
+    on_file_opened: Option<OnFileOpened>,
+    on_file_closed: Option<OnFileClosed>,
+    /// Path of the schema sidecar, until it is removed, see
+    /// [`ParquetTableWriterConfig::eager_file_creation`]
+    schema_sidecar_path: Option<PathBuf>,
+    /// See [`ParquetTableWriterConfig::skip_arrow_schema`]
+    skip_arrow_schema: bool,
+    row_filter: Option<RowFilter>,
+    /// See [`ParquetTableWriterConfig::auto_bloom_filter_cardinality`]
+    auto_bloom_filter_cardinality: Option<usize>,
+    /// Hashes of the distinct values seen so far of each column which may still reach
+    /// `auto_bloom_filter_cardinality`
+    distinct_value_hashes: BTreeMap<String, HashSet<u64>>,
+    /// Columns which reached `auto_bloom_filter_cardinality`
+    high_cardinality_columns: Vec<String>,
+    /// Set when a write fails, so `Drop` does not try to write to the file again
+    poisoned: bool,
     builder: Builder,
 }
 
@@ -65,10 +581,130 @@ impl<Builder: Default + StructArrayBuilder> TableWriter for ParquetTableWriter<B
         ParquetTableWriterConfig {
             autoflush_row_group_len,
             autoflush_buffer_size,
+            min_flush_rows,
+            statistics_truncate_length,
+            manual_commit,
+            sort_within_flush,
+            max_files_per_writer,
+            column_logical_types,
+            float_nan_stats,
+            row_group_boundary_column,
+            compression_for_file,
+            bloom_filter_position,
+            max_builder_rows,
+            statistics_enabled_per_column,
+            column_compression,
+            write_batch_size,
+            index_column,
+            row_groups_sidecar,
+            max_footer_estimate_bytes,
+            check_schema_stability,
+            bitmap_index_columns,
+            encode_row_count_in_name,
+            warn_small_row_groups,
+            file_pool_size,
+            on_file_opened,
+            on_file_closed,
+            eager_file_creation,
+            skip_arrow_schema,
+            row_filter,
+            auto_bloom_filter_cardinality,
         }: Self::Config,
     ) -> Result<Self> {
         let base_path = path;
 
+        let mut properties_builder = properties.into_builder();
+        if let Some(statistics_truncate_length) = statistics_truncate_length {
+            properties_builder =
+                properties_builder.set_statistics_truncate_length(Some(statistics_truncate_length));
+        }
+        if let Some(write_batch_size) = write_batch_size {
+            properties_builder = properties_builder.set_write_batch_size(write_batch_size);
+        }
+        if let Some(bloom_filter_position) = bloom_filter_position {
+            properties_builder =
+                properties_builder.set_bloom_filter_position(bloom_filter_position);
+        }
+        if !sort_within_flush.is_empty() {
+            let parquet_schema = ArrowSchemaConverter::new()
+                .convert(&schema)
+                .context("Could not convert schema to Parquet")?;
+            let sorting_columns = sort_within_flush
+                .iter()
+                .map(|(name, options)| {
+                    let column_idx = parquet_schema
+                        .columns()
+                        .iter()
+                        .position(|column| column.path().parts() == [name.as_str()])
+                        .with_context(|| format!("Unknown top-level sort column {}", name))?;
+                    Ok(SortingColumn {
+                        column_idx: column_idx.try_into().expect("column index overflowed i32"),
+                        descending: options.descending,
+                        nulls_first: options.nulls_first,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            properties_builder = properties_builder.set_sorting_columns(Some(sorting_columns));
+        }
+        if float_nan_stats == NanStatsPolicy::DisableFloatStatistics {
+            let parquet_schema = ArrowSchemaConverter::new()
+                .convert(&schema)
+                .context("Could not convert schema to Parquet")?;
+            for column in parquet_schema.columns() {
+                let is_float = match column.physical_type() {
+                    PhysicalType::FLOAT | PhysicalType::DOUBLE => true,
+                    PhysicalType::FIXED_LEN_BYTE_ARRAY => {
+                        column.logical_type_ref() == Some(&LogicalType::Float16)
+                    }
+                    _ => false,
+                };
+                if is_float {
+                    properties_builder = properties_builder.set_column_statistics_enabled(
+                        column.path().clone(),
+                        EnabledStatistics::None,
+                    );
+                }
+            }
+        }
+        for (name, enabled_statistics) in statistics_enabled_per_column {
+            ensure!(
+                schema.column_with_name(&name).is_some(),
+                "Unknown top-level column {}",
+                name
+            );
+            properties_builder = properties_builder
+                .set_column_statistics_enabled(ColumnPath::from(name), enabled_statistics);
+        }
+        for (name, compression) in column_compression {
+            ensure!(
+                schema.column_with_name(&name).is_some(),
+                "Unknown top-level column {}",
+                name
+            );
+            properties_builder =
+                properties_builder.set_column_compression(ColumnPath::from(name), compression);
+        }
+        let properties = properties_builder.build();
+        let parquet_schema = if column_logical_types.is_empty() {
+            None
+        } else {
+            Some(parquet_schema_with_logical_types(
+                &schema,
+                &properties,
+                &column_logical_types,
+            )?)
+        };
+
+        let distinct_value_hashes = match auto_bloom_filter_cardinality {
+            None => BTreeMap::new(),
+            Some(_) => schema
+                .fields()
+                .iter()
+                .filter(|field| !field.data_type().is_nested())
+                .map(|field| (field.name().clone(), HashSet::new()))
+                .collect(),
+        };
+
         let mut writer = ParquetTableWriter {
             base_path,
             // See above, we need to make sure the user does not write more than
@@ -78,24 +714,320 @@ impl<Builder: Default + StructArrayBuilder> TableWriter for ParquetTableWriter<B
             autoflush_row_group_len: autoflush_row_group_len
                 .unwrap_or(properties.max_row_group_size() * 9 / 10),
             autoflush_buffer_size,
-            schema, properties,
+            min_flush_rows: min_flush_rows.unwrap_or(0),
+            manual_commit,
+            sort_within_flush,
+            row_group_boundary_column,
+            compression_for_file,
+            max_files_per_writer,
+            max_builder_rows,
+            max_footer_estimate_bytes,
+            footer_estimate_bytes: 0,
+            last_file_is_full: false,
+            last_full_file_metadata: None,
+            schema,
+            parquet_schema,
+            properties,
             file_writer: None,
             num_written_files: 0,
+            created_files: Vec::new(),
+            num_bytes_in_closed_files: 0,
+            num_written_rows: 0,
+            file_metadata: Vec::new(),
+            marks: Vec::new(),
+            next_row_group_metadata: Vec::new(),
+            row_group_metadata: Vec::new(),
+            index_column,
+            index_entries: Vec::new(),
+            row_groups_sidecar,
+            check_schema_stability: check_schema_stability.unwrap_or(cfg!(debug_assertions)),
+            first_flush_fields: None,
+            bitmap_indexes: vec![BTreeMap::new(); bitmap_index_columns.len()],
+            bitmap_index_columns,
+            encode_row_count_in_name,
+            warn_small_row_groups,
+            files_with_small_row_groups: Vec::new(),
+            file_pool: file_pool_size.map(|file_pool_size| {
+                Arc::new(FilePool {
+                    slots_in_use: Mutex::new(vec![
+                        false;
+                        usize::try_from(file_pool_size)
+                            .expect("file_pool_size overflowed usize")
+                    ]),
+                    slot_freed: Condvar::new(),
+                })
+            }),
+            file_pool_size,
+            current_file_index: 0,
+            on_file_opened,
+            on_file_closed,
+            schema_sidecar_path: None,
+            skip_arrow_schema,
+            row_filter,
+            auto_bloom_filter_cardinality,
+            distinct_value_hashes,
+            high_cardinality_columns: Vec::new(),
+            poisoned: false,
             builder: Builder::default(),
         };
+        if eager_file_creation {
+            writer.write_schema_sidecar()?;
+        }
         writer.new_file_writer()?;
         Ok(writer)
     }
 
+    fn schema_with_metadata(
+        (schema, properties): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        let mut schema = Arc::unwrap_or_clone(schema);
+        schema.metadata.insert(key.to_owned(), value.to_owned());
+        (Arc::new(schema), properties)
+    }
+
+    /// Writes a Parquet file with no row groups.
+    ///
+    /// Its footer has the same schema and key-value metadata as data files, except
+    /// metadata set with [`TableWriter::set_file_metadata`], and always includes the
+    /// Arrow schema. If [`ParquetTableWriterConfig::skip_arrow_schema`] is set, the
+    /// Arrow schema is also written as `schema.json` in the same directory.
+    fn write_common_metadata(
+        path: &Path,
+        (schema, properties): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+        let mut options = ArrowWriterOptions::new().with_properties(properties.clone());
+        if !config.column_logical_types.is_empty() {
+            options = options.with_parquet_schema(parquet_schema_with_logical_types(
+                schema,
+                properties,
+                &config.column_logical_types,
+            )?);
+        }
+        ParquetWriter::try_new_with_options(file, schema.clone(), options)
+            .with_context(|| format!("Could not create writer for {}", path.display()))?
+            .close()
+            .with_context(|| format!("Could not close {}", path.display()))?;
+        if config.skip_arrow_schema {
+            let schema_path = path.with_file_name("schema.json");
+            let schema_json =
+                serde_json::to_string_pretty(&**schema).context("Could not serialize schema")?;
+            std::fs::write(&schema_path, schema_json + "\n")
+                .with_context(|| format!("Could not write {}", schema_path.display()))?;
+        }
+        Ok(())
+    }
+
     fn flush(&mut self) -> Result<()> {
         // Get built array
-        let struct_array = self.builder.finish()?;
+        let num_bytes = self.builder.buffer_size();
+        let struct_array = self
+            .builder
+            .finish()
+            .inspect_err(|_| self.poisoned = true)?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+        let struct_array = match &self.row_filter {
+            None => struct_array,
+            Some(row_filter) => {
+                filter_rows(row_filter, struct_array).inspect_err(|_| self.poisoned = true)?
+            }
+        };
+
+        if self.check_schema_stability {
+            match &self.first_flush_fields {
+                None => self.first_flush_fields = Some(struct_array.fields().clone()),
+                Some(first_flush_fields) => {
+                    if first_flush_fields != struct_array.fields() {
+                        self.poisoned = true;
+                        bail!(
+                            "Builder of {} returned an array with different fields than on the first flush:\n{}",
+                            self.base_path.display(),
+                            fields_diff(first_flush_fields, struct_array.fields())
+                        );
+                    }
+                }
+            }
+        }
+
+        // Not poisoning the writer, so the file is still closed (without these rows)
+        self.ensure_file_is_not_full(&struct_array)?;
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
+    }
+
+    fn close(mut self) -> Result<ParquetMetaData> {
+        self.flush()?;
+        let (path, file_writer) = self
+            .file_writer
+            .take()
+            .expect("File writer is unexpectedly None");
+        let metadata = file_writer
+            .close()
+            .with_context(|| format!("Could not close {}", path.display()))?;
+        let path = self.rename_closed_file(path, &metadata)?;
+        self.check_row_group_sizes(&path, &metadata);
+        self.write_row_group_metadata_sidecar(&path)?;
+        self.write_bitmap_indexes(&path)?;
+        self.remove_schema_sidecar()?;
+        self.write_index()?;
+        self.release_closed_file(&path);
+        self.warn_small_row_groups();
+        Ok(metadata)
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.num_written_rows
+    }
+
+    fn written_bytes(&self) -> u64 {
+        let current_file_bytes = match &self.file_writer {
+            Some((_path, file_writer)) => file_writer.bytes_written(),
+            None => 0,
+        };
+        self.num_bytes_in_closed_files
+            + u64::try_from(current_file_bytes).expect("usize overflowed u64")
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+    }
+
+    /// The metadata is written in the footer, so it applies to the current file even
+    /// if rows were already written to it.
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        let key_value = KeyValue::new(key.to_owned(), value.to_owned());
+        if let Some((_path, file_writer)) = &mut self.file_writer {
+            file_writer.append_key_value_metadata(key_value.clone());
+        }
+        self.file_metadata.push(key_value);
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> StructArrayTableWriter for ParquetTableWriter<Builder> {
+    fn write_struct_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if self.builder.len() > 0 {
+            self.flush()?;
+        }
+        self.ensure_file_is_not_full(&struct_array)?;
+        self.write_array(struct_array)
+            .inspect_err(|_| self.poisoned = true)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> ParquetTableWriter<Builder> {
+    /// Writes the array as new row groups
+    fn write_array(&mut self, struct_array: StructArray) -> Result<()> {
+        if self.auto_bloom_filter_cardinality.is_some() && !struct_array.is_empty() {
+            self.count_distinct_values(&struct_array)?;
+        }
+        let struct_array = if self.sort_within_flush.is_empty() {
+            struct_array
+        } else {
+            let sort_columns = self
+                .sort_within_flush
+                .iter()
+                .map(|(name, options)| {
+                    Ok(SortColumn {
+                        values: struct_array
+                            .column_by_name(name)
+                            .with_context(|| format!("Unknown sort column {}", name))?
+                            .clone(),
+                        options: Some(*options),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let indices =
+                lexsort_to_indices(&sort_columns, None).context("Could not sort batch")?;
+            take(&struct_array, &indices, None)
+                .context("Could not sort batch")?
+                .as_struct()
+                .clone()
+        };
+
+        match &self.row_group_boundary_column {
+            None => self.write_row_groups(struct_array),
+            Some(boundary_column) => {
+                let ranges = partition(&[struct_array
+                    .column_by_name(boundary_column)
+                    .with_context(|| format!("Unknown boundary column {}", boundary_column))?
+                    .clone()])
+                .context("Could not split batch on boundary column")?
+                .ranges();
+                for range in ranges {
+                    self.write_row_groups(struct_array.slice(range.start, range.len()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the given rows as row groups of at most
+    /// [`max_row_group_size`](WriterProperties::max_row_group_size) rows each, so
+    /// rotation is checked between each of them.
+    fn write_row_groups(&mut self, struct_array: StructArray) -> Result<()> {
+        let max_row_group_size = self.properties.max_row_group_size();
+        let mut offset = 0;
+        while offset < struct_array.len() {
+            let len = max_row_group_size.min(struct_array.len() - offset);
+            self.write_row_group(struct_array.slice(offset, len))?;
+            offset += len;
+        }
+        Ok(())
+    }
 
+    /// Writes the given rows and flushes them as one row group (or more, if there are
+    /// more than [`max_row_group_size`](WriterProperties::max_row_group_size) rows)
+    fn write_row_group(&mut self, struct_array: StructArray) -> Result<()> {
+        let num_rows = struct_array.len();
         let (path, file_writer) = self
             .file_writer
             .as_mut()
             .expect("File writer is unexpectedly None");
 
+        let first_row_group = file_writer.flushed_row_groups().len();
+        if let Some(index_column) = &self.index_column {
+            let keys = struct_array
+                .column_by_name(index_column)
+                .with_context(|| format!("Unknown index column {}", index_column))?
+                .clone();
+            self.index_entries.push((
+                keys,
+                self.created_files.len() - 1,
+                u64::try_from(first_row_group).expect("usize overflowed u64"),
+            ));
+        }
+        if !self.bitmap_index_columns.is_empty() {
+            let first_row: i64 = file_writer
+                .flushed_row_groups()
+                .iter()
+                .map(|row_group| row_group.num_rows())
+                .sum();
+            for (column_name, bitmap_index) in self
+                .bitmap_index_columns
+                .iter()
+                .zip(&mut self.bitmap_indexes)
+            {
+                let column = struct_array
+                    .column_by_name(column_name)
+                    .with_context(|| format!("Unknown bitmap index column {}", column_name))?;
+                let formatter = ArrayFormatter::try_new(column, &FormatOptions::default())
+                    .with_context(|| format!("Could not format column {}", column_name))?;
+                for row in 0..column.len() {
+                    let value = column
+                        .is_valid(row)
+                        .then(|| formatter.value(row).to_string());
+                    let position = u32::try_from(first_row + row as i64).with_context(|| {
+                        format!("{} has too many rows for a bitmap index", path.display())
+                    })?;
+                    bitmap_index.entry(value).or_default().insert(position);
+                }
+            }
+        }
+
         // Write it
         file_writer
             .write(&struct_array.into())
@@ -103,60 +1035,187 @@ impl<Builder: Default + StructArrayBuilder> TableWriter for ParquetTableWriter<B
         file_writer
             .flush()
             .with_context(|| format!("Could not flush to {}", path.display()))?;
+        self.num_written_rows += u64::try_from(num_rows).expect("usize overflowed u64");
+        if self.row_groups_sidecar {
+            file_writer
+                .sync()
+                .with_context(|| format!("Could not flush to {}", path.display()))?;
+            let mut sidecar = String::new();
+            for (row_group, metadata) in file_writer
+                .flushed_row_groups()
+                .iter()
+                .enumerate()
+                .skip(first_row_group)
+            {
+                sidecar.push_str(&format!("{}\t{}", row_group, metadata.num_rows()));
+                for column in metadata.columns() {
+                    let (start, length) = column.byte_range();
+                    sidecar.push_str(&format!("\t{}\t{}", start, length));
+                }
+                sidecar.push('\n');
+            }
+            let sidecar_path = row_groups_sidecar_path(path);
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&sidecar_path)
+                .and_then(|mut file| file.write_all(sidecar.as_bytes()))
+                .with_context(|| format!("Could not write to {}", sidecar_path.display()))?;
+        }
+        if !self.next_row_group_metadata.is_empty() {
+            for row_group in first_row_group..file_writer.flushed_row_groups().len() {
+                self.row_group_metadata
+                    .push((row_group, self.next_row_group_metadata.clone()));
+            }
+        }
 
-        if file_writer.flushed_row_groups().len() >= (i16::MAX - 2).try_into().expect("i16 overflowed usize") {
+        for row_group in &file_writer.flushed_row_groups()[first_row_group..] {
+            self.footer_estimate_bytes += estimate_footer_bytes(row_group);
+        }
+        let footer_is_full =
+            self.max_footer_estimate_bytes
+                .is_some_and(|max_footer_estimate_bytes| {
+                    self.footer_estimate_bytes >= max_footer_estimate_bytes
+                });
+
+        if file_writer.flushed_row_groups().len() >= MAX_ROW_GROUPS_PER_FILE || footer_is_full {
             // Parquet does not support more than 32767 row groups per file, so we need to open a
-            // new file.
-            self.new_file_writer()?;
+            // new file. Likewise if the footer would grow too large.
+            if !self.can_rotate() {
+                // No row group can be added to this file anymore, so writing more rows
+                // returns an error
+                self.last_file_is_full = true;
+            } else {
+                self.last_full_file_metadata = self.new_file_writer()?;
+            }
         }
 
         Ok(())
     }
 
-    fn close(mut self) -> Result<ParquetMetaData> {
-        self.flush()?;
-        let (path, file_writer) = self.file_writer
-            .take()
-            .expect("File writer is unexpectedly None");
-        file_writer
-            .close()
-            .with_context(|| format!("Could not close {}", path.display()))
-    }
-}
-
-impl<Builder: Default + StructArrayBuilder> ParquetTableWriter<Builder> {
-    fn new_file_writer(&mut self) -> Result<()> {
+    /// Closes the current file (if any) and opens a new one.
+    ///
+    /// Returns the metadata of the closed file.
+    fn new_file_writer(&mut self) -> Result<Option<ParquetMetaData>> {
         // Close previous writer, if any.
+        let mut closed_file_metadata = None;
         if let Some((path, file_writer)) = self.file_writer.take() {
-            file_writer.close().with_context(|| format!("Could not close {}", path.display()))?;
+            let metadata = file_writer
+                .close()
+                .with_context(|| format!("Could not close {}", path.display()))?;
+            let path = self.rename_closed_file(path, &metadata)?;
+            self.check_row_group_sizes(&path, &metadata);
+            closed_file_metadata = Some(metadata);
+            self.write_row_group_metadata_sidecar(&path)?;
+            self.write_bitmap_indexes(&path)?;
+            self.remove_schema_sidecar()?;
+            self.num_bytes_in_closed_files += std::fs::metadata(&path)
+                .with_context(|| format!("Could not get size of {}", path.display()))?
+                .len();
+            self.release_closed_file(&path);
             self.num_written_files += 1;
         }
+        self.footer_estimate_bytes = 0;
 
-        let mut path = if self.num_written_files == 0 {
+        self.current_file_index = match self.file_pool_size {
+            None => self.num_written_files,
+            Some(file_pool_size) => self.num_written_files % file_pool_size,
+        };
+        if let Some(file_pool) = &self.file_pool {
+            file_pool.acquire(self.current_file_index);
+        }
+        let mut path = if self.current_file_index == 0 {
             self.base_path.to_owned()
         } else {
-            let mut file_name = self.base_path.file_name().expect("file has no name").to_owned();
-            file_name.push(format!("_{}", self.num_written_files));
+            let mut file_name = self
+                .base_path
+                .file_name()
+                .expect("file has no name")
+                .to_owned();
+            file_name.push(format!("_{}", self.current_file_index));
             self.base_path.with_file_name(&file_name)
         };
         path.set_extension("parquet");
         let file =
             File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
-        let file_writer = ParquetWriter::try_new(file, self.schema.clone(), Some(self.properties.clone()))
+        if let Some(on_file_opened) = &self.on_file_opened {
+            on_file_opened(&path);
+        }
+        if self.row_groups_sidecar {
+            let sidecar_path = row_groups_sidecar_path(&path);
+            File::create(&sidecar_path)
+                .with_context(|| format!("Could not create {}", sidecar_path.display()))?;
+        }
+        let properties = match &self.compression_for_file {
+            None => self.properties.clone(),
+            Some(compression_for_file) => self
+                .properties
+                .clone()
+                .into_builder()
+                .set_compression(compression_for_file(self.num_written_files))
+                .build(),
+        };
+        let properties = if self.high_cardinality_columns.is_empty() {
+            properties
+        } else {
+            let mut properties_builder = properties.into_builder();
+            for name in &self.high_cardinality_columns {
+                properties_builder = properties_builder
+                    .set_column_bloom_filter_enabled(ColumnPath::from(name.as_str()), true);
+            }
+            properties_builder.build()
+        };
+        let properties = if self.file_metadata.is_empty() {
+            properties
+        } else {
+            let mut key_value_metadata =
+                properties.key_value_metadata().cloned().unwrap_or_default();
+            key_value_metadata.extend(self.file_metadata.iter().cloned());
+            properties
+                .into_builder()
+                .set_key_value_metadata(Some(key_value_metadata))
+                .build()
+        };
+        let mut options = ArrowWriterOptions::new()
+            .with_properties(properties.clone())
+            .with_skip_arrow_metadata(self.skip_arrow_schema);
+        if let Some(parquet_schema) = &self.parquet_schema {
+            options = options.with_parquet_schema(parquet_schema.clone());
+        }
+        let file_writer = ParquetWriter::try_new_with_options(file, self.schema.clone(), options)
             .with_context(|| {
-                format!(
-                    "Could not create writer for {} with schema {} and properties {:?}",
-                    path.display(),
-                    self.schema,
-                    self.properties.clone()
-                )
-            })?;
+            format!(
+                "Could not create writer for {} with schema {} and properties {:?}",
+                path.display(),
+                self.schema,
+                properties
+            )
+        })?;
 
+        self.created_files.push(path.clone());
         self.file_writer = Some((path, file_writer));
-        Ok(())
+        Ok(closed_file_metadata)
     }
     /// Flushes the internal buffer is too large, then returns the array builder.
     pub fn builder(&mut self) -> Result<&mut Builder> {
+        ensure!(
+            !self.last_file_is_full,
+            "{} has too many row groups, and max_files_per_writer ({:?}) is reached",
+            self.base_path.display(),
+            self.max_files_per_writer
+        );
+        if let Some(max_builder_rows) = self.max_builder_rows {
+            ensure!(
+                self.builder.len() <= max_builder_rows,
+                "Builder for {} has {} rows, {} more than max_builder_rows ({})",
+                self.base_path.display(),
+                self.builder.len(),
+                self.builder.len() - max_builder_rows,
+                max_builder_rows
+            );
+        }
+        if self.manual_commit || self.builder.len() < self.min_flush_rows {
+            return Ok(&mut self.builder);
+        }
         if self.builder.len() >= self.autoflush_row_group_len {
             self.flush()?;
         }
@@ -168,19 +1227,740 @@ impl<Builder: Default + StructArrayBuilder> ParquetTableWriter<Builder> {
 
         Ok(&mut self.builder)
     }
+
+    /// Returns an error if `struct_array` has rows but the current file is full and
+    /// cannot be rotated, see `last_file_is_full`
+    fn ensure_file_is_not_full(&self, struct_array: &StructArray) -> Result<()> {
+        ensure!(
+            !self.last_file_is_full || struct_array.is_empty(),
+            "Could not write {} rows: {} has too many row groups, and max_files_per_writer ({:?}) is reached",
+            struct_array.len(),
+            self.base_path.display(),
+            self.max_files_per_writer
+        );
+        Ok(())
+    }
+
+    /// Returns whether `max_files_per_writer` allows opening a new file
+    fn can_rotate(&self) -> bool {
+        self.max_files_per_writer
+            .is_none_or(|max_files| self.num_written_files + 1 < max_files)
+    }
+
+    /// Returns `true` if the next flush will close the current file and open a new one,
+    /// because it will reach the maximum number of row groups per file (or, with
+    /// [`max_footer_estimate_bytes`](ParquetTableWriterConfig::max_footer_estimate_bytes),
+    /// probably reach the maximum footer size, assuming the next row group's metadata
+    /// is about as large as that of previous ones).
+    ///
+    /// This allows callers to prepare for the new file before calling
+    /// [`builder`](Self::builder), which may flush.
+    pub fn rotation_imminent(&self) -> bool {
+        let Some((_, file_writer)) = &self.file_writer else {
+            return false;
+        };
+        let num_row_groups = file_writer.flushed_row_groups().len();
+        let footer_will_be_full =
+            self.max_footer_estimate_bytes
+                .is_some_and(|max_footer_estimate_bytes| {
+                    num_row_groups > 0
+                        && self.footer_estimate_bytes + self.footer_estimate_bytes / num_row_groups
+                            >= max_footer_estimate_bytes
+                });
+        (num_row_groups + 1 >= MAX_ROW_GROUPS_PER_FILE || footer_will_be_full) && self.can_rotate()
+    }
+
+    /// Number of files this writer closed because they had too many row groups (or
+    /// because of [`rotate`](Self::rotate)), and replaced with a new one.
+    pub fn files_rotated(&self) -> u64 {
+        self.num_written_files
+    }
+
+    /// Flushes, then closes the current file and opens a new one, even if the current
+    /// file is not full.
+    ///
+    /// Returns the metadata of the closed file. If the current file has no rows after
+    /// flushing, it is left open, and this returns the metadata of the file this flush
+    /// closed because it was full, if any, or `None`.
+    pub fn rotate(&mut self) -> Result<Option<ParquetMetaData>> {
+        self.last_full_file_metadata = None;
+        self.flush()?;
+        let (_path, file_writer) = self
+            .file_writer
+            .as_ref()
+            .expect("File writer is unexpectedly None");
+        if file_writer.flushed_row_groups().is_empty() {
+            return Ok(self.last_full_file_metadata.take());
+        }
+        self.last_full_file_metadata = None;
+        ensure!(
+            self.can_rotate(),
+            "Cannot rotate {}, as max_files_per_writer ({:?}) is reached",
+            self.base_path.display(),
+            self.max_files_per_writer
+        );
+        self.new_file_writer().inspect_err(|_| self.poisoned = true)
+    }
+
+    /// Returns `true` if a previous write failed, in which case dropping this writer
+    /// does not flush nor close the current file (which is left without footer).
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
+    /// Flushes, then records `label` along with the number of rows written so far by
+    /// this writer (across all its files).
+    ///
+    /// Each mark is added to the footer metadata of the current file (the one the
+    /// next rows are written to), with key [`MARK_METADATA_KEY_PREFIX`] followed by
+    /// `label`, and the number of rows as value. It is therefore also part of the
+    /// [`ParquetMetaData`] returned when closing this file.
+    pub fn flush_and_mark(&mut self, label: String) -> Result<()> {
+        self.flush()?;
+        let (_path, file_writer) = self
+            .file_writer
+            .as_mut()
+            .expect("File writer is unexpectedly None");
+        file_writer.append_key_value_metadata(KeyValue::new(
+            format!("{}{}", MARK_METADATA_KEY_PREFIX, label),
+            self.num_written_rows.to_string(),
+        ));
+        self.marks.push((label, self.num_written_rows));
+        Ok(())
+    }
+
+    /// Flushes, and attaches `metadata` to the row group(s) written by this flush.
+    ///
+    /// Parquet has no key-value metadata at the row group level, so it is written
+    /// when closing each file to a sidecar file, whose path is the path of the
+    /// Parquet file followed by [`ROW_GROUP_METADATA_SIDECAR_SUFFIX`]. It has one line
+    /// per key-value pair, made of the row group index (within that file), the key,
+    /// and the value, separated by tabs. Keys and values may therefore not contain
+    /// tabs or newlines.
+    ///
+    /// The sidecar is not written for files without any such metadata.
+    pub fn flush_with_metadata(&mut self, metadata: Vec<(String, String)>) -> Result<()> {
+        for (key, value) in &metadata {
+            ensure!(
+                !key.contains(['\t', '\n']) && !value.contains(['\t', '\n']),
+                "Row group metadata may not contain tabs or newlines: {:?} = {:?}",
+                key,
+                value
+            );
+        }
+        self.next_row_group_metadata = metadata;
+        let result = self.flush();
+        self.next_row_group_metadata = Vec::new();
+        result
+    }
+
+    /// Writes metadata attached by [`flush_with_metadata`](Self::flush_with_metadata)
+    /// to row groups of the file that was just closed
+    fn write_row_group_metadata_sidecar(&mut self, path: &Path) -> Result<()> {
+        if self.row_group_metadata.is_empty() {
+            return Ok(());
+        }
+        let mut sidecar = String::new();
+        for (row_group, metadata) in self.row_group_metadata.drain(..) {
+            for (key, value) in metadata {
+                sidecar.push_str(&format!("{}\t{}\t{}\n", row_group, key, value));
+            }
+        }
+        let mut sidecar_path = path.to_owned().into_os_string();
+        sidecar_path.push(ROW_GROUP_METADATA_SIDECAR_SUFFIX);
+        let sidecar_path = PathBuf::from(sidecar_path);
+        std::fs::write(&sidecar_path, sidecar)
+            .with_context(|| format!("Could not write {}", sidecar_path.display()))
+    }
+
+    /// If `encode_row_count_in_name` is `true`, renames the file that was just closed
+    /// (and its row groups sidecar) to include its number of rows. Returns its new path.
+    fn rename_closed_file(&mut self, path: PathBuf, metadata: &ParquetMetaData) -> Result<PathBuf> {
+        if !self.encode_row_count_in_name {
+            return Ok(path);
+        }
+        let new_path = path_with_row_count(&path, metadata.file_metadata().num_rows());
+        std::fs::rename(&path, &new_path).with_context(|| {
+            format!(
+                "Could not rename {} to {}",
+                path.display(),
+                new_path.display()
+            )
+        })?;
+        if self.row_groups_sidecar {
+            let sidecar_path = row_groups_sidecar_path(&path);
+            let new_sidecar_path = row_groups_sidecar_path(&new_path);
+            std::fs::rename(&sidecar_path, &new_sidecar_path).with_context(|| {
+                format!(
+                    "Could not rename {} to {}",
+                    sidecar_path.display(),
+                    new_sidecar_path.display()
+                )
+            })?;
+        }
+        let created_file = self
+            .created_files
+            .iter_mut()
+            .rfind(|created_file| **created_file == path)
+            .expect("Closed file is not in created_files");
+        created_file.clone_from(&new_path);
+        Ok(new_path)
+    }
+
+    /// Adds hashes of the values of `struct_array` to `distinct_value_hashes`, and moves
+    /// columns which reach `auto_bloom_filter_cardinality` to `high_cardinality_columns`
+    fn count_distinct_values(&mut self, struct_array: &StructArray) -> Result<()> {
+        let auto_bloom_filter_cardinality = self
+            .auto_bloom_filter_cardinality
+            .expect("auto_bloom_filter_cardinality is unexpectedly None");
+        let mut high_cardinality_columns = Vec::new();
+        for (name, hashes) in &mut self.distinct_value_hashes {
+            let column = struct_array
+                .column_by_name(name)
+                .with_context(|| format!("Missing column {}", name))?;
+            let converter = RowConverter::new(vec![SortField::new(column.data_type().clone())])
+                .with_context(|| format!("Could not count distinct values of {}", name))?;
+            let rows = converter
+                .convert_columns(std::slice::from_ref(column))
+                .with_context(|| format!("Could not count distinct values of {}", name))?;
+            for row in rows.iter() {
+                let mut hasher = DefaultHasher::new();
+                row.as_ref().hash(&mut hasher);
+                hashes.insert(hasher.finish());
+                if hashes.len() > auto_bloom_filter_cardinality {
+                    high_cardinality_columns.push(name.clone());
+                    break;
+                }
+            }
+        }
+        for name in high_cardinality_columns {
+            self.distinct_value_hashes.remove(&name);
+            self.high_cardinality_columns.push(name);
+        }
+        Ok(())
+    }
+
+    /// Writes a Parquet file with no rows next to the first file, see
+    /// [`ParquetTableWriterConfig::eager_file_creation`]
+    fn write_schema_sidecar(&mut self) -> Result<()> {
+        let mut path = self.base_path.clone();
+        path.set_extension("parquet");
+        let mut path = path.into_os_string();
+        path.push(SCHEMA_SIDECAR_SUFFIX);
+        let path = PathBuf::from(path);
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let mut options = ArrowWriterOptions::new().with_properties(self.properties.clone());
+        if let Some(parquet_schema) = &self.parquet_schema {
+            options = options.with_parquet_schema(parquet_schema.clone());
+        }
+        ParquetWriter::try_new_with_options(file, self.schema.clone(), options)
+            .with_context(|| format!("Could not create writer for {}", path.display()))?
+            .close()
+            .with_context(|| format!("Could not close {}", path.display()))?;
+        self.schema_sidecar_path = Some(path);
+        Ok(())
+    }
+
+    /// Removes the schema sidecar, if it was not removed yet
+    fn remove_schema_sidecar(&mut self) -> Result<()> {
+        if let Some(path) = self.schema_sidecar_path.take() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Could not remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Calls `on_file_closed` on the file that was just closed, and frees its slot in
+    /// the file pool (if any) once the [`FileSlot`] is dropped
+    fn release_closed_file(&mut self, path: &Path) {
+        let slot = FileSlot {
+            index: self.current_file_index,
+            pool: self.file_pool.clone(),
+        };
+        if let Some(on_file_closed) = &self.on_file_closed {
+            on_file_closed(path, slot);
+        }
+    }
+
+    /// Records the file that was just closed if its median row group size is below
+    /// `warn_small_row_groups`
+    fn check_row_group_sizes(&mut self, path: &Path, metadata: &ParquetMetaData) {
+        let Some(warn_small_row_groups) = self.warn_small_row_groups else {
+            return;
+        };
+        let mut row_group_sizes: Vec<_> = metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.num_rows())
+            .collect();
+        if row_group_sizes.is_empty() {
+            return;
+        }
+        row_group_sizes.sort_unstable();
+        let median = row_group_sizes[row_group_sizes.len() / 2];
+        if usize::try_from(median).is_ok_and(|median| median < warn_small_row_groups) {
+            self.files_with_small_row_groups
+                .push((path.to_owned(), median));
+        }
+    }
+
+    /// Logs a warning listing files recorded by [`Self::check_row_group_sizes`], if any
+    fn warn_small_row_groups(&mut self) {
+        if self.files_with_small_row_groups.is_empty() {
+            return;
+        }
+        let files = self
+            .files_with_small_row_groups
+            .drain(..)
+            .map(|(path, median)| format!("{} (median: {} rows)", path.display(), median))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::warn!(
+            "Files have row groups smaller than {} rows (warn_small_row_groups), which makes them slow to read: {}",
+            self.warn_small_row_groups.unwrap_or(0),
+            files
+        );
+    }
+
+    /// Writes the bitmap indexes of the file that was just closed, see
+    /// [`ParquetTableWriterConfig::bitmap_index_columns`]
+    fn write_bitmap_indexes(&mut self, path: &Path) -> Result<()> {
+        for (column, bitmap_index) in self
+            .bitmap_index_columns
+            .iter()
+            .zip(&mut self.bitmap_indexes)
+        {
+            let mut sidecar = Vec::new();
+            for (value, bitmap) in std::mem::take(bitmap_index) {
+                match value {
+                    None => sidecar.extend(u32::MAX.to_le_bytes()),
+                    Some(value) => {
+                        let len = u32::try_from(value.len())
+                            .context("Value is too long for a bitmap index")?;
+                        sidecar.extend(len.to_le_bytes());
+                        sidecar.extend(value.as_bytes());
+                    }
+                }
+                let len = u32::try_from(bitmap.serialized_size()).context("Bitmap is too large")?;
+                sidecar.extend(len.to_le_bytes());
+                bitmap
+                    .serialize_into(&mut sidecar)
+                    .context("Could not serialize bitmap")?;
+            }
+            let sidecar_path = bitmap_index_sidecar_path(path, column);
+            std::fs::write(&sidecar_path, sidecar)
+                .with_context(|| format!("Could not write {}", sidecar_path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the index of all rows, if `index_column` is set
+    fn write_index(&mut self) -> Result<()> {
+        let Some(index_column) = &self.index_column else {
+            return Ok(());
+        };
+        let mut index_path = self.created_files[0].clone().into_os_string();
+        index_path.push(INDEX_SIDECAR_SUFFIX);
+        let index_path = PathBuf::from(index_path);
+
+        let key_type = self
+            .schema
+            .field_with_name(index_column)
+            .with_context(|| format!("Unknown index column {}", index_column))?
+            .data_type()
+            .clone();
+        let keys = if self.index_entries.is_empty() {
+            new_empty_array(&key_type)
+        } else {
+            let keys: Vec<&dyn Array> = self
+                .index_entries
+                .iter()
+                .map(|(keys, _, _)| keys.as_ref())
+                .collect();
+            concat(&keys).context("Could not concatenate index keys")?
+        };
+        let mut files = StringBuilder::new();
+        let mut row_groups = UInt64Builder::new();
+        let mut row_offsets = UInt64Builder::new();
+        for (keys, file_index, row_group) in &self.index_entries {
+            let file_name = self.created_files[*file_index]
+                .file_name()
+                .expect("file has no name")
+                .to_string_lossy();
+            for row_offset in 0..keys.len() {
+                files.append_value(&file_name);
+                row_groups.append_value(*row_group);
+                row_offsets.append_value(u64::try_from(row_offset).expect("usize overflowed u64"));
+            }
+        }
+        let columns: Vec<ArrayRef> = vec![
+            keys,
+            Arc::new(files.finish()),
+            Arc::new(row_groups.finish()),
+            Arc::new(row_offsets.finish()),
+        ];
+        let indices = sort_to_indices(&columns[0], None, None).context("Could not sort index")?;
+        let columns = columns
+            .iter()
+            .map(|column| take(column, &indices, None).context("Could not sort index"))
+            .collect::<Result<Vec<_>>>()?;
+        let index_schema = Arc::new(Schema::new(vec![
+            Field::new("key", key_type, true),
+            Field::new("file", DataType::Utf8, false),
+            Field::new("row_group", DataType::UInt64, false),
+            Field::new("row_offset", DataType::UInt64, false),
+        ]));
+        let batch =
+            RecordBatch::try_new(index_schema.clone(), columns).context("Could not build index")?;
+
+        let file = File::create(&index_path)
+            .with_context(|| format!("Could not create {}", index_path.display()))?;
+        let mut index_writer = ParquetWriter::try_new(file, index_schema, None)
+            .with_context(|| format!("Could not create writer for {}", index_path.display()))?;
+        index_writer
+            .write(&batch)
+            .with_context(|| format!("Could not write to {}", index_path.display()))?;
+        index_writer
+            .close()
+            .with_context(|| format!("Could not close {}", index_path.display()))?;
+        self.index_entries.clear();
+        Ok(())
+    }
+
+    /// Labels passed to [`flush_and_mark`](Self::flush_and_mark) so far, with the
+    /// number of rows written before each of them
+    pub fn marks(&self) -> &[(String, u64)] {
+        &self.marks
+    }
+
+    /// Paths of all files opened by this writer so far, including the current one
+    pub fn created_files(&self) -> &[PathBuf] {
+        &self.created_files
+    }
+
+    /// Discards buffered rows, and deletes all files created by this writer (without
+    /// writing their footer), and their sidecars, including the schema sidecar written
+    /// with [`eager_file_creation`](ParquetTableWriterConfig::eager_file_creation).
+    ///
+    /// Files which no longer exist (eg. because an
+    /// [`on_file_closed`](ParquetTableWriterConfig::on_file_closed) callback moved them
+    /// away) are ignored.
+    pub fn abort(mut self) -> Result<()> {
+        // Done before anything which may fail, so Drop never writes the discarded rows
+        self.file_writer = None;
+        std::mem::take(&mut self.builder);
+        for path in &self.created_files {
+            remove_data_file(path, &self.bitmap_index_columns)?;
+        }
+        self.remove_schema_sidecar()?;
+        Ok(())
+    }
+}
+
+impl<W: TableWriter<Schema = (Arc<Schema>, WriterProperties)> + Send> ParallelDatasetWriter<W>
+where
+    W::Config: Default,
+{
+    /// Same as [`with_schema`](Self::with_schema) with `(schema, properties)` as schema,
+    /// and `config` as [`config`](Self::config).
+    pub fn new_parquet(
+        path: PathBuf,
+        schema: Arc<Schema>,
+        properties: WriterProperties,
+        config: W::Config,
+    ) -> Result<Self> {
+        let mut dataset_writer = Self::with_schema(path, (schema, properties))?;
+        dataset_writer.config = config;
+        Ok(dataset_writer)
+    }
+}
+
+/// Returns a human-readable list of the differences between two lists of fields
+fn fields_diff(expected: &Fields, actual: &Fields) -> String {
+    let describe = |field: &Field| {
+        format!(
+            "{}: {}{}",
+            field.name(),
+            field.data_type(),
+            if field.is_nullable() {
+                " (nullable)"
+            } else {
+                ""
+            }
+        )
+    };
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let expected = expected.get(i).map(|field| describe(field));
+        let actual = actual.get(i).map(|field| describe(field));
+        if expected != actual {
+            diff.push_str(&format!(
+                "  field {}: expected {}, got {}\n",
+                i,
+                expected.as_deref().unwrap_or("nothing"),
+                actual.as_deref().unwrap_or("nothing")
+            ));
+        }
+    }
+    if diff.is_empty() {
+        // Differences are not visible in the descriptions, eg. in metadata
+        diff = format!("  expected {:?}\n  got {:?}\n", expected, actual);
+    }
+    diff
+}
+
+/// Returns a rough estimate of the size of the metadata of a row group in a footer,
+/// see [`ParquetTableWriterConfig::max_footer_estimate_bytes`]
+fn estimate_footer_bytes(row_group: &RowGroupMetaData) -> usize {
+    // Offsets, sizes, encodings, etc. of the column chunk
+    const COLUMN_CHUNK_OVERHEAD_BYTES: usize = 64;
+    row_group
+        .columns()
+        .iter()
+        .map(|column| {
+            let path_bytes: usize = column.column_path().parts().iter().map(String::len).sum();
+            let statistics_bytes = column
+                .statistics()
+                .map(|statistics| {
+                    statistics.min_bytes_opt().map_or(0, <[u8]>::len)
+                        + statistics.max_bytes_opt().map_or(0, <[u8]>::len)
+                })
+                .unwrap_or(0);
+            COLUMN_CHUNK_OVERHEAD_BYTES + path_bytes + statistics_bytes
+        })
+        .sum()
+}
+
+/// Returns a rough estimate of the size on disk of `num_rows` rows of the given schema
+/// written to Parquet, assuming data compresses by a factor of `compression_ratio`.
+///
+/// This is intentionally approximate, meant for capacity planning (eg. choosing a
+/// number of partitions): it is based only on column types, assuming 32 bytes per
+/// variable-length value and 4 items per list, and ignores encodings and metadata.
+///
+/// # Panics
+///
+/// If `compression_ratio` is not positive.
+pub fn estimate_parquet_size(schema: &Schema, num_rows: u64, compression_ratio: f64) -> u64 {
+    assert!(
+        compression_ratio > 0.,
+        "compression_ratio must be positive, got {}",
+        compression_ratio
+    );
+    let row_size: f64 = schema
+        .fields()
+        .iter()
+        .map(|field| estimate_value_size(field.data_type()))
+        .sum();
+    (row_size * num_rows as f64 / compression_ratio).ceil() as u64
+}
+
+/// Returns the average uncompressed size of a value of the given type, see
+/// [`estimate_parquet_size`]
+fn estimate_value_size(data_type: &DataType) -> f64 {
+    const VARIABLE_LENGTH_VALUE_SIZE: f64 = 32.;
+    const LIST_LENGTH: f64 = 4.;
+    match data_type {
+        DataType::Null => 0.,
+        DataType::Boolean => 1. / 8.,
+        DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Utf8View
+        | DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView => VARIABLE_LENGTH_VALUE_SIZE,
+        DataType::FixedSizeBinary(size) => *size as f64,
+        DataType::Struct(fields) => fields
+            .iter()
+            .map(|field| estimate_value_size(field.data_type()))
+            .sum(),
+        DataType::List(field)
+        | DataType::LargeList(field)
+        | DataType::ListView(field)
+        | DataType::LargeListView(field)
+        | DataType::Map(field, _) => LIST_LENGTH * estimate_value_size(field.data_type()),
+        DataType::FixedSizeList(field, size) => {
+            *size as f64 * estimate_value_size(field.data_type())
+        }
+        // Parquet stores dictionary indices
+        DataType::Dictionary(key_type, _) => estimate_value_size(key_type),
+        DataType::RunEndEncoded(_, values) => estimate_value_size(values.data_type()),
+        data_type => data_type.primitive_width().unwrap_or(8) as f64,
+    }
+}
+
+/// Returns the path of the sidecar of the given data file, see
+/// [`ParquetTableWriterConfig::row_groups_sidecar`]
+fn row_groups_sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar_path = path.to_owned().into_os_string();
+    sidecar_path.push(ROW_GROUPS_SIDECAR_SUFFIX);
+    PathBuf::from(sidecar_path)
+}
+
+/// Returns `<name>.r<num_rows>.parquet` for `<name>.parquet`, see
+/// [`ParquetTableWriterConfig::encode_row_count_in_name`]
+fn path_with_row_count(path: &Path, num_rows: i64) -> PathBuf {
+    let mut file_name = path.file_stem().expect("file has no name").to_owned();
+    file_name.push(format!(".r{}.parquet", num_rows));
+    path.with_file_name(file_name)
+}
+
+/// Returns the path of a file returned by [`ParquetTableWriter::created_files`] before
+/// the writer was closed, now that it is closed: the same path, or the one with its
+/// row count if it was renamed because of
+/// [`ParquetTableWriterConfig::encode_row_count_in_name`]
+pub(crate) fn closed_file_path(path: &Path) -> Result<PathBuf> {
+    if path.exists() {
+        return Ok(path.to_owned());
+    }
+    let dir = path.parent().expect("file has no parent");
+    let mut prefix = path.file_stem().expect("file has no name").to_owned();
+    prefix.push(".r");
+    let prefix = prefix.to_string_lossy().into_owned();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Could not list {}", dir.display()))?
+    {
+        let entry = entry.with_context(|| format!("Could not list {}", dir.display()))?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(num_rows) = file_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(".parquet"))
+        {
+            if !num_rows.is_empty() && num_rows.bytes().all(|c| c.is_ascii_digit()) {
+                return Ok(entry.path());
+            }
+        }
+    }
+    bail!("{} does not exist", path.display())
+}
+
+/// Returns the path of the bitmap index of `column` for the given data file, see
+/// [`ParquetTableWriterConfig::bitmap_index_columns`]
+fn bitmap_index_sidecar_path(path: &Path, column: &str) -> PathBuf {
+    let mut sidecar_path = path.to_owned().into_os_string();
+    sidecar_path.push(format!(".{}{}", column, BITMAP_INDEX_SIDECAR_SUFFIX));
+    PathBuf::from(sidecar_path)
+}
+
+/// Removes a file written by [`ParquetTableWriter`], and its sidecars if any, including
+/// bitmap indexes of the given columns
+///
+/// Files which do not exist are ignored.
+pub(crate) fn remove_data_file(path: &Path, bitmap_index_columns: &[String]) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        result => result.with_context(|| format!("Could not remove {}", path.display()))?,
+    }
+    let suffixes = [
+        ROW_GROUP_METADATA_SIDECAR_SUFFIX,
+        INDEX_SIDECAR_SUFFIX,
+        ROW_GROUPS_SIDECAR_SUFFIX,
+    ]
+    .map(|suffix| suffix.to_owned());
+    let bitmap_index_suffixes = bitmap_index_columns
+        .iter()
+        .map(|column| format!(".{}{}", column, BITMAP_INDEX_SIDECAR_SUFFIX));
+    for suffix in suffixes.into_iter().chain(bitmap_index_suffixes) {
+        let mut sidecar_path = path.to_owned().into_os_string();
+        sidecar_path.push(suffix);
+        let sidecar_path = PathBuf::from(sidecar_path);
+        match std::fs::remove_file(&sidecar_path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            result => {
+                result.with_context(|| format!("Could not remove {}", sidecar_path.display()))?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converts `schema` to a Parquet schema, then overrides the logical types of the
+/// given top-level columns.
+pub(crate) fn parquet_schema_with_logical_types(
+    schema: &Schema,
+    properties: &WriterProperties,
+    column_logical_types: &[(String, LogicalType)],
+) -> Result<SchemaDescriptor> {
+    let parquet_schema = ArrowSchemaConverter::new()
+        .with_coerce_types(properties.coerce_types())
+        .convert(schema)
+        .context("Could not convert schema to Parquet")?;
+    let root = parquet_schema.root_schema();
+    let mut fields = root.get_fields().to_vec();
+    for (name, logical_type) in column_logical_types {
+        let field = fields
+            .iter_mut()
+            .find(|field| field.name() == name)
+            .with_context(|| format!("Unknown top-level column {}", name))?;
+        let Type::PrimitiveType {
+            basic_info,
+            physical_type,
+            type_length,
+            scale,
+            precision,
+        } = field.as_ref()
+        else {
+            bail!("Cannot set logical type of non-primitive column {}", name);
+        };
+        let new_field = Type::primitive_type_builder(name, *physical_type)
+            .with_repetition(basic_info.repetition())
+            .with_logical_type(Some(logical_type.clone()))
+            .with_length(*type_length)
+            .with_precision(*precision)
+            .with_scale(*scale)
+            .with_id(basic_info.has_id().then(|| basic_info.id()))
+            .build()
+            .with_context(|| {
+                format!(
+                    "Logical type {:?} is incompatible with column {} of physical type {}",
+                    logical_type, name, physical_type
+                )
+            })?;
+        *field = Arc::new(new_field);
+    }
+    let root = Type::group_type_builder(root.name())
+        .with_fields(fields)
+        .build()
+        .context("Could not build Parquet schema")?;
+    Ok(SchemaDescriptor::new(Arc::new(root)))
 }
 
 impl<Builder: Default + StructArrayBuilder> Drop for ParquetTableWriter<Builder> {
     fn drop(&mut self) {
+        if self.poisoned {
+            if let Some((path, _file_writer)) = &self.file_writer {
+                tracing::warn!(
+                    "Not closing {} on drop, as a previous write to it failed",
+                    path.display()
+                );
+            }
+            return;
+        }
         if self.file_writer.is_some() {
+            if self.last_file_is_full && self.builder.len() > 0 {
+                tracing::warn!(
+                    "Discarding {} rows of {} on drop, as max_files_per_writer ({:?}) is reached",
+                    self.builder.len(),
+                    self.base_path.display(),
+                    self.max_files_per_writer
+                );
+                std::mem::take(&mut self.builder);
+            }
             self.flush().unwrap();
-            let (path, file_writer) = self.file_writer
-                .take()
-                .unwrap();
-            file_writer
+            let (path, file_writer) = self.file_writer.take().unwrap();
+            let metadata = file_writer
                 .close()
                 .with_context(|| format!("Could not close {}", path.display()))
                 .unwrap();
+            let path = self.rename_closed_file(path, &metadata).unwrap();
+            self.check_row_group_sizes(&path, &metadata);
+            self.write_row_group_metadata_sidecar(&path).unwrap();
+            self.write_bitmap_indexes(&path).unwrap();
+            self.remove_schema_sidecar().unwrap();
+            self.write_index().unwrap();
+            self.release_closed_file(&path);
+            self.warn_small_row_groups();
         }
     }
 }
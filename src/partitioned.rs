@@ -3,16 +3,118 @@
 // License: GNU General Public License version 3, or any later version
 // See top-level LICENSE file for more information
 
-use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::num::NonZeroU16;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{ensure, Context, Result};
+#[cfg(feature = "arrow")]
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch, StructArray, UInt32Array};
+#[cfg(feature = "arrow")]
+use arrow::compute::{cast, lexsort_to_indices, partition, take, SortColumn};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, UInt16Type};
 use rayon::prelude::*;
 
-use crate::TableWriter;
+#[cfg(feature = "arrow")]
+use crate::StructArrayTableWriter;
+use crate::{PartitionLayout, TableWriter};
+
+/// Returns the configuration of the writer of a partition, given its key (as written
+/// in the partition's directory name)
+pub type ConfigForPartition<PartitionConfig> = Arc<dyn Fn(&str) -> PartitionConfig + Send + Sync>;
+
+/// Configuration of [`U16PartitionedTableWriter`] and [`Utf8PartitionedTableWriter`]
+#[derive(Default, Clone)]
+pub struct PartitionedTableWriterConfig<PartitionConfig> {
+    /// Configuration passed to the writer of each partition
+    pub partition_config: PartitionConfig,
+    /// If `true`, the partition column and value are added to the schema metadata
+    /// of each file (for formats which support it), so files read in isolation still
+    /// describe which partition they belong to.
+    pub embed_partition_metadata: bool,
+    /// If set, called when creating the writer of each partition, and its result is
+    /// used instead of [`partition_config`](Self::partition_config). This allows
+    /// configuring partitions differently, eg. with larger flush thresholds for
+    /// partitions expected to get more rows.
+    ///
+    /// [`partition_config`](Self::partition_config) is still used to write
+    /// `_common_metadata`.
+    pub config_for_partition: Option<ConfigForPartition<PartitionConfig>>,
+}
+
+impl<PartitionConfig: std::fmt::Debug> std::fmt::Debug
+    for PartitionedTableWriterConfig<PartitionConfig>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let PartitionedTableWriterConfig {
+            partition_config,
+            embed_partition_metadata,
+            config_for_partition,
+        } = self;
+        f.debug_struct("PartitionedTableWriterConfig")
+            .field("partition_config", partition_config)
+            .field("embed_partition_metadata", embed_partition_metadata)
+            .field(
+                "config_for_partition",
+                &config_for_partition.as_ref().map(|_| "<callback>"),
+            )
+            .finish()
+    }
+}
+
+impl<PartitionConfig: Clone> PartitionedTableWriterConfig<PartitionConfig> {
+    /// Returns the configuration of the writer of the partition with the given key
+    fn config_for(&self, partition_key: &str) -> PartitionConfig {
+        match &self.config_for_partition {
+            Some(config_for_partition) => config_for_partition(partition_key),
+            None => self.partition_config.clone(),
+        }
+    }
+}
+
+/// Returns the directory of a partition of a [`U16PartitionedTableWriter`] or
+/// [`LazyU16PartitionedTableWriter`]
+fn u16_partition_path(
+    path: &Path,
+    partition_column: &str,
+    num_partitions: Option<NonZeroU16>,
+    partition_id: u16,
+) -> PathBuf {
+    if num_partitions.is_some() {
+        path.join(format!("{}={}", partition_column, partition_id))
+    } else {
+        // Partitioning disabled
+        path.to_owned()
+    }
+}
+
+/// Returns the schema of a partition of a [`U16PartitionedTableWriter`] or
+/// [`LazyU16PartitionedTableWriter`]
+fn u16_partition_schema<PartitionWriter: TableWriter>(
+    schema: &PartitionWriter::Schema,
+    partition_column: &str,
+    num_partitions: Option<NonZeroU16>,
+    partition_id: u16,
+    embed_partition_metadata: bool,
+) -> PartitionWriter::Schema {
+    if embed_partition_metadata && num_partitions.is_some() {
+        PartitionWriter::schema_with_metadata(
+            schema.clone(),
+            partition_column,
+            &partition_id.to_string(),
+        )
+    } else {
+        schema.clone()
+    }
+}
+
+/// Partition key used by Hive and Spark for null values, see
+/// [`Utf8PartitionedTableWriter::partition_opt`]
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
 
 /// Alias of [`U16PartitionedTableWriter`] for backward compatibility
 pub type PartitionedTableWriter<PartitionWriter> = U16PartitionedTableWriter<PartitionWriter>;
@@ -24,17 +126,23 @@ pub type PartitionedTableWriter<PartitionWriter> = U16PartitionedTableWriter<Par
 /// thread id in the example above).
 ///
 /// If `num_partitions` is `None`, disables partitioning.
+///
+/// Partitioned writers can be nested to get multiple partitioning levels; for example
+/// `U16PartitionedTableWriter<Utf8PartitionedTableWriter<W>>` writes to
+/// `base/<u16_column>=<i>/<utf8_column>=<key>/x.parquet`.
 pub struct U16PartitionedTableWriter<PartitionWriter: TableWriter + Send> {
     partition_writers: Vec<PartitionWriter>,
 }
 
-impl<PartitionWriter: TableWriter + Send> TableWriter
-    for U16PartitionedTableWriter<PartitionWriter>
+impl<PartitionWriter: TableWriter + Send> TableWriter for U16PartitionedTableWriter<PartitionWriter>
+where
+    PartitionWriter::Schema: Sync,
+    PartitionWriter::Config: Sync,
 {
     /// `(partition_column, num_partitions, underlying_schema)`
     type Schema = (String, Option<NonZeroU16>, PartitionWriter::Schema);
     type CloseResult = Vec<PartitionWriter::CloseResult>;
-    type Config = PartitionWriter::Config;
+    type Config = PartitionedTableWriterConfig<PartitionWriter::Config>;
 
     fn new(
         mut path: PathBuf,
@@ -51,27 +159,63 @@ impl<PartitionWriter: TableWriter + Send> TableWriter
         );
         let thread_id = thread_id.unwrap();
         Ok(U16PartitionedTableWriter {
+            // Partitions are independent, so create them in parallel as there may be many
             partition_writers: (0..num_partitions.map(NonZeroU16::get).unwrap_or(1))
+                .into_par_iter()
                 .map(|partition_id| {
-                    let partition_path = if num_partitions.is_some() {
-                        path.join(format!("{}={}", partition_column, partition_id))
-                    } else {
-                        // Partitioning disabled
-                        path.to_owned()
-                    };
+                    let partition_path =
+                        u16_partition_path(&path, &partition_column, num_partitions, partition_id);
                     std::fs::create_dir_all(&partition_path).with_context(|| {
                         format!("Could not create {}", partition_path.display())
                     })?;
+                    let schema = u16_partition_schema::<PartitionWriter>(
+                        &schema,
+                        &partition_column,
+                        num_partitions,
+                        partition_id,
+                        config.embed_partition_metadata,
+                    );
                     PartitionWriter::new(
                         partition_path.join(&thread_id),
-                        schema.clone(),
-                        config.clone(),
+                        schema,
+                        config.config_for(&partition_id.to_string()),
                     )
                 })
                 .collect::<Result<_>>()?,
         })
     }
 
+    fn schema_with_metadata(
+        (partition_column, num_partitions, schema): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        (
+            partition_column,
+            num_partitions,
+            PartitionWriter::schema_with_metadata(schema, key, value),
+        )
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        (_partition_column, _num_partitions, schema): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        PartitionWriter::write_common_metadata(path, schema, &config.partition_config)
+    }
+
+    fn partitioning(
+        (partition_column, _num_partitions, schema): &Self::Schema,
+    ) -> Vec<PartitionLayout> {
+        let mut partitioning = vec![PartitionLayout {
+            column: partition_column.clone(),
+            key_type: "u16",
+        }];
+        partitioning.extend(PartitionWriter::partitioning(schema));
+        partitioning
+    }
+
     fn flush(&mut self) -> Result<()> {
         self.partition_writers
             .par_iter_mut()
@@ -84,6 +228,32 @@ impl<PartitionWriter: TableWriter + Send> TableWriter
             .map(|writer| writer.close())
             .collect()
     }
+    fn written_rows(&self) -> u64 {
+        self.partition_writers
+            .iter()
+            .map(|writer| writer.written_rows())
+            .sum()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.partition_writers
+            .iter()
+            .map(|writer| writer.written_bytes())
+            .sum()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.partition_writers
+            .iter()
+            .map(|writer| writer.buffer_size())
+            .sum()
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        for writer in &mut self.partition_writers {
+            writer.set_file_metadata(key, value);
+        }
+    }
 }
 
 impl<PartitionWriter: TableWriter + Send> U16PartitionedTableWriter<PartitionWriter> {
@@ -92,19 +262,225 @@ impl<PartitionWriter: TableWriter + Send> U16PartitionedTableWriter<PartitionWri
     }
 }
 
+/// Configuration of [`LazyU16PartitionedTableWriter`]
+#[derive(Debug, Default, Clone)]
+pub struct LazyU16PartitionedTableWriterConfig<PartitionConfig> {
+    pub partitioned: PartitionedTableWriterConfig<PartitionConfig>,
+    /// If `true`, the directories of all partitions are created by
+    /// [`TableWriter::new`], even though their writers are only created on first use,
+    /// so the set of directories does not depend on the data.
+    pub create_dirs_eagerly: bool,
+}
+
+/// Same as [`U16PartitionedTableWriter`], but the writer of each partition is only
+/// created when it is first accessed with
+/// [`partition`](LazyU16PartitionedTableWriter::partition), so partitions without
+/// rows have no files.
+pub struct LazyU16PartitionedTableWriter<PartitionWriter: TableWriter + Send> {
+    path: PathBuf,
+    partition_column: String,
+    num_partitions: Option<NonZeroU16>,
+    schema: PartitionWriter::Schema,
+    config: PartitionedTableWriterConfig<PartitionWriter::Config>,
+    thread_id: OsString,
+    partition_writers: Vec<Option<PartitionWriter>>,
+    /// Set by [`TableWriter::set_file_metadata`], and set on each new partition
+    file_metadata: Vec<(String, String)>,
+}
+
+impl<PartitionWriter: TableWriter + Send> TableWriter
+    for LazyU16PartitionedTableWriter<PartitionWriter>
+{
+    /// `(partition_column, num_partitions, underlying_schema)`
+    type Schema = (String, Option<NonZeroU16>, PartitionWriter::Schema);
+    type CloseResult = Vec<PartitionWriter::CloseResult>;
+    type Config = LazyU16PartitionedTableWriterConfig<PartitionWriter::Config>;
+
+    fn new(
+        mut path: PathBuf,
+        (partition_column, num_partitions, schema): Self::Schema,
+        config: Self::Config,
+    ) -> Result<Self> {
+        // Remove the last part of the path (the thread id), so we can insert the
+        // partition number between the base path and the thread id.
+        let thread_id = path.file_name().map(|p| p.to_owned());
+        ensure!(
+            path.pop(),
+            "Unexpected root path for partitioned writer: {}",
+            path.display()
+        );
+        let thread_id = thread_id.unwrap();
+        let partition_ids = 0..num_partitions.map(NonZeroU16::get).unwrap_or(1);
+        if config.create_dirs_eagerly {
+            partition_ids
+                .clone()
+                .into_par_iter()
+                .try_for_each(|partition_id| {
+                    let partition_path =
+                        u16_partition_path(&path, &partition_column, num_partitions, partition_id);
+                    std::fs::create_dir_all(&partition_path)
+                        .with_context(|| format!("Could not create {}", partition_path.display()))
+                })?;
+        }
+        Ok(LazyU16PartitionedTableWriter {
+            path,
+            partition_column,
+            num_partitions,
+            schema,
+            config: config.partitioned,
+            thread_id,
+            partition_writers: partition_ids.map(|_| None).collect(),
+            file_metadata: Vec::new(),
+        })
+    }
+
+    fn schema_with_metadata(
+        (partition_column, num_partitions, schema): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        (
+            partition_column,
+            num_partitions,
+            PartitionWriter::schema_with_metadata(schema, key, value),
+        )
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        (_partition_column, _num_partitions, schema): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        PartitionWriter::write_common_metadata(path, schema, &config.partitioned.partition_config)
+    }
+
+    fn partitioning(
+        (partition_column, _num_partitions, schema): &Self::Schema,
+    ) -> Vec<PartitionLayout> {
+        let mut partitioning = vec![PartitionLayout {
+            column: partition_column.clone(),
+            key_type: "u16",
+        }];
+        partitioning.extend(PartitionWriter::partitioning(schema));
+        partitioning
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.partition_writers
+            .par_iter_mut()
+            .flatten()
+            .try_for_each(|writer| writer.flush())
+    }
+
+    /// Returns the results of partitions which were created, in order
+    fn close(self) -> Result<Self::CloseResult> {
+        self.partition_writers
+            .into_par_iter()
+            .flatten()
+            .map(|writer| writer.close())
+            .collect()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.partition_writers
+            .iter()
+            .flatten()
+            .map(|writer| writer.written_rows())
+            .sum()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.partition_writers
+            .iter()
+            .flatten()
+            .map(|writer| writer.written_bytes())
+            .sum()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.partition_writers
+            .iter()
+            .flatten()
+            .map(|writer| writer.buffer_size())
+            .sum()
+    }
+
+    /// Also applies to partitions created later.
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        for writer in self.partition_writers.iter_mut().flatten() {
+            writer.set_file_metadata(key, value);
+        }
+        self.file_metadata.push((key.to_owned(), value.to_owned()));
+    }
+}
+
+impl<PartitionWriter: TableWriter + Send> LazyU16PartitionedTableWriter<PartitionWriter> {
+    /// Returns the writer of the given partition, creating it if needed.
+    ///
+    /// If partitioning is disabled (`num_partitions` is `None`), the only partition is 0.
+    pub fn partition(&mut self, partition_id: u16) -> Result<&mut PartitionWriter> {
+        let slot = self
+            .partition_writers
+            .get_mut(usize::from(partition_id))
+            .with_context(|| {
+                format!(
+                    "Partition {} is out of range for {} partitions",
+                    partition_id,
+                    self.num_partitions.map(NonZeroU16::get).unwrap_or(1)
+                )
+            })?;
+        if slot.is_none() {
+            let partition_path = u16_partition_path(
+                &self.path,
+                &self.partition_column,
+                self.num_partitions,
+                partition_id,
+            );
+            std::fs::create_dir_all(&partition_path)
+                .with_context(|| format!("Could not create {}", partition_path.display()))?;
+            let schema = u16_partition_schema::<PartitionWriter>(
+                &self.schema,
+                &self.partition_column,
+                self.num_partitions,
+                partition_id,
+                self.config.embed_partition_metadata,
+            );
+            let mut writer = PartitionWriter::new(
+                partition_path.join(&self.thread_id),
+                schema,
+                self.config.config_for(&partition_id.to_string()),
+            )?;
+            for (key, value) in &self.file_metadata {
+                writer.set_file_metadata(key, value);
+            }
+            *slot = Some(writer);
+        }
+        Ok(slot
+            .as_mut()
+            .expect("Partition writer is unexpectedly None"))
+    }
+}
+
 /// Wraps a set of [`TableWriter`] in such a way that they each write to a different
 /// `base/<partition_key>/x.parquet` instead of `base/x.parquet`, where `<partition_key>`
 /// is a UTF8 column.
 ///
 /// This allows Hive partitioning while writing with multiple threads (`x` is the
 /// thread id in the example above).
+///
+/// Like [`U16PartitionedTableWriter`], it can be nested within (or wrap) other partitioned
+/// writers to get multiple partitioning levels.
 pub struct Utf8PartitionedTableWriter<PartitionWriter: TableWriter + Send> {
     path: PathBuf,
     partition_column: String,
     schema: PartitionWriter::Schema,
-    config: PartitionWriter::Config,
+    config: PartitionedTableWriterConfig<PartitionWriter::Config>,
     thread_id: OsString,
     partition_writers: HashMap<String, PartitionWriter>,
+    /// Number of writers opened so far for each partition, including closed ones
+    num_opened_writers: HashMap<String, u64>,
+    /// Set by [`TableWriter::set_file_metadata`], and set on each new partition
+    file_metadata: Vec<(String, String)>,
 }
 
 impl<PartitionWriter: TableWriter + Send> TableWriter
@@ -113,7 +489,7 @@ impl<PartitionWriter: TableWriter + Send> TableWriter
     /// `(partition_column, num_partitions, underlying_schema)`
     type Schema = (String, PartitionWriter::Schema);
     type CloseResult = Vec<PartitionWriter::CloseResult>;
-    type Config = PartitionWriter::Config;
+    type Config = PartitionedTableWriterConfig<PartitionWriter::Config>;
 
     fn new(
         mut path: PathBuf,
@@ -136,9 +512,39 @@ impl<PartitionWriter: TableWriter + Send> TableWriter
             config,
             thread_id,
             partition_writers: HashMap::new(),
+            num_opened_writers: HashMap::new(),
+            file_metadata: Vec::new(),
         })
     }
 
+    fn schema_with_metadata(
+        (partition_column, schema): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        (
+            partition_column,
+            PartitionWriter::schema_with_metadata(schema, key, value),
+        )
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        (_partition_column, schema): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        PartitionWriter::write_common_metadata(path, schema, &config.partition_config)
+    }
+
+    fn partitioning((partition_column, schema): &Self::Schema) -> Vec<PartitionLayout> {
+        let mut partitioning = vec![PartitionLayout {
+            column: partition_column.clone(),
+            key_type: "utf8",
+        }];
+        partitioning.extend(PartitionWriter::partitioning(schema));
+        partitioning
+    }
+
     fn flush(&mut self) -> Result<()> {
         self.partition_writers
             .par_iter_mut()
@@ -151,27 +557,250 @@ impl<PartitionWriter: TableWriter + Send> TableWriter
             .map(|(_partitiong_key, writer)| writer.close())
             .collect()
     }
+    fn written_rows(&self) -> u64 {
+        self.partition_writers
+            .values()
+            .map(|writer| writer.written_rows())
+            .sum()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.partition_writers
+            .values()
+            .map(|writer| writer.written_bytes())
+            .sum()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.partition_writers
+            .values()
+            .map(|writer| writer.buffer_size())
+            .sum()
+    }
+
+    /// Also applies to partitions created later.
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        for writer in self.partition_writers.values_mut() {
+            writer.set_file_metadata(key, value);
+        }
+        self.file_metadata.push((key.to_owned(), value.to_owned()));
+    }
 }
 
 impl<PartitionWriter: TableWriter + Send> Utf8PartitionedTableWriter<PartitionWriter> {
+    /// Returns the writer of the given partition, creating it if needed.
+    ///
+    /// If the partition was closed by [`close_partition`](Self::close_partition) (or
+    /// removed from [`partitions`](Self::partitions)), the new writer writes to
+    /// `<thread_id>_<n>` instead of `<thread_id>`, where `<n>` is the number of writers
+    /// previously opened for this partition, so it does not overwrite earlier files.
     pub fn partition(&mut self, partition_key: String) -> Result<&mut PartitionWriter> {
         match self.partition_writers.entry(partition_key) {
             Entry::Occupied(entry) => Ok(entry.into_mut()),
             Entry::Vacant(entry) => {
-                let partition_path = self
-                    .path
-                    .join(format!("{}={}", self.partition_column, entry.key()));
+                let partition_path =
+                    self.path
+                        .join(format!("{}={}", self.partition_column, entry.key()));
                 std::fs::create_dir_all(&partition_path)
                     .with_context(|| format!("Could not create {}", partition_path.display()))?;
-                Ok(entry.insert(PartitionWriter::new(
-                    partition_path.join(&self.thread_id),
-                    self.schema.clone(),
-                    self.config.clone(),
-                )?))
+                let schema = if self.config.embed_partition_metadata {
+                    PartitionWriter::schema_with_metadata(
+                        self.schema.clone(),
+                        &self.partition_column,
+                        entry.key(),
+                    )
+                } else {
+                    self.schema.clone()
+                };
+                let num_opened_writers = self
+                    .num_opened_writers
+                    .entry(entry.key().clone())
+                    .or_default();
+                let mut file_name = self.thread_id.clone();
+                if *num_opened_writers > 0 {
+                    file_name.push(format!("_{}", num_opened_writers));
+                }
+                let mut writer = PartitionWriter::new(
+                    partition_path.join(file_name),
+                    schema,
+                    self.config.config_for(entry.key()),
+                )?;
+                *num_opened_writers += 1;
+                for (key, value) in &self.file_metadata {
+                    writer.set_file_metadata(key, value);
+                }
+                Ok(entry.insert(writer))
             }
         }
     }
+
+    /// Same as [`partition`](Self::partition), but `None` is mapped to
+    /// [`HIVE_DEFAULT_PARTITION`], which Hive and Spark read as a null value.
+    pub fn partition_opt(&mut self, partition_key: Option<String>) -> Result<&mut PartitionWriter> {
+        self.partition(partition_key.unwrap_or_else(|| HIVE_DEFAULT_PARTITION.to_owned()))
+    }
+
     pub fn partitions(&mut self) -> &mut HashMap<String, PartitionWriter> {
         &mut self.partition_writers
     }
+
+    /// Closes the writer of the given partition, if it is open, and returns its result.
+    ///
+    /// This limits the number of open files when partitions are written one after the
+    /// other. The partition is reopened (with a new file) by the next call to
+    /// [`partition`](Self::partition) with the same key.
+    pub fn close_partition(
+        &mut self,
+        partition_key: &str,
+    ) -> Result<Option<PartitionWriter::CloseResult>> {
+        self.partition_writers
+            .remove(partition_key)
+            .map(|writer| writer.close())
+            .transpose()
+    }
+}
+
+/// Groups rows of `batch` by value of `partition_column`, with arrow kernels.
+///
+/// Returns `partition_column` cast to `data_type`, and the rows of each partition
+/// along with the index of one of its rows in the cast column. Rows keep their
+/// relative order within each partition.
+#[cfg(feature = "arrow")]
+fn split_batch_by_column(
+    batch: RecordBatch,
+    partition_column: &str,
+    data_type: &DataType,
+) -> Result<(ArrayRef, Vec<(usize, StructArray)>)> {
+    let column = batch
+        .column_by_name(partition_column)
+        .with_context(|| format!("Unknown partition column {}", partition_column))?;
+    let column = cast(column, data_type).with_context(|| {
+        format!(
+            "Could not cast partition column {} to {}",
+            partition_column, data_type
+        )
+    })?;
+    // Values which cannot be cast are turned into nulls
+    ensure!(
+        column.null_count() == 0,
+        "Partition column {} contains nulls or values which cannot be cast to {}",
+        partition_column,
+        data_type
+    );
+
+    // Sort by partition, using row indices as tie-breaker to keep the order of rows
+    let num_rows = u32::try_from(batch.num_rows()).context("Batch has too many rows")?;
+    let indices = lexsort_to_indices(
+        &[
+            SortColumn {
+                values: column.clone(),
+                options: None,
+            },
+            SortColumn {
+                values: Arc::new(UInt32Array::from_iter_values(0..num_rows)),
+                options: None,
+            },
+        ],
+        None,
+    )
+    .context("Could not sort batch by partition")?;
+    let struct_array = StructArray::from(batch);
+    let sorted_array = take(&struct_array, &indices, None)
+        .context("Could not sort batch by partition")?
+        .as_struct()
+        .clone();
+    let sorted_column =
+        take(&column, &indices, None).context("Could not sort batch by partition")?;
+
+    let ranges = partition(std::slice::from_ref(&sorted_column))
+        .context("Could not split batch by partition")?
+        .ranges();
+    let partitions = ranges
+        .into_iter()
+        .map(|range| (range.start, sorted_array.slice(range.start, range.len())))
+        .collect();
+    Ok((sorted_column, partitions))
+}
+
+#[cfg(feature = "arrow")]
+impl<PartitionWriter: StructArrayTableWriter + Send> U16PartitionedTableWriter<PartitionWriter>
+where
+    PartitionWriter::Schema: Sync,
+    PartitionWriter::Config: Sync,
+{
+    /// Splits `batch` by value of `partition_column` (which must contain integers
+    /// castable to `u16` and no null), and writes the rows of each partition to its
+    /// writer.
+    ///
+    /// The partition column is written too.
+    pub fn write_partitioned_batch(
+        &mut self,
+        batch: RecordBatch,
+        partition_column: &str,
+    ) -> Result<()> {
+        let (column, partitions) =
+            split_batch_by_column(batch, partition_column, &DataType::UInt16)?;
+        let column = column.as_primitive::<UInt16Type>();
+        let num_partitions = self.partition_writers.len();
+        for (row, partition_array) in partitions {
+            let partition_id = column.value(row);
+            self.partition_writers
+                .get_mut(usize::from(partition_id))
+                .with_context(|| {
+                    format!(
+                        "Partition {} is out of range for {} partitions",
+                        partition_id, num_partitions
+                    )
+                })?
+                .write_struct_array(partition_array)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl<PartitionWriter: StructArrayTableWriter + Send>
+    LazyU16PartitionedTableWriter<PartitionWriter>
+{
+    /// Splits `batch` by value of `partition_column` (which must contain integers
+    /// castable to `u16` and no null), and writes the rows of each partition to its
+    /// writer, creating it if needed.
+    ///
+    /// The partition column is written too.
+    pub fn write_partitioned_batch(
+        &mut self,
+        batch: RecordBatch,
+        partition_column: &str,
+    ) -> Result<()> {
+        let (column, partitions) =
+            split_batch_by_column(batch, partition_column, &DataType::UInt16)?;
+        let column = column.as_primitive::<UInt16Type>();
+        for (row, partition_array) in partitions {
+            self.partition(column.value(row))?
+                .write_struct_array(partition_array)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl<PartitionWriter: StructArrayTableWriter + Send> Utf8PartitionedTableWriter<PartitionWriter> {
+    /// Splits `batch` by value of `partition_column` (which must contain values
+    /// castable to strings and no null), and writes the rows of each partition to its
+    /// writer, creating it if needed.
+    ///
+    /// The partition column is written too.
+    pub fn write_partitioned_batch(
+        &mut self,
+        batch: RecordBatch,
+        partition_column: &str,
+    ) -> Result<()> {
+        let (column, partitions) = split_batch_by_column(batch, partition_column, &DataType::Utf8)?;
+        let column = column.as_string::<i32>();
+        for (row, partition_array) in partitions {
+            self.partition(column.value(row).to_owned())?
+                .write_struct_array(partition_array)?;
+        }
+        Ok(())
+    }
 }
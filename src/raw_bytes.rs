@@ -0,0 +1,168 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::{TableWriter, ZstFile};
+
+#[derive(Debug, Clone)]
+pub struct RawBytesTableWriterConfig {
+    pub extension: String,
+    /// If set, files are compressed with zstd at this level, and `.zst` is appended to
+    /// their extension.
+    pub compression_level: Option<i32>,
+    /// Automatically flushes the buffer to disk when its length (in bytes) reaches
+    /// the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+}
+
+impl Default for RawBytesTableWriterConfig {
+    fn default() -> Self {
+        RawBytesTableWriterConfig {
+            extension: "bin".to_owned(),
+            compression_level: None,
+            flush_threshold: None,
+        }
+    }
+}
+
+/// File written by [`RawBytesTableWriter`]
+enum OutputFile {
+    Plain(File),
+    Zstd(ZstFile<'static>),
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Zstd(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Zstd(file) => Write::flush(file),
+        }
+    }
+}
+
+/// Writer of bytes serialized by the caller, usable with
+/// [`ParallelDatasetWriter`](super::ParallelDatasetWriter)
+///
+/// This allows writing formats this crate does not support, while still relying on
+/// it for the lifecycle of files (one per thread, partitioning, ...). Bytes appended
+/// to the [`builder`](Self::builder) are written as-is to the file on each flush.
+pub struct RawBytesTableWriter {
+    path: PathBuf,
+    file: Option<OutputFile>, // None only between .close() call and Drop
+    buffer: Vec<u8>,
+    num_written_bytes: u64,
+    /// Set when a write fails, so `Drop` does not try to write to the file again
+    poisoned: bool,
+    pub flush_threshold: usize,
+}
+
+impl TableWriter for RawBytesTableWriter {
+    type Schema = ();
+    type CloseResult = ();
+    type Config = RawBytesTableWriterConfig;
+
+    fn new(
+        mut path: PathBuf,
+        _schema: Self::Schema,
+        RawBytesTableWriterConfig {
+            extension,
+            compression_level,
+            flush_threshold,
+        }: Self::Config,
+    ) -> Result<Self> {
+        let file = match compression_level {
+            None => {
+                path.set_extension(extension);
+                OutputFile::Plain(
+                    File::create(&path)
+                        .with_context(|| format!("Could not create {}", path.display()))?,
+                )
+            }
+            Some(compression_level) => {
+                path.set_extension(format!("{}.zst", extension));
+                OutputFile::Zstd(ZstFile::create(path.clone(), compression_level, false)?)
+            }
+        };
+        Ok(RawBytesTableWriter {
+            path,
+            file: Some(file),
+            buffer: Vec::new(),
+            num_written_bytes: 0,
+            poisoned: false,
+            flush_threshold: flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file
+            .as_mut()
+            .expect("File is unexpectedly None")
+            .write_all(&self.buffer)
+            .inspect_err(|_| self.poisoned = true)
+            .with_context(|| format!("Could not write to {}", self.path.display()))?;
+        self.num_written_bytes += u64::try_from(self.buffer.len()).expect("usize overflowed u64");
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.flush()?;
+        match self.file.take().expect("File is unexpectedly None") {
+            OutputFile::Plain(mut file) => file
+                .flush()
+                .with_context(|| format!("Could not close {}", self.path.display())),
+            OutputFile::Zstd(mut file) => file.finalize().map(|_| ()),
+        }
+    }
+
+    /// Number of bytes flushed so far, before compression
+    fn written_bytes(&self) -> u64 {
+        self.num_written_bytes
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl RawBytesTableWriter {
+    /// Flushes the internal buffer is too large, then returns it.
+    pub fn builder(&mut self) -> Result<&mut Vec<u8>> {
+        if self.buffer.len() >= self.flush_threshold {
+            self.flush()?;
+        }
+
+        Ok(&mut self.buffer)
+    }
+}
+
+impl Drop for RawBytesTableWriter {
+    fn drop(&mut self) {
+        if self.poisoned {
+            tracing::warn!(
+                "Not flushing {} on drop, as a previous write to it failed",
+                self.path.display()
+            );
+            return;
+        }
+        if self.file.is_some() {
+            self.flush().unwrap();
+        }
+    }
+}
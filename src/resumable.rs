@@ -0,0 +1,199 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{ensure, Context, Result};
+use arrow::array::StructArray;
+use arrow::datatypes::Schema;
+use parquet::arrow::arrow_reader::{
+    ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder,
+};
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::WriterProperties;
+
+use crate::{
+    ParquetTableWriter, ParquetTableWriterConfig, PartitionLayout, StructArrayBuilder,
+    StructArrayTableWriter, TableWriter,
+};
+
+/// Suffix appended to the path of a file being resumed by [`ResumableParquetWriter`],
+/// while its content is copied to the new file
+pub const RESUME_SUFFIX: &str = ".resume";
+
+/// Wraps a [`ParquetTableWriter`] so that, if the file it would write already exists
+/// (eg. because a previous run of the program was interrupted after closing it), new
+/// rows are appended to it instead of overwriting it.
+///
+/// As Parquet files cannot be appended to once their footer is written, this is done
+/// by renaming the existing file (appending [`RESUME_SUFFIX`] to its path), then
+/// copying all its rows to a new file at the original path, one row group at a time,
+/// before any new row is written. **This rewrites the whole file** every time it is
+/// resumed, so resuming a large file many times is expensive; it is meant for
+/// occasional restarts.
+///
+/// The renamed file is only removed once the new file is closed, so if the program is
+/// interrupted before that, the next resume uses it again (instead of the partial new
+/// file, which has no footer).
+///
+/// Rows are copied in order, so the final file contains the rows of the previous
+/// file followed by the new rows. Key-value metadata of the previous file and its
+/// sidecars are not copied, but row-based sidecars (such as
+/// [bitmap indexes](ParquetTableWriterConfig::bitmap_index_columns)) are rebuilt.
+/// Only the first file of the writer is resumed, so the previous run must not have
+/// had enough row groups to open a second file.
+pub struct ResumableParquetWriter<Builder: Default + StructArrayBuilder> {
+    inner: ParquetTableWriter<Builder>,
+    /// Path of the renamed previous file, if any
+    resume_path: Option<PathBuf>,
+    resumed_rows: u64,
+}
+
+impl<Builder: Default + StructArrayBuilder> TableWriter for ResumableParquetWriter<Builder> {
+    type Schema = (Arc<Schema>, WriterProperties);
+    type CloseResult = ParquetMetaData;
+    type Config = ParquetTableWriterConfig;
+
+    fn new(path: PathBuf, schema: Self::Schema, config: Self::Config) -> Result<Self> {
+        ensure!(
+            !config.encode_row_count_in_name,
+            "ResumableParquetWriter does not support encode_row_count_in_name"
+        );
+        let mut data_path = path.clone();
+        data_path.set_extension("parquet");
+        let mut resume_path = data_path.clone().into_os_string();
+        resume_path.push(RESUME_SUFFIX);
+        let resume_path = PathBuf::from(resume_path);
+
+        if resume_path.exists() && has_footer(&data_path) {
+            // The previous run closed the new file, but was interrupted before removing
+            // the file it resumed from, which is therefore outdated
+            std::fs::remove_file(&resume_path)
+                .with_context(|| format!("Could not remove {}", resume_path.display()))?;
+        }
+        // Otherwise, if there is a resume file, a previous resume was interrupted and
+        // left a partial file at data_path, which is about to be overwritten.
+        if !resume_path.exists() && data_path.exists() {
+            std::fs::rename(&data_path, &resume_path).with_context(|| {
+                format!(
+                    "Could not rename {} to {}",
+                    data_path.display(),
+                    resume_path.display()
+                )
+            })?;
+        }
+        let resume_path = resume_path.exists().then_some(resume_path);
+
+        let mut writer = ResumableParquetWriter {
+            inner: ParquetTableWriter::new(path, schema, config)?,
+            resume_path,
+            resumed_rows: 0,
+        };
+        if let Some(resume_path) = writer.resume_path.clone() {
+            writer.copy_rows_from(&resume_path)?;
+        }
+        Ok(writer)
+    }
+
+    fn schema_with_metadata(schema: Self::Schema, key: &str, value: &str) -> Self::Schema {
+        ParquetTableWriter::<Builder>::schema_with_metadata(schema, key, value)
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        schema: &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        ParquetTableWriter::<Builder>::write_common_metadata(path, schema, config)
+    }
+
+    fn partitioning(schema: &Self::Schema) -> Vec<PartitionLayout> {
+        ParquetTableWriter::<Builder>::partitioning(schema)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(self) -> Result<Self::CloseResult> {
+        let metadata = self.inner.close()?;
+        if let Some(resume_path) = self.resume_path {
+            std::fs::remove_file(&resume_path)
+                .with_context(|| format!("Could not remove {}", resume_path.display()))?;
+        }
+        Ok(metadata)
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.inner.written_rows()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.inner.written_bytes()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.inner.buffer_size()
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        self.inner.set_file_metadata(key, value)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> StructArrayTableWriter
+    for ResumableParquetWriter<Builder>
+{
+    fn write_struct_array(&mut self, struct_array: StructArray) -> Result<()> {
+        self.inner.write_struct_array(struct_array)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder> ResumableParquetWriter<Builder> {
+    /// Writes all rows of the given file, keeping its row groups
+    fn copy_rows_from(&mut self, path: &Path) -> Result<()> {
+        let file =
+            File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+        let metadata = ArrowReaderMetadata::load(&file, ArrowReaderOptions::default())
+            .with_context(|| format!("Could not read footer of {}", path.display()))?;
+        // Readers may return batches spanning several row groups, so read them one by one
+        for (row_group, row_group_metadata) in metadata.metadata().row_groups().iter().enumerate() {
+            let num_rows = row_group_metadata.num_rows();
+            let file = file
+                .try_clone()
+                .with_context(|| format!("Could not open {}", path.display()))?;
+            let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(file, metadata.clone())
+                .with_row_groups(vec![row_group])
+                .with_batch_size(usize::try_from(num_rows).expect("Negative number of rows"))
+                .build()
+                .with_context(|| format!("Could not read {}", path.display()))?;
+            for batch in reader {
+                let batch = batch.with_context(|| format!("Could not read {}", path.display()))?;
+                self.resumed_rows += u64::try_from(batch.num_rows()).expect("usize overflowed u64");
+                self.inner.write_struct_array(batch.into())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the wrapped writer
+    pub fn inner(&mut self) -> &mut ParquetTableWriter<Builder> {
+        &mut self.inner
+    }
+
+    /// Number of rows copied from the previous file, or 0 if there was none
+    pub fn resumed_rows(&self) -> u64 {
+        self.resumed_rows
+    }
+}
+
+/// Returns whether the file exists and has a valid footer, ie. was closed
+fn has_footer(path: &Path) -> bool {
+    File::open(path)
+        .ok()
+        .is_some_and(|file| ArrowReaderMetadata::load(&file, ArrowReaderOptions::default()).is_ok())
+}
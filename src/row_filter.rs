@@ -0,0 +1,39 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::sync::Arc;
+
+use anyhow::{ensure, Context, Result};
+use arrow::array::{Array, AsArray, BooleanArray, StructArray};
+
+/// Predicate called by table writers on the rows of each flush, returning whether each
+/// row should be written; rows for which it returns `false` or null are dropped.
+///
+/// See eg. [`ParquetTableWriterConfig::row_filter`](crate::ParquetTableWriterConfig::row_filter).
+pub type RowFilter = Arc<dyn Fn(&StructArray) -> Result<BooleanArray> + Send + Sync>;
+
+/// Returns the rows of `struct_array` selected by `row_filter`
+pub(crate) fn filter_rows(
+    row_filter: &RowFilter,
+    struct_array: StructArray,
+) -> Result<StructArray> {
+    if struct_array.is_empty() {
+        return Ok(struct_array);
+    }
+    let mask = row_filter(&struct_array).context("Could not evaluate row filter")?;
+    ensure!(
+        mask.len() == struct_array.len(),
+        "Row filter returned {} values for {} rows",
+        mask.len(),
+        struct_array.len()
+    );
+    if mask.true_count() == struct_array.len() {
+        return Ok(struct_array);
+    }
+    Ok(arrow::compute::filter(&struct_array, &mask)
+        .context("Could not filter rows")?
+        .as_struct()
+        .clone())
+}
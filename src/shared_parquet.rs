@@ -0,0 +1,91 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context, Result};
+use arrow::array::StructArray;
+use arrow::datatypes::Schema;
+use parquet::arrow::ArrowWriter as ParquetWriter;
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::WriterProperties;
+
+/// Writer to a single .parquet file, shared by all threads
+///
+/// Unlike [`ParallelDatasetWriter`](crate::ParallelDatasetWriter), which gives each
+/// thread its own file, all batches go to the same file. Writes are serialized by a
+/// [`Mutex`], so threads calling [`write_batch`](Self::write_batch) concurrently wait
+/// for each other, including while row groups are encoded and compressed; this only
+/// scales if threads spend most of their time building batches rather than writing
+/// them.
+///
+/// As Parquet does not support more than 32767 row groups per file, this is not
+/// suitable for very large datasets.
+pub struct SharedParquetWriter {
+    path: PathBuf,
+    file_writer: Mutex<Option<ParquetWriter<File>>>, // None only between .close() call and Drop
+}
+
+impl SharedParquetWriter {
+    /// Creates `path` and returns a writer to it
+    pub fn new(path: PathBuf, schema: Arc<Schema>, properties: WriterProperties) -> Result<Self> {
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let file_writer = ParquetWriter::try_new(file, schema.clone(), Some(properties.clone()))
+            .with_context(|| {
+                format!(
+                    "Could not create writer for {} with schema {} and properties {:?}",
+                    path.display(),
+                    schema,
+                    properties
+                )
+            })?;
+        Ok(SharedParquetWriter {
+            path,
+            file_writer: Mutex::new(Some(file_writer)),
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Option<ParquetWriter<File>>>> {
+        self.file_writer
+            .lock()
+            .map_err(|_| anyhow!("Writer to {} is poisoned", self.path.display()))
+    }
+
+    /// Appends the given rows to the file
+    ///
+    /// Blocks while another thread is writing.
+    pub fn write_batch(&self, struct_array: StructArray) -> Result<()> {
+        self.lock()?
+            .as_mut()
+            .expect("File writer is unexpectedly None")
+            .write(&struct_array.into())
+            .with_context(|| format!("Could not write to {}", self.path.display()))
+    }
+
+    /// Writes the footer and closes the file
+    pub fn close(self) -> Result<ParquetMetaData> {
+        self.lock()?
+            .take()
+            .expect("File writer is unexpectedly None")
+            .close()
+            .with_context(|| format!("Could not close {}", self.path.display()))
+    }
+}
+
+impl Drop for SharedParquetWriter {
+    fn drop(&mut self) {
+        // If the mutex is poisoned, a thread panicked while writing, so the file is
+        // left as is.
+        if let Ok(Some(file_writer)) = self.file_writer.get_mut().map(Option::take) {
+            file_writer
+                .close()
+                .with_context(|| format!("Could not close {}", self.path.display()))
+                .unwrap();
+        }
+    }
+}
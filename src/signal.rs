@@ -0,0 +1,206 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+#[cfg(unix)]
+use signal_hook::consts::SIGTERM;
+use signal_hook::SigId;
+
+use crate::{ParallelDatasetWriter, TableWriter};
+
+/// How often the background thread of [`CloseOnSignal`] checks whether it should close
+/// the writer
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Writer shared between [`CloseOnSignal`] and its background thread, `None` once
+/// closed
+type SharedWriter<W> = Arc<RwLock<Option<ParallelDatasetWriter<W>>>>;
+
+/// Background thread of [`CloseOnSignal`], returning the results of closing the writer
+/// if it closed it
+type BackgroundThread<CloseResult> = JoinHandle<Result<Option<Vec<CloseResult>>>>;
+
+/// Owns a [`ParallelDatasetWriter`], and closes it from a background thread when a flag
+/// is set, such as on SIGTERM (see [`ParallelDatasetWriter::install_signal_handler`]).
+///
+/// The writer is used through [`with_writer`](Self::with_writer), which returns an
+/// error once the writer was closed.
+pub struct CloseOnSignal<W: TableWriter + Send + 'static> {
+    writer: SharedWriter<W>,
+    /// Set by [`close`](Self::close) to stop the background thread
+    stop: Arc<AtomicBool>,
+    /// `None` only after [`close`](Self::close) joined it
+    thread: Option<BackgroundThread<W::CloseResult>>,
+    /// Handler registered by [`ParallelDatasetWriter::install_signal_handler`], to
+    /// unregister once the writer is closed
+    signal_id: Option<SigId>,
+}
+
+impl<W: TableWriter + Send + 'static> CloseOnSignal<W>
+where
+    ParallelDatasetWriter<W>: Send + Sync,
+    W::Config: Default,
+    W::CloseResult: Send,
+{
+    /// Closes the writer from a background thread as soon as `flag` is set, which
+    /// is checked every 100ms.
+    ///
+    /// Unlike [`ParallelDatasetWriter::install_signal_handler`], this does not
+    /// terminate the process after closing the writer.
+    pub fn new(writer: ParallelDatasetWriter<W>, flag: Arc<AtomicBool>) -> Result<Self> {
+        Self::with_termination(writer, flag, None, None)
+    }
+
+    /// Same as [`new`](Self::new), but raises `terminate_with` (with its default
+    /// behavior) after closing the writer, if set.
+    fn with_termination(
+        writer: ParallelDatasetWriter<W>,
+        flag: Arc<AtomicBool>,
+        terminate_with: Option<i32>,
+        signal_id: Option<SigId>,
+    ) -> Result<Self> {
+        let writer = Arc::new(RwLock::new(Some(writer)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("dataset-writer-close-on-signal".to_owned())
+            .spawn({
+                let writer = writer.clone();
+                let stop = stop.clone();
+                move || {
+                    while !flag.load(Ordering::Relaxed) {
+                        if stop.load(Ordering::Relaxed) {
+                            return Ok(None);
+                        }
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    tracing::info!("Closing dataset writer after receiving a signal");
+                    let writer = writer
+                        .write()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .take()
+                        .expect("Writer was closed before the background thread stopped");
+                    let result = writer.close();
+                    if let Err(e) = &result {
+                        tracing::error!("Could not close dataset writer: {:#}", e);
+                    }
+                    if let Some(signal) = terminate_with {
+                        signal_hook::low_level::emulate_default_handler(signal)
+                            .context("Could not terminate process")?;
+                    }
+                    result.map(Some)
+                }
+            })
+            .context("Could not spawn thread")?;
+        Ok(CloseOnSignal {
+            writer,
+            stop,
+            thread: Some(thread),
+            signal_id,
+        })
+    }
+
+    /// Calls `f` with the writer, or returns an error if it was already closed.
+    ///
+    /// The writer is only closed once no call to this function is running, so `f`
+    /// should return quickly (eg. after writing a batch of rows) for the writer to be
+    /// closed in time.
+    pub fn with_writer<T>(
+        &self,
+        f: impl FnOnce(&ParallelDatasetWriter<W>) -> Result<T>,
+    ) -> Result<T> {
+        let writer = self.writer.read().unwrap_or_else(|e| e.into_inner());
+        f(writer
+            .as_ref()
+            .context("Dataset writer was closed after receiving a signal")?)
+    }
+
+    /// Returns whether the writer was closed by the background thread
+    pub fn is_closed(&self) -> bool {
+        self.writer
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none()
+    }
+
+    /// Closes the writer, unless the background thread already did, and stops the
+    /// background thread.
+    ///
+    /// Returns the results of closing the writer, either here or by the background
+    /// thread.
+    pub fn close(mut self) -> Result<Vec<W::CloseResult>> {
+        self.stop();
+        let results = self
+            .thread
+            .take()
+            .expect("Thread is unexpectedly None")
+            .join()
+            .map_err(|_| anyhow!("Background thread panicked"))??;
+        match results {
+            Some(results) => Ok(results),
+            None => self
+                .writer
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .take()
+                .expect("Writer is unexpectedly None")
+                .close(),
+        }
+    }
+}
+
+impl<W: TableWriter + Send + 'static> CloseOnSignal<W> {
+    /// Stops the background thread (without waiting for it) and unregisters the signal
+    /// handler, if any
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(signal_id) = self.signal_id.take() {
+            signal_hook::low_level::unregister(signal_id);
+        }
+    }
+}
+
+impl<W: TableWriter + Send + 'static> Drop for CloseOnSignal<W> {
+    /// Stops the background thread, which then drops the writer (closing it) if it
+    /// was not closed yet
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<W: TableWriter + Send + 'static> ParallelDatasetWriter<W>
+where
+    ParallelDatasetWriter<W>: Send + Sync,
+    W::Config: Default,
+    W::CloseResult: Send,
+{
+    /// Makes the writer close itself (flushing all table writers and writing their
+    /// footers) when the process receives SIGTERM, then terminates the process as
+    /// SIGTERM would by default.
+    ///
+    /// This registers a signal handler which only sets a flag, which a background
+    /// thread checks to close the writer, see [`CloseOnSignal`]. Limitations:
+    ///
+    /// * Only rows already given to table writers are written; rows the program was
+    ///   about to write are lost.
+    /// * Closing waits for running calls to [`CloseOnSignal::with_writer`] to return,
+    ///   and closing itself may take a while; the process may still be killed (eg. by
+    ///   SIGKILL after a container runtime's grace period) before it completes.
+    /// * The process is terminated right after the writer is closed, without running
+    ///   destructors or `atexit` handlers; other cleanup should use its own handler.
+    /// * The handler is unregistered when the [`CloseOnSignal`] is closed or dropped,
+    ///   so SIGTERM then terminates the process immediately again.
+    #[cfg(unix)]
+    pub fn install_signal_handler(self) -> Result<CloseOnSignal<W>> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let signal_id = signal_hook::flag::register(SIGTERM, flag.clone())
+            .context("Could not register SIGTERM handler")?;
+        CloseOnSignal::with_termination(self, flag, Some(SIGTERM), Some(signal_id))
+    }
+}
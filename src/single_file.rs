@@ -0,0 +1,107 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::path::PathBuf;
+
+#[cfg(feature = "csv")]
+use anyhow::Context;
+use anyhow::Result;
+use arrow::array::RecordBatch;
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+use arrow::array::StructArray;
+
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+use crate::{StructArrayBuilder, StructArrayTableWriter, TableWriter};
+
+/// Builder which never has any row, for table writers only used with
+/// [`StructArrayTableWriter::write_struct_array`]
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+#[derive(Default)]
+struct NoBuilder;
+
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+impl StructArrayBuilder for NoBuilder {
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn buffer_size(&self) -> usize {
+        0
+    }
+
+    fn finish(&mut self) -> Result<StructArray> {
+        Ok(StructArray::new_empty_fields(0, None))
+    }
+}
+
+/// Writes `batch` to a Parquet file at `path` (with its extension replaced by
+/// `.parquet`), and returns the file's metadata.
+#[cfg(feature = "parquet")]
+pub fn write_parquet_file(
+    path: PathBuf,
+    batch: RecordBatch,
+    properties: parquet::file::properties::WriterProperties,
+) -> Result<parquet::file::metadata::ParquetMetaData> {
+    let mut writer = crate::ParquetTableWriter::<NoBuilder>::new(
+        path,
+        (batch.schema(), properties),
+        Default::default(),
+    )?;
+    writer.write_struct_array(batch.into())?;
+    writer.close()
+}
+
+/// Writes `batch` to an Arrow IPC file at `path` (with its extension replaced by
+/// `.arrow`)
+#[cfg(feature = "arrow-ipc")]
+pub fn write_arrow_file(path: PathBuf, batch: RecordBatch) -> Result<()> {
+    let mut writer = crate::ArrowTableWriter::<NoBuilder>::new(
+        path,
+        batch.schema_ref().as_ref().clone(),
+        Default::default(),
+    )?;
+    writer.write_struct_array(batch.into())?;
+    writer.close()
+}
+
+/// Writes `batch` to a JSON Lines file at `path` (with its extension replaced by
+/// `.jsonl`)
+#[cfg(feature = "json")]
+pub fn write_jsonl_file(path: PathBuf, batch: RecordBatch) -> Result<()> {
+    let mut writer = crate::JsonlTableWriter::<NoBuilder>::new(path, (), Default::default())?;
+    writer.write_struct_array(batch.into())?;
+    writer.close()
+}
+
+/// Writes `batch` to a zstd-compressed CSV file with a header at `path` (with its
+/// extension replaced by `.csv.zst`), with the same compression level as
+/// [`CsvZstTableWriter`](crate::CsvZstTableWriter).
+///
+/// Values are formatted by [`arrow::csv`], which does not support nested types.
+#[cfg(feature = "csv")]
+pub fn write_csv_zst_file(
+    mut path: PathBuf,
+    batch: RecordBatch,
+) -> Result<crate::CsvZstCloseResult> {
+    path.set_extension("csv.zst");
+    let compression_level = 3;
+    let mut writer = arrow::csv::Writer::new(crate::ZstFile::create(
+        path.clone(),
+        compression_level,
+        false,
+    )?);
+    writer
+        .write(&batch)
+        .with_context(|| format!("Could not write to {}", path.display()))?;
+    let crate::ZstCloseResult {
+        compressed_bytes,
+        uncompressed_bytes,
+    } = writer.into_inner().finalize()?;
+    Ok(crate::CsvZstCloseResult {
+        rows_written: u64::try_from(batch.num_rows()).expect("usize overflowed u64"),
+        compressed_bytes,
+        uncompressed_bytes,
+    })
+}
@@ -0,0 +1,317 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{ensure, Context, Result};
+use arrow::array::{
+    new_empty_array, new_null_array, Array, ArrayRef, RecordBatch, StringArray, UInt64Array,
+};
+use arrow::compute::{concat, sort_to_indices, sum, take, SortOptions};
+use arrow::datatypes::{DataType, Field, Schema};
+use parquet::arrow::arrow_reader::statistics::StatisticsConverter;
+use parquet::arrow::{ArrowSchemaConverter, ArrowWriter as ParquetWriter};
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+
+use crate::{closed_file_path, ParallelDatasetWriter, ParquetTableWriter, StructArrayBuilder};
+
+impl<Builder: Default + StructArrayBuilder + Send>
+    ParallelDatasetWriter<ParquetTableWriter<Builder>>
+{
+    /// Closes all underlying writers, then writes `_stats.parquet` at the root of the
+    /// dataset, with one row per data file.
+    ///
+    /// Its columns are `path` (relative to the dataset root), `rows`, and
+    /// `<column>.min`, `<column>.max`, and `<column>.null_count` for each top-level
+    /// non-nested column. Min and max are computed from the row groups' statistics,
+    /// and are null if no row group has them.
+    pub fn close_with_stats_table(self) -> Result<Vec<ParquetMetaData>> {
+        let schema = self.schema.0.clone();
+        let dataset_path = self.path.clone();
+
+        let (close_results, paths, footers) = self.close_and_read_footers()?;
+        write_stats_table(
+            &dataset_path.join("_stats.parquet"),
+            &dataset_path,
+            &schema,
+            &paths,
+            &footers,
+        )?;
+
+        Ok(close_results)
+    }
+
+    /// Closes all underlying writers, then writes `_zonemap.parquet` at the root of the
+    /// dataset, with one row per row group of each data file, so engines can prune
+    /// row groups without reading the footers of all files.
+    ///
+    /// Its columns are `path` (relative to the dataset root), `row_group` (the index
+    /// of the row group in its file), `rows`, and `<column>.min` and `<column>.max`
+    /// for each of the given top-level non-nested `columns`, which are null if the row
+    /// group has no statistics for the column.
+    pub fn close_with_zonemap(self, columns: &[String]) -> Result<Vec<ParquetMetaData>> {
+        let schema = self.schema.0.clone();
+        let dataset_path = self.path.clone();
+        for column in columns {
+            let (_, field) = schema
+                .column_with_name(column)
+                .with_context(|| format!("Unknown top-level column {}", column))?;
+            ensure!(
+                !field.data_type().is_nested(),
+                "Cannot build zone map of nested column {}",
+                column
+            );
+        }
+
+        let (close_results, paths, footers) = self.close_and_read_footers()?;
+        write_zonemap(
+            &dataset_path.join("_zonemap.parquet"),
+            &dataset_path,
+            &schema,
+            columns,
+            &paths,
+            &footers,
+        )?;
+
+        Ok(close_results)
+    }
+
+    /// Closes all underlying writers, and returns the results of closing them, along
+    /// with the sorted paths and footers of all the files they wrote.
+    fn close_and_read_footers(
+        mut self,
+    ) -> Result<(Vec<ParquetMetaData>, Vec<PathBuf>, Vec<ParquetMetaData>)> {
+        // Files of writers which could not be closed (with best_effort) are skipped
+        let (paths, close_results): (Vec<Vec<PathBuf>>, Vec<_>) = self
+            .close_with(|writer| writer.created_files().to_vec())?
+            .into_iter()
+            .unzip();
+        let paths: Vec<PathBuf> = paths.into_iter().flatten().collect();
+        // The last file of each writer may have been renamed when closing it
+        let mut paths = paths
+            .iter()
+            .map(|path| closed_file_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        paths.sort();
+
+        // Read footers back, as close() only returns those of the last file of each
+        // writer.
+        let footers = paths
+            .iter()
+            .map(|path| {
+                let file = File::open(path)
+                    .with_context(|| format!("Could not open {}", path.display()))?;
+                ParquetMetaDataReader::new()
+                    .parse_and_finish(&file)
+                    .with_context(|| format!("Could not read footer of {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((close_results, paths, footers))
+    }
+}
+
+fn write_stats_table(
+    stats_path: &Path,
+    dataset_path: &Path,
+    schema: &Schema,
+    paths: &[PathBuf],
+    footers: &[ParquetMetaData],
+) -> Result<()> {
+    let parquet_schema = ArrowSchemaConverter::new()
+        .convert(schema)
+        .context("Could not convert schema to Parquet")?;
+
+    let mut fields = vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("rows", DataType::UInt64, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(paths.iter().map(|path| {
+            path.strip_prefix(dataset_path)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        }))),
+        Arc::new(UInt64Array::from_iter_values(footers.iter().map(
+            |footer| u64::try_from(footer.file_metadata().num_rows()).unwrap_or(0),
+        ))),
+    ];
+
+    for field in schema.fields() {
+        if field.data_type().is_nested() {
+            continue;
+        }
+        let converter = StatisticsConverter::try_new(field.name(), schema, &parquet_schema)
+            .with_context(|| format!("Could not read statistics of {}", field.name()))?;
+        let mut mins = Vec::new();
+        let mut maxes = Vec::new();
+        let mut null_counts = Vec::new();
+        for footer in footers {
+            let row_group_mins = converter
+                .row_group_mins(footer.row_groups())
+                .with_context(|| format!("Could not read statistics of {}", field.name()))?;
+            let row_group_maxes = converter
+                .row_group_maxes(footer.row_groups())
+                .with_context(|| format!("Could not read statistics of {}", field.name()))?;
+            let row_group_null_counts = converter
+                .row_group_null_counts(footer.row_groups())
+                .with_context(|| format!("Could not read statistics of {}", field.name()))?;
+            mins.push(first_sorted(&row_group_mins, false)?);
+            maxes.push(first_sorted(&row_group_maxes, true)?);
+            null_counts.push(sum(&row_group_null_counts));
+        }
+
+        fields.push(Field::new(
+            format!("{}.min", field.name()),
+            field.data_type().clone(),
+            true,
+        ));
+        columns.push(concat_or_empty(&mins, field.data_type())?);
+        fields.push(Field::new(
+            format!("{}.max", field.name()),
+            field.data_type().clone(),
+            true,
+        ));
+        columns.push(concat_or_empty(&maxes, field.data_type())?);
+        fields.push(Field::new(
+            format!("{}.null_count", field.name()),
+            DataType::UInt64,
+            true,
+        ));
+        columns.push(Arc::new(UInt64Array::from(null_counts)));
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .context("Could not build statistics table")?;
+    write_batch(stats_path, &batch)
+}
+
+fn write_zonemap(
+    zonemap_path: &Path,
+    dataset_path: &Path,
+    schema: &Schema,
+    column_names: &[String],
+    paths: &[PathBuf],
+    footers: &[ParquetMetaData],
+) -> Result<()> {
+    let parquet_schema = ArrowSchemaConverter::new()
+        .convert(schema)
+        .context("Could not convert schema to Parquet")?;
+
+    let mut fields = vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("row_group", DataType::UInt64, false),
+        Field::new("rows", DataType::UInt64, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(
+            paths
+                .iter()
+                .zip(footers)
+                .flat_map(|(path, footer)| {
+                    let path = path
+                        .strip_prefix(dataset_path)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string();
+                    std::iter::repeat_n(path, footer.num_row_groups())
+                })
+                .collect::<Vec<_>>(),
+        )),
+        Arc::new(UInt64Array::from_iter_values(footers.iter().flat_map(
+            |footer| (0..footer.num_row_groups()).map(|row_group| row_group as u64),
+        ))),
+        Arc::new(UInt64Array::from_iter_values(footers.iter().flat_map(
+            |footer| {
+                footer
+                    .row_groups()
+                    .iter()
+                    .map(|row_group| u64::try_from(row_group.num_rows()).unwrap_or(0))
+            },
+        ))),
+    ];
+
+    for name in column_names {
+        let (_, field) = schema
+            .column_with_name(name)
+            .with_context(|| format!("Unknown top-level column {}", name))?;
+        let converter = StatisticsConverter::try_new(name, schema, &parquet_schema)
+            .with_context(|| format!("Could not read statistics of {}", name))?;
+        let mut mins = Vec::new();
+        let mut maxes = Vec::new();
+        for footer in footers {
+            mins.push(
+                converter
+                    .row_group_mins(footer.row_groups())
+                    .with_context(|| format!("Could not read statistics of {}", name))?,
+            );
+            maxes.push(
+                converter
+                    .row_group_maxes(footer.row_groups())
+                    .with_context(|| format!("Could not read statistics of {}", name))?,
+            );
+        }
+
+        fields.push(Field::new(
+            format!("{}.min", name),
+            field.data_type().clone(),
+            true,
+        ));
+        columns.push(concat_or_empty(&mins, field.data_type())?);
+        fields.push(Field::new(
+            format!("{}.max", name),
+            field.data_type().clone(),
+            true,
+        ));
+        columns.push(concat_or_empty(&maxes, field.data_type())?);
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .context("Could not build zone map")?;
+    write_batch(zonemap_path, &batch)
+}
+
+/// Writes `batch` as a whole Parquet file
+fn write_batch(path: &Path, batch: &RecordBatch) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Could not create {}", path.display()))?;
+    let mut file_writer = ParquetWriter::try_new(file, batch.schema(), None)
+        .with_context(|| format!("Could not create writer for {}", path.display()))?;
+    file_writer
+        .write(batch)
+        .with_context(|| format!("Could not write to {}", path.display()))?;
+    file_writer
+        .close()
+        .with_context(|| format!("Could not close {}", path.display()))?;
+    Ok(())
+}
+
+/// Returns a single-element array with the smallest (or largest if `descending`)
+/// non-null value of the array, or null if there is none.
+fn first_sorted(array: &ArrayRef, descending: bool) -> Result<ArrayRef> {
+    let options = SortOptions {
+        descending,
+        nulls_first: false,
+    };
+    let indices =
+        sort_to_indices(array, Some(options), Some(1)).context("Could not sort statistics")?;
+    if indices.is_empty() {
+        Ok(new_null_array(array.data_type(), 1))
+    } else {
+        take(array, &indices, None).context("Could not select statistics")
+    }
+}
+
+fn concat_or_empty(arrays: &[ArrayRef], data_type: &DataType) -> Result<ArrayRef> {
+    if arrays.is_empty() {
+        Ok(new_empty_array(data_type))
+    } else {
+        let arrays: Vec<&dyn Array> = arrays.iter().map(|array| array.as_ref()).collect();
+        concat(&arrays).context("Could not concatenate statistics")
+    }
+}
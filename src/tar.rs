@@ -0,0 +1,315 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+
+use crate::{ParallelDatasetWriter, PartitionLayout, TableWriter};
+
+/// File written by [`TarDatasetWriter`]
+enum OutputFile {
+    Plain(File),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+impl OutputFile {
+    /// Ends the zstd stream, if any
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Zstd(encoder) => encoder.finish().map(|_file| ()),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Archive written by a [`TarDatasetWriter`], shared by all its [`TarTableWriter`]s
+#[derive(Clone)]
+pub struct TarArchive {
+    path: PathBuf,
+    /// Directory table writers write their files to, before they are added to the
+    /// archive
+    spool_path: PathBuf,
+    builder: Arc<Mutex<Option<::tar::Builder<OutputFile>>>>, // None only after .finish()
+}
+
+impl std::fmt::Debug for TarArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TarArchive")
+            .field("path", &self.path)
+            .field("spool_path", &self.spool_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TarArchive {
+    /// Adds all files in `dir` and its subdirectories to the archive, named after their
+    /// path relative to `dir`
+    fn append_dir(&self, dir: &Path) -> Result<()> {
+        let paths = list_files(dir)?;
+        let mut builder = self.builder.lock().unwrap();
+        let builder = builder
+            .as_mut()
+            .with_context(|| format!("{} is already closed", self.path.display()))?;
+        for path in paths {
+            let name = path
+                .strip_prefix(dir)
+                .expect("file is not in listed directory");
+            builder
+                .append_path_with_name(&path, name)
+                .with_context(|| {
+                    format!(
+                        "Could not add {} to {}",
+                        path.display(),
+                        self.path.display()
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Writes the end of the archive
+    fn finish(&self) -> Result<()> {
+        self.builder
+            .lock()
+            .unwrap()
+            .take()
+            .with_context(|| format!("{} is already closed", self.path.display()))?
+            .into_inner()
+            .and_then(OutputFile::finish)
+            .with_context(|| format!("Could not close {}", self.path.display()))
+    }
+}
+
+/// Returns all files in `dir` and its subdirectories, sorted
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Could not list {}", dir.display()))?
+    {
+        let entry = entry.with_context(|| format!("Could not list {}", dir.display()))?;
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("Could not get type of {}", entry.path().display()))?;
+        if file_type.is_dir() {
+            paths.extend(list_files(&entry.path())?);
+        } else {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TarTableWriterConfig<InnerConfig> {
+    /// Configuration passed to the wrapped writer
+    pub inner: InnerConfig,
+    /// Archive to add files to. Set by [`TarDatasetWriter`].
+    pub archive: Option<TarArchive>,
+}
+
+/// Wraps a [`TableWriter`] so the files it writes are added to the archive of a
+/// [`TarDatasetWriter`] when it is closed.
+///
+/// The wrapped writer writes its files to a private directory, as tar entries cannot
+/// be written before their size is known. When closing, these files are added to the
+/// archive (named after the path they would have in a dataset written by
+/// [`ParallelDatasetWriter`]), then deleted.
+///
+/// The archive is shared by all table writers, and a tar archive can only be written
+/// sequentially, so table writers add their files one writer at a time. Files of a
+/// writer are contiguous in the archive.
+pub struct TarTableWriter<W: TableWriter> {
+    inner: W,
+    /// Directory `inner` writes to
+    spool_path: PathBuf,
+    archive: TarArchive,
+}
+
+impl<W: TableWriter> TableWriter for TarTableWriter<W> {
+    type Schema = W::Schema;
+    type CloseResult = W::CloseResult;
+    type Config = TarTableWriterConfig<W::Config>;
+
+    fn new(path: PathBuf, schema: Self::Schema, config: Self::Config) -> Result<Self> {
+        let archive = config
+            .archive
+            .context("TarTableWriter can only be created by a TarDatasetWriter")?;
+        let relative_path = path
+            .strip_prefix(&archive.spool_path)
+            .with_context(|| {
+                format!(
+                    "{} is not in {}",
+                    path.display(),
+                    archive.spool_path.display()
+                )
+            })?
+            .to_owned();
+        let mut spool_path = path.into_os_string();
+        spool_path.push(".spool");
+        let spool_path = PathBuf::from(spool_path);
+        let inner_path = spool_path.join(relative_path);
+        let inner_dir = inner_path.parent().expect("spool path has no parent");
+        std::fs::create_dir_all(inner_dir)
+            .with_context(|| format!("Could not create {}", inner_dir.display()))?;
+        Ok(TarTableWriter {
+            inner: W::new(inner_path, schema, config.inner)?,
+            spool_path,
+            archive,
+        })
+    }
+
+    fn schema_with_metadata(schema: Self::Schema, key: &str, value: &str) -> Self::Schema {
+        W::schema_with_metadata(schema, key, value)
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        schema: &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        W::write_common_metadata(path, schema, &config.inner)
+    }
+
+    fn partitioning(schema: &Self::Schema) -> Vec<PartitionLayout> {
+        W::partitioning(schema)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(self) -> Result<Self::CloseResult> {
+        let result = self.inner.close()?;
+        self.archive.append_dir(&self.spool_path)?;
+        std::fs::remove_dir_all(&self.spool_path)
+            .with_context(|| format!("Could not remove {}", self.spool_path.display()))?;
+        Ok(result)
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.inner.written_rows()
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.inner.written_bytes()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.inner.buffer_size()
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        self.inner.set_file_metadata(key, value)
+    }
+}
+
+impl<W: TableWriter> TarTableWriter<W> {
+    /// Returns the wrapped writer
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+/// Writes a dataset to a single tar archive (optionally zstd-compressed), instead of
+/// a directory.
+///
+/// This dereferences to a [`ParallelDatasetWriter`], which writes files to
+/// `<path>.spool` until the table writers are closed, see [`TarTableWriter`]. The
+/// archive is only complete once [`close`](Self::close) returns.
+pub struct TarDatasetWriter<W: TableWriter + Send> {
+    dataset_writer: ParallelDatasetWriter<TarTableWriter<W>>,
+    archive: TarArchive,
+}
+
+impl<W: TableWriter + Send> TarDatasetWriter<W>
+where
+    W::Config: Default,
+{
+    /// Creates the archive at `path`, compressed with zstd at `compression_level` if
+    /// set, and an empty `<path>.spool` directory.
+    pub fn new(
+        path: PathBuf,
+        schema: W::Schema,
+        config: W::Config,
+        compression_level: Option<i32>,
+    ) -> Result<Self> {
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let file = match compression_level {
+            Some(compression_level) => OutputFile::Zstd(
+                zstd::stream::write::Encoder::new(file, compression_level).with_context(|| {
+                    format!("Could not create ZSTD encoder for {}", path.display())
+                })?,
+            ),
+            None => OutputFile::Plain(file),
+        };
+        let mut spool_path = path.clone().into_os_string();
+        spool_path.push(".spool");
+        let spool_path = PathBuf::from(spool_path);
+        std::fs::create_dir(&spool_path)
+            .with_context(|| format!("Could not create {}", spool_path.display()))?;
+        let archive = TarArchive {
+            path,
+            spool_path: spool_path.clone(),
+            builder: Arc::new(Mutex::new(Some(::tar::Builder::new(file)))),
+        };
+
+        let mut dataset_writer = ParallelDatasetWriter::with_schema(spool_path, schema)?;
+        dataset_writer.config = TarTableWriterConfig {
+            inner: config,
+            archive: Some(archive.clone()),
+        };
+        Ok(TarDatasetWriter {
+            dataset_writer,
+            archive,
+        })
+    }
+
+    /// Closes all table writers, adds the remaining files (such as `_common_metadata`)
+    /// to the archive, then finishes it and removes `<path>.spool`.
+    pub fn close(self) -> Result<Vec<W::CloseResult>> {
+        let results = self.dataset_writer.close()?;
+        self.archive.append_dir(&self.archive.spool_path)?;
+        self.archive.finish()?;
+        std::fs::remove_dir_all(&self.archive.spool_path)
+            .with_context(|| format!("Could not remove {}", self.archive.spool_path.display()))?;
+        Ok(results)
+    }
+}
+
+impl<W: TableWriter + Send> std::ops::Deref for TarDatasetWriter<W> {
+    type Target = ParallelDatasetWriter<TarTableWriter<W>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.dataset_writer
+    }
+}
+
+impl<W: TableWriter + Send> std::ops::DerefMut for TarDatasetWriter<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.dataset_writer
+    }
+}
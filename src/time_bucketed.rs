@@ -0,0 +1,233 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use arrow::array::{Array, AsArray, Int32Array};
+use arrow::compute::{date_part, DatePart};
+use arrow::datatypes::Int32Type;
+
+use crate::derived_partition::write_rows_by_key;
+use crate::{
+    PartitionLayout, PartitionedTableWriterConfig, StructArrayBuilder, StructArrayTableWriter,
+    TableWriter, Utf8PartitionedTableWriter, HIVE_DEFAULT_PARTITION,
+};
+
+/// Name of the partition column of [`TimeBucketedTableWriter`]
+pub const TIME_BUCKET_PARTITION_COLUMN: &str = "date";
+
+/// Size of the time buckets of a [`TimeBucketedTableWriter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBucket {
+    /// Rows are written to `date=YYYY-MM-DDTHH` partitions
+    Hour,
+    /// Rows are written to `date=YYYY-MM-DD` partitions
+    Day,
+    /// Rows are written to `date=YYYY-MM` partitions
+    Month,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TimeBucketedTableWriterConfig<PartitionConfig> {
+    /// Automatically flushes the builder to disk when its length (in number of rows)
+    /// reaches the value.
+    ///
+    /// Defaults to 1048576 if `None`.
+    pub flush_threshold: Option<usize>,
+    pub partitioned: PartitionedTableWriterConfig<PartitionConfig>,
+}
+
+/// Writer which partitions rows by the time bucket of a timestamp (or date) column,
+/// to `base/date=<bucket>/x.parquet`.
+///
+/// Like [`DerivedPartitionWriter`](crate::DerivedPartitionWriter), callers write to a
+/// single builder, and each flush splits the built array by bucket and writes every
+/// slice to the writer of its partition. Buckets are computed in the timezone of the
+/// column, or UTC if it has none. Rows with a null timestamp are written to the
+/// [`HIVE_DEFAULT_PARTITION`].
+pub struct TimeBucketedTableWriter<
+    Builder: Default + StructArrayBuilder,
+    PartitionWriter: StructArrayTableWriter + Send,
+> {
+    builder: Builder,
+    timestamp_column: String,
+    bucket: TimeBucket,
+    partitions: Option<Utf8PartitionedTableWriter<PartitionWriter>>, // None only between .close() call and Drop
+    pub flush_threshold: usize,
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send>
+    TableWriter for TimeBucketedTableWriter<Builder, PartitionWriter>
+{
+    /// `(timestamp_column, bucket, underlying_schema)`
+    type Schema = (String, TimeBucket, PartitionWriter::Schema);
+    type CloseResult = Vec<PartitionWriter::CloseResult>;
+    type Config = TimeBucketedTableWriterConfig<PartitionWriter::Config>;
+
+    fn new(
+        path: PathBuf,
+        (timestamp_column, bucket, schema): Self::Schema,
+        config: Self::Config,
+    ) -> Result<Self> {
+        Ok(TimeBucketedTableWriter {
+            builder: Builder::default(),
+            timestamp_column,
+            bucket,
+            partitions: Some(Utf8PartitionedTableWriter::new(
+                path,
+                (TIME_BUCKET_PARTITION_COLUMN.to_owned(), schema),
+                config.partitioned,
+            )?),
+            flush_threshold: config.flush_threshold.unwrap_or(1024 * 1024), // Arbitrary
+        })
+    }
+
+    fn schema_with_metadata(
+        (timestamp_column, bucket, schema): Self::Schema,
+        key: &str,
+        value: &str,
+    ) -> Self::Schema {
+        (
+            timestamp_column,
+            bucket,
+            PartitionWriter::schema_with_metadata(schema, key, value),
+        )
+    }
+
+    fn write_common_metadata(
+        path: &Path,
+        (_timestamp_column, _bucket, schema): &Self::Schema,
+        config: &Self::Config,
+    ) -> Result<()> {
+        PartitionWriter::write_common_metadata(path, schema, &config.partitioned.partition_config)
+    }
+
+    fn partitioning((_timestamp_column, _bucket, schema): &Self::Schema) -> Vec<PartitionLayout> {
+        let mut partitioning = vec![PartitionLayout {
+            column: TIME_BUCKET_PARTITION_COLUMN.to_owned(),
+            key_type: "utf8",
+        }];
+        partitioning.extend(PartitionWriter::partitioning(schema));
+        partitioning
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let num_bytes = self.builder.buffer_size();
+        let struct_array = self.builder.finish()?;
+        self.builder.reserve(struct_array.len(), num_bytes);
+
+        let timestamps = struct_array
+            .column_by_name(&self.timestamp_column)
+            .with_context(|| format!("Unknown timestamp column {}", self.timestamp_column))?;
+        let keys = time_bucket_keys(timestamps, self.bucket).with_context(|| {
+            format!(
+                "Could not compute time buckets of {}",
+                self.timestamp_column
+            )
+        })?;
+        write_rows_by_key(
+            self.partitions
+                .as_mut()
+                .expect("Partitions are unexpectedly None"),
+            &struct_array,
+            keys,
+        )
+    }
+
+    fn close(mut self) -> Result<Self::CloseResult> {
+        self.flush()?;
+        self.partitions
+            .take()
+            .expect("Partitions are unexpectedly None")
+            .close()
+    }
+
+    fn written_rows(&self) -> u64 {
+        self.partitions
+            .as_ref()
+            .map(|partitions| partitions.written_rows())
+            .unwrap_or(0)
+    }
+
+    fn written_bytes(&self) -> u64 {
+        self.partitions
+            .as_ref()
+            .map(|partitions| partitions.written_bytes())
+            .unwrap_or(0)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.builder.buffer_size()
+            + self
+                .partitions
+                .as_ref()
+                .map(|partitions| partitions.buffer_size())
+                .unwrap_or(0)
+    }
+
+    fn set_file_metadata(&mut self, key: &str, value: &str) {
+        if let Some(partitions) = &mut self.partitions {
+            partitions.set_file_metadata(key, value);
+        }
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send>
+    TimeBucketedTableWriter<Builder, PartitionWriter>
+{
+    /// Flushes the internal buffer is too large, then returns the array builder.
+    pub fn builder(&mut self) -> Result<&mut Builder> {
+        if self.builder.len() >= self.flush_threshold {
+            self.flush()?;
+        }
+
+        Ok(&mut self.builder)
+    }
+}
+
+impl<Builder: Default + StructArrayBuilder, PartitionWriter: StructArrayTableWriter + Send> Drop
+    for TimeBucketedTableWriter<Builder, PartitionWriter>
+{
+    fn drop(&mut self) {
+        if self.partitions.is_some() {
+            self.flush().unwrap();
+        }
+    }
+}
+
+/// Returns the partition key of each row, given its timestamp
+fn time_bucket_keys(timestamps: &dyn Array, bucket: TimeBucket) -> Result<Vec<String>> {
+    let part = |part| -> Result<Int32Array> {
+        Ok(date_part(timestamps, part)?
+            .as_primitive::<Int32Type>()
+            .clone())
+    };
+    let years = part(DatePart::Year)?;
+    let months = part(DatePart::Month)?;
+    let days = match bucket {
+        TimeBucket::Hour | TimeBucket::Day => Some(part(DatePart::Day)?),
+        TimeBucket::Month => None,
+    };
+    let hours = match bucket {
+        TimeBucket::Hour => Some(part(DatePart::Hour)?),
+        TimeBucket::Day | TimeBucket::Month => None,
+    };
+    Ok((0..timestamps.len())
+        .map(|row| {
+            if timestamps.is_null(row) {
+                return HIVE_DEFAULT_PARTITION.to_owned();
+            }
+            let mut key = format!("{:04}-{:02}", years.value(row), months.value(row));
+            if let Some(days) = &days {
+                key.push_str(&format!("-{:02}", days.value(row)));
+            }
+            if let Some(hours) = &hours {
+                key.push_str(&format!("T{:02}", hours.value(row)));
+            }
+            key
+        })
+        .collect())
+}
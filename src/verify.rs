@@ -0,0 +1,65 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use parquet::file::metadata::ParquetMetaDataReader;
+
+/// Status of a Parquet file, as returned by [`verify_dataset`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The file has a valid footer
+    Valid { rows: u64 },
+    /// The footer could not be read, eg. because the file was truncated by a crash
+    Corrupt { error: String },
+}
+
+/// Returns the status of every `.parquet` file in `path` and its subdirectories,
+/// sorted by path.
+///
+/// This only reads footers, so it detects files which were not closed, but not
+/// corrupt pages.
+pub fn verify_dataset(path: &Path) -> Result<Vec<(PathBuf, FileStatus)>> {
+    let mut statuses = Vec::new();
+    verify_dir(path, &mut statuses)?;
+    statuses.sort_by(|(path1, _), (path2, _)| path1.cmp(path2));
+    Ok(statuses)
+}
+
+fn verify_dir(dir: &Path, statuses: &mut Vec<(PathBuf, FileStatus)>) -> Result<()> {
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Could not list {}", dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("Could not list {}", dir.display()))?
+            .path();
+        if path.is_dir() {
+            verify_dir(&path, statuses)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "parquet")
+        {
+            let status = match read_num_rows(&path) {
+                Ok(rows) => FileStatus::Valid { rows },
+                Err(e) => FileStatus::Corrupt {
+                    error: format!("{:#}", e),
+                },
+            };
+            statuses.push((path, status));
+        }
+    }
+    Ok(())
+}
+
+fn read_num_rows(path: &Path) -> Result<u64> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let metadata = ParquetMetaDataReader::new()
+        .parse_and_finish(&file)
+        .with_context(|| format!("Could not read footer of {}", path.display()))?;
+    u64::try_from(metadata.file_metadata().num_rows())
+        .with_context(|| format!("{} has a negative number of rows", path.display()))
+}
@@ -5,9 +5,9 @@
 
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 
 use crate::TableWriter;
 
@@ -15,6 +15,18 @@ use crate::TableWriter;
 pub struct PlainZstTableWriterConfig {
     pub extension: String,
     pub compression_level: i32,
+    /// If `true`, the number of uncompressed bytes written to each file is written
+    /// to a `<file>.size` sidecar file when closing it.
+    pub write_size_sidecar: bool,
+    /// If set, a new zstd frame is started whenever the current one contains this
+    /// many bytes (before compression), which bounds the memory needed by streaming
+    /// decoders that work a frame at a time.
+    ///
+    /// Must be positive.
+    pub max_frame_content_size: Option<u64>,
+    /// If `true`, each zstd frame ends with a checksum of its content, which decoders
+    /// verify when decompressing. Defaults to `false`.
+    pub include_checksum: bool,
 }
 
 impl Default for PlainZstTableWriterConfig {
@@ -22,32 +34,195 @@ impl Default for PlainZstTableWriterConfig {
         PlainZstTableWriterConfig {
             extension: "zst".to_owned(),
             compression_level: 3,
+            write_size_sidecar: false,
+            max_frame_content_size: None,
+            include_checksum: false,
         }
     }
 }
 
-pub type PlainZstTableWriter<'a> = zstd::stream::AutoFinishEncoder<'a, File>;
+/// Returned by [`PlainZstTableWriter`] when closing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZstCloseResult {
+    /// Size of the file
+    pub compressed_bytes: u64,
+    /// Number of bytes written to the file, before compression
+    pub uncompressed_bytes: u64,
+}
+
+/// Zstd-compressed file, which keeps track of the number of (uncompressed) bytes
+/// written to it
+///
+/// The zstd stream is finished when closing it or when it is dropped.
+pub struct ZstFile<'a> {
+    path: PathBuf,
+    encoder: Option<zstd::stream::write::Encoder<'a, File>>, // None only after .finalize()
+    compression_level: i32,
+    uncompressed_bytes: u64,
+    write_size_sidecar: bool,
+    max_frame_content_size: Option<u64>,
+    /// Number of bytes written to the current frame, before compression
+    frame_uncompressed_bytes: u64,
+    include_checksum: bool,
+}
+
+impl<'a> ZstFile<'a> {
+    /// Creates a file at the given path, with `compression_level` from 1 to 22.
+    ///
+    /// If `write_size_sidecar` is `true`, closing the file writes the number of
+    /// uncompressed bytes to `<path>.size`.
+    pub fn create(path: PathBuf, compression_level: i32, write_size_sidecar: bool) -> Result<Self> {
+        let file =
+            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
+        let encoder = zstd::stream::write::Encoder::new(file, compression_level)
+            .with_context(|| format!("Could not create ZSTD encoder for {}", path.display()))?;
+        Ok(ZstFile {
+            path,
+            encoder: Some(encoder),
+            compression_level,
+            uncompressed_bytes: 0,
+            write_size_sidecar,
+            max_frame_content_size: None,
+            frame_uncompressed_bytes: 0,
+            include_checksum: false,
+        })
+    }
+
+    /// Starts a new zstd frame whenever the current one contains
+    /// `max_frame_content_size` bytes (before compression).
+    pub fn with_max_frame_content_size(
+        mut self,
+        max_frame_content_size: Option<u64>,
+    ) -> Result<Self> {
+        ensure!(
+            max_frame_content_size != Some(0),
+            "max_frame_content_size must be positive"
+        );
+        self.max_frame_content_size = max_frame_content_size;
+        Ok(self)
+    }
+
+    /// Sets whether each zstd frame ends with a checksum of its content.
+    pub fn with_checksum(mut self, include_checksum: bool) -> Result<Self> {
+        self.encoder()
+            .include_checksum(include_checksum)
+            .with_context(|| {
+                format!(
+                    "Could not configure ZSTD checksum for {}",
+                    self.path.display()
+                )
+            })?;
+        self.include_checksum = include_checksum;
+        Ok(self)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of bytes written so far, before compression
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.uncompressed_bytes
+    }
+
+    /// Finishes the compressed stream, and writes the size sidecar if enabled.
+    pub(crate) fn finalize(&mut self) -> Result<ZstCloseResult> {
+        let file = self
+            .encoder
+            .take()
+            .expect("Encoder is unexpectedly None")
+            .finish()
+            .with_context(|| format!("Could not close {}", self.path.display()))?;
+        let compressed_bytes = file
+            .metadata()
+            .with_context(|| format!("Could not get size of {}", self.path.display()))?
+            .len();
+        if self.write_size_sidecar {
+            let mut sidecar_path = self.path.clone().into_os_string();
+            sidecar_path.push(".size");
+            let sidecar_path = PathBuf::from(sidecar_path);
+            std::fs::write(&sidecar_path, format!("{}\n", self.uncompressed_bytes))
+                .with_context(|| format!("Could not write {}", sidecar_path.display()))?;
+        }
+        Ok(ZstCloseResult {
+            compressed_bytes,
+            uncompressed_bytes: self.uncompressed_bytes,
+        })
+    }
+
+    fn encoder(&mut self) -> &mut zstd::stream::write::Encoder<'a, File> {
+        self.encoder.as_mut().expect("Encoder is unexpectedly None")
+    }
+
+    /// Ends the current zstd frame and starts a new one
+    fn start_new_frame(&mut self) -> std::io::Result<()> {
+        let file = self
+            .encoder
+            .take()
+            .expect("Encoder is unexpectedly None")
+            .finish()?;
+        let mut encoder = zstd::stream::write::Encoder::new(file, self.compression_level)?;
+        encoder.include_checksum(self.include_checksum)?;
+        self.encoder = Some(encoder);
+        self.frame_uncompressed_bytes = 0;
+        Ok(())
+    }
+}
+
+impl Drop for ZstFile<'_> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            // Errors are ignored, like zstd's AutoFinishEncoder does
+            let _ = encoder.finish();
+        }
+    }
+}
+
+impl Write for ZstFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let buf = match self.max_frame_content_size {
+            None => buf,
+            Some(max_frame_content_size) => {
+                if self.frame_uncompressed_bytes >= max_frame_content_size {
+                    self.start_new_frame()?;
+                }
+                let remaining = max_frame_content_size - self.frame_uncompressed_bytes;
+                &buf[..buf
+                    .len()
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX))]
+            }
+        };
+        let written = self.encoder().write(buf)?;
+        let written_u64 = u64::try_from(written).expect("usize overflowed u64");
+        self.uncompressed_bytes += written_u64;
+        self.frame_uncompressed_bytes += written_u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder().flush()
+    }
+}
+
+pub type PlainZstTableWriter<'a> = ZstFile<'a>;
 
 impl TableWriter for PlainZstTableWriter<'_> {
     type Schema = ();
-    type CloseResult = ();
+    type CloseResult = ZstCloseResult;
     type Config = PlainZstTableWriterConfig;
 
     fn new(mut path: PathBuf, _schema: Self::Schema, config: Self::Config) -> Result<Self> {
         path.set_extension(&config.extension);
-        let file =
-            File::create(&path).with_context(|| format!("Could not create {}", path.display()))?;
-        let encoder = zstd::stream::write::Encoder::new(file, config.compression_level)
-            .with_context(|| format!("Could not create ZSTD encoder for {}", path.display()))?
-            .auto_finish();
-        Ok(encoder)
+        ZstFile::create(path, config.compression_level, config.write_size_sidecar)?
+            .with_max_frame_content_size(config.max_frame_content_size)?
+            .with_checksum(config.include_checksum)
     }
 
     fn flush(&mut self) -> Result<()> {
         Write::flush(self).context("Could not flush Zst writer")
     }
 
-    fn close(mut self) -> Result<()> {
-        Write::flush(&mut self).context("Could not close Zst writer")
+    fn close(mut self) -> Result<ZstCloseResult> {
+        self.finalize()
     }
 }
@@ -0,0 +1,114 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::sync::Arc;
+
+use arrow::array::{Array, AsArray, BooleanArray};
+use arrow::datatypes::UInt64Type;
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+/// Writes `ids[i]` from the `i`-th of `ids.len()` threads, keeping threads alive until
+/// all wrote so none reuses the writer of another
+fn write_from_threads(
+    dataset_writer: &ParallelDatasetWriter<ParquetTableWriter<IdBuilder>>,
+    ids: &[&[u64]],
+) {
+    let barrier = std::sync::Barrier::new(ids.len());
+    std::thread::scope(|s| {
+        for thread_ids in ids {
+            let barrier = &barrier;
+            s.spawn(move || {
+                dataset_writer
+                    .get_thread_writer()
+                    .unwrap()
+                    .builder()
+                    .unwrap()
+                    .0
+                    .append_slice(thread_ids);
+                barrier.wait();
+            });
+        }
+    });
+}
+
+#[test]
+fn barrier_rotates_all_writers() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.parallel_chunk_size = Some(2);
+    // The last thread writes no row, so its writer has nothing to rotate
+    write_from_threads(&dataset_writer, &[&[1, 2], &[3], &[4, 5, 6], &[]]);
+
+    let closed_files = dataset_writer.barrier().unwrap();
+    let mut num_rows: Vec<_> = closed_files
+        .iter()
+        .map(|metadata| metadata.file_metadata().num_rows())
+        .collect();
+    num_rows.sort();
+    assert_eq!(num_rows, [1, 2, 3]);
+
+    write_from_threads(&dataset_writer, &[&[7], &[8], &[9], &[10]]);
+    dataset_writer.close().unwrap();
+
+    // Files closed by the barrier have exactly the rows written before it
+    // (threads of the second round may get new writers, so this checks all files)
+    let mut files_before_barrier = Vec::new();
+    let mut ids_after_barrier = Vec::new();
+    for file_name in list_tree(tmp_dir.path()) {
+        let Some(writer_id) = file_name.strip_suffix(".parquet") else {
+            continue;
+        };
+        if writer_id.ends_with("_1") {
+            continue;
+        }
+        let first_file = tmp_dir.path().join(&file_name);
+        let second_file = tmp_dir.path().join(format!("{}_1.parquet", writer_id));
+        if second_file.exists() {
+            files_before_barrier.push(read_parquet_ids(&first_file));
+            ids_after_barrier.extend(read_parquet_ids(&second_file));
+        } else {
+            // Writers without rows kept their file open
+            ids_after_barrier.extend(read_parquet_ids(&first_file));
+        }
+    }
+    files_before_barrier.sort();
+    assert_eq!(files_before_barrier, [vec![1, 2], vec![3], vec![4, 5, 6]]);
+    ids_after_barrier.sort();
+    assert_eq!(ids_after_barrier, [7, 8, 9, 10]);
+}
+
+#[test]
+fn barrier_panic_names_the_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.config.row_filter = Some(Arc::new(|array| {
+        let ids = array.column(0).as_primitive::<UInt64Type>();
+        assert!(!ids.values().contains(&666), "unexpected id");
+        Ok(BooleanArray::from(vec![true; array.len()]))
+    }));
+    write_from_threads(&dataset_writer, &[&[666]]);
+
+    let err = dataset_writer.barrier().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Table writer of {} panicked while rotating: unexpected id",
+            tmp_dir.path().join("0").display()
+        )
+    );
+}
@@ -0,0 +1,165 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+//! Helpers shared by integration tests
+
+#![allow(dead_code)]
+
+use std::path::Path;
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+#[cfg(feature = "arrow")]
+use anyhow::Result;
+#[cfg(feature = "arrow")]
+use arrow::array::{ArrayBuilder, ArrayRef, StructArray, UInt64Array, UInt64Builder};
+#[cfg(feature = "arrow")]
+use arrow::datatypes::{DataType, Field, Schema};
+
+#[cfg(feature = "arrow")]
+use dataset_writer::StructArrayBuilder;
+
+/// Schema of tables written with [`IdBuilder`]
+#[cfg(feature = "arrow")]
+pub fn id_schema() -> Schema {
+    Schema::new(vec![Field::new("id", DataType::UInt64, false)])
+}
+
+/// Builder of tables with a single non-nullable `id` column
+#[cfg(feature = "arrow")]
+#[derive(Debug, Default)]
+pub struct IdBuilder(pub UInt64Builder);
+
+#[cfg(feature = "arrow")]
+impl StructArrayBuilder for IdBuilder {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.0.len() * 8
+    }
+
+    fn finish(&mut self) -> Result<StructArray> {
+        let columns: Vec<ArrayRef> = vec![Arc::new(self.0.finish())];
+        Ok(StructArray::new(
+            id_schema().fields().clone(),
+            columns,
+            None,
+        ))
+    }
+}
+
+/// Returns an array with the given ids, for writers of tables with [`id_schema`]
+#[cfg(feature = "arrow")]
+pub fn id_array(ids: impl IntoIterator<Item = u64>) -> StructArray {
+    let columns: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from_iter_values(ids))];
+    StructArray::new(id_schema().fields().clone(), columns, None)
+}
+
+/// Row filter keeping the rows at even positions in each flush
+#[cfg(any(feature = "arrow-ipc", feature = "json", feature = "parquet"))]
+pub fn even_rows_filter() -> dataset_writer::RowFilter {
+    Arc::new(|array| {
+        Ok((0..arrow::array::Array::len(array))
+            .map(|row| Some(row % 2 == 0))
+            .collect())
+    })
+}
+
+/// Schema of [`ParquetTableWriter`](dataset_writer::ParquetTableWriter)s of tables
+/// with [`id_schema`]
+#[cfg(feature = "parquet")]
+pub fn parquet_id_schema() -> (Arc<Schema>, parquet::file::properties::WriterProperties) {
+    (Arc::new(id_schema()), Default::default())
+}
+
+/// Returns the ids in a Parquet file written with [`id_schema`], in order
+#[cfg(feature = "parquet")]
+pub fn read_parquet_ids(path: &Path) -> Vec<u64> {
+    use arrow::array::AsArray;
+    use arrow::datatypes::UInt64Type;
+
+    parquet_reader(path)
+        .build()
+        .unwrap()
+        .flat_map(|batch| {
+            batch
+                .unwrap()
+                .column_by_name("id")
+                .unwrap()
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+/// Returns a reader of the given Parquet file
+#[cfg(feature = "parquet")]
+pub fn parquet_reader(
+    path: &Path,
+) -> parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder<std::fs::File> {
+    let file = std::fs::File::open(path)
+        .unwrap_or_else(|e| panic!("Could not open {}: {}", path.display(), e));
+    parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file).unwrap()
+}
+
+/// Returns the paths of all files and directories in `path` (recursively), relative to
+/// `path`, sorted
+pub fn list_tree(path: &Path) -> Vec<String> {
+    fn walk(base: &Path, path: &Path, paths: &mut Vec<String>) {
+        for entry in std::fs::read_dir(path).unwrap() {
+            let entry_path = entry.unwrap().path();
+            paths.push(
+                entry_path
+                    .strip_prefix(base)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+            if entry_path.is_dir() {
+                walk(base, &entry_path, paths);
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    walk(path, path, &mut paths);
+    paths.sort();
+    paths
+}
+
+/// Returns the entries of a bitmap index written by
+/// [`ParquetTableWriter`](dataset_writer::ParquetTableWriter), as values (`None` for
+/// nulls) and their row positions
+#[cfg(feature = "parquet")]
+pub fn read_bitmap_index(path: &Path) -> Vec<(Option<String>, Vec<u32>)> {
+    fn read_u32(bytes: &mut &[u8]) -> u32 {
+        let (head, tail) = bytes.split_at(4);
+        *bytes = tail;
+        u32::from_le_bytes(head.try_into().unwrap())
+    }
+
+    let sidecar =
+        std::fs::read(path).unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e));
+    let mut bytes = sidecar.as_slice();
+    let mut entries = Vec::new();
+    while !bytes.is_empty() {
+        let value = match read_u32(&mut bytes) {
+            u32::MAX => None,
+            len => {
+                let (value, tail) = bytes.split_at(len as usize);
+                bytes = tail;
+                Some(String::from_utf8(value.to_vec()).unwrap())
+            }
+        };
+        let len = read_u32(&mut bytes) as usize;
+        let (bitmap, tail) = bytes.split_at(len);
+        bytes = tail;
+        let bitmap = roaring::RoaringBitmap::deserialize_from(bitmap).unwrap();
+        entries.push((value, bitmap.into_iter().collect()));
+    }
+    entries
+}
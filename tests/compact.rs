@@ -0,0 +1,163 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::bail;
+use arrow::array::{Array, AsArray, BooleanArray, StructArray};
+use arrow::datatypes::UInt64Type;
+use dataset_writer::*;
+use parquet::basic::Compression;
+
+mod common;
+use common::*;
+
+/// Writes 4 files of 10 ids each, in a single thread
+fn four_file_dataset(path: PathBuf) -> ParallelDatasetWriter<ParquetTableWriter<IdBuilder>> {
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        path,
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.config.column_compression =
+        vec![("id".to_owned(), Compression::ZSTD(Default::default()))];
+    dataset_writer.config.bitmap_index_columns = vec!["id".to_owned()];
+    {
+        let mut writer = dataset_writer.get_thread_writer().unwrap();
+        for file in 0..4 {
+            if file > 0 {
+                writer.rotate().unwrap();
+            }
+            for id in file * 10..(file + 1) * 10 {
+                writer.builder().unwrap().0.append_value(id);
+            }
+        }
+    }
+    dataset_writer
+}
+
+#[test]
+fn close_and_compact() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+
+    // Same files, without compaction, to get their sizes
+    let uncompacted_path = tmp_dir.path().join("uncompacted");
+    four_file_dataset(uncompacted_path.clone()).close().unwrap();
+    let file_size = |name| {
+        std::fs::metadata(uncompacted_path.join(name))
+            .unwrap()
+            .len()
+    };
+    let target_file_bytes = file_size("0.parquet") + file_size("0_1.parquet");
+
+    let dataset_path = tmp_dir.path().join("dataset");
+    let paths = four_file_dataset(dataset_path.clone())
+        .close_and_compact(target_file_bytes)
+        .unwrap();
+    assert_eq!(
+        paths,
+        [
+            dataset_path.join("compacted-0.parquet"),
+            dataset_path.join("compacted-1.parquet")
+        ]
+    );
+    assert_eq!(
+        list_tree(&dataset_path),
+        [
+            "compacted-0.parquet",
+            "compacted-0.parquet.id.bitmap",
+            "compacted-1.parquet",
+            "compacted-1.parquet.id.bitmap"
+        ]
+    );
+    for (i, path) in paths.iter().enumerate() {
+        let first_id = i as u64 * 20;
+        assert_eq!(
+            read_parquet_ids(path),
+            (first_id..first_id + 20).collect::<Vec<_>>()
+        );
+        let size = std::fs::metadata(path).unwrap().len();
+        assert!(
+            size < target_file_bytes,
+            "{} has {} bytes",
+            path.display(),
+            size
+        );
+
+        // Written with the dataset's config
+        let metadata = parquet_reader(path).metadata().clone();
+        for row_group in metadata.row_groups() {
+            assert_eq!(
+                row_group.column(0).compression(),
+                Compression::ZSTD(Default::default())
+            );
+        }
+        let mut bitmap_index_path = path.clone().into_os_string();
+        bitmap_index_path.push(".id.bitmap");
+        let mut expected_entries: Vec<_> = (0..20)
+            .map(|row| (Some((first_id + row).to_string()), vec![row as u32]))
+            .collect();
+        // Entries are sorted by formatted value
+        expected_entries.sort();
+        assert_eq!(
+            read_bitmap_index(&PathBuf::from(bitmap_index_path)),
+            expected_entries
+        );
+    }
+}
+
+#[test]
+fn close_and_compact_best_effort() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.best_effort = true;
+    // Makes closing the writer with id 666 fail
+    dataset_writer.config.row_filter = Some(Arc::new(|array| {
+        if read_ids(array).contains(&666) {
+            bail!("Unexpected id");
+        }
+        Ok(BooleanArray::from(vec![true; array.len()]))
+    }));
+
+    // Keeps threads alive until both got a writer, so none reuses the writer of the other
+    let barrier = std::sync::Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut writer = dataset_writer.get_thread_writer().unwrap();
+            writer.builder().unwrap().0.append_value(666);
+            drop(writer);
+            barrier.wait();
+        });
+        s.spawn(|| {
+            let mut writer = dataset_writer.get_thread_writer().unwrap();
+            writer.builder().unwrap().0.append_slice(&[1, 2]);
+            writer.rotate().unwrap();
+            writer.builder().unwrap().0.append_slice(&[3, 4]);
+            drop(writer);
+            barrier.wait();
+        });
+    });
+
+    let paths = dataset_writer.close_and_compact(u64::MAX).unwrap();
+    assert_eq!(paths, [tmp_dir.path().join("compacted-0.parquet")]);
+    assert_eq!(read_parquet_ids(&paths[0]), [1, 2, 3, 4]);
+    // The file of the writer which could not be closed is left as-is
+    assert_eq!(list_tree(tmp_dir.path()).len(), 2);
+}
+
+fn read_ids(array: &StructArray) -> Vec<u64> {
+    array
+        .column(0)
+        .as_primitive::<UInt64Type>()
+        .values()
+        .to_vec()
+}
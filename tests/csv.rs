@@ -0,0 +1,174 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "csv")]
+
+use std::path::Path;
+
+use dataset_writer::*;
+
+/// Returns the decompressed content of a file
+fn read_zst(path: &Path) -> Vec<u8> {
+    zstd::decode_all(std::fs::File::open(path).unwrap()).unwrap()
+}
+
+#[test]
+fn rfc4180_quoting() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = CsvZstTableWriterConfig {
+        quote_style: csv::QuoteStyle::Always,
+        ..Default::default()
+    };
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    writer.write_header(["quote", "comma", "newline"]).unwrap();
+    writer
+        .write_record(["a \"quoted\" word", "a,b", "line 1\nline 2"])
+        .unwrap();
+    writer.close().unwrap();
+
+    assert_eq!(
+        String::from_utf8(read_zst(&tmp_dir.path().join("0.csv.zst"))).unwrap(),
+        "\"quote\",\"comma\",\"newline\"\r\n\"a \"\"quoted\"\" word\",\"a,b\",\"line 1\nline 2\"\r\n"
+    );
+}
+
+#[test]
+fn backslash_escapes_and_lf_terminator() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = CsvZstTableWriterConfig {
+        double_quote: false,
+        terminator: csv::Terminator::Any(b'\n'),
+        ..Default::default()
+    };
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    writer.write_record(["a \"quoted\" word", "plain"]).unwrap();
+    writer.close().unwrap();
+
+    assert_eq!(
+        String::from_utf8(read_zst(&tmp_dir.path().join("0.csv.zst"))).unwrap(),
+        "\"a \\\"quoted\\\" word\",plain\n"
+    );
+}
+
+#[test]
+fn write_bom() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for write_bom in [false, true] {
+        let config = CsvZstTableWriterConfig {
+            write_bom,
+            ..Default::default()
+        };
+        let path = tmp_dir.path().join(write_bom.to_string());
+        let mut writer = CsvZstTableWriter::new(path, (), config).unwrap();
+        writer.write_header(["a", "b"]).unwrap();
+        writer.write_record(["1", "2"]).unwrap();
+        writer.close().unwrap();
+    }
+
+    assert_eq!(
+        read_zst(&tmp_dir.path().join("false.csv.zst")),
+        b"a,b\r\n1,2\r\n"
+    );
+    assert_eq!(
+        read_zst(&tmp_dir.path().join("true.csv.zst")),
+        b"\xef\xbb\xbfa,b\r\n1,2\r\n"
+    );
+}
+
+#[test]
+fn close_result() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer =
+        CsvZstTableWriter::new(tmp_dir.path().join("0"), (), Default::default()).unwrap();
+    writer.write_header(["id", "name"]).unwrap();
+    for id in 0..1000 {
+        writer
+            .write_record([id.to_string(), "foo".to_owned()])
+            .unwrap();
+    }
+    let result = writer.close().unwrap();
+
+    let path = tmp_dir.path().join("0.csv.zst");
+    assert_eq!(result.rows_written, 1000);
+    assert_eq!(
+        result.uncompressed_bytes,
+        u64::try_from(read_zst(&path).len()).unwrap()
+    );
+    assert_eq!(
+        result.compressed_bytes,
+        std::fs::metadata(&path).unwrap().len()
+    );
+    assert!(result.compressed_bytes < result.uncompressed_bytes);
+}
+
+#[test]
+fn header_sidecar() {
+    #[derive(serde::Serialize)]
+    struct Row {
+        id: u64,
+        name: &'static str,
+    }
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = CsvZstTableWriterConfig {
+        header_sidecar: true,
+        ..Default::default()
+    };
+
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("0"), (), config.clone()).unwrap();
+    writer.write_header(["id", "name"]).unwrap();
+    writer.write_record(["1", "foo"]).unwrap();
+    writer.close().unwrap();
+
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("1"), (), config).unwrap();
+    writer.serialize(Row { id: 1, name: "foo" }).unwrap();
+    writer.serialize(Row { id: 2, name: "bar" }).unwrap();
+    writer.close().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("0.csv.zst.header")).unwrap(),
+        "id,name\r\n"
+    );
+    assert_eq!(read_zst(&tmp_dir.path().join("0.csv.zst")), b"1,foo\r\n");
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("1.csv.zst.header")).unwrap(),
+        "id,name\r\n"
+    );
+    assert_eq!(
+        read_zst(&tmp_dir.path().join("1.csv.zst")),
+        b"1,foo\r\n2,bar\r\n"
+    );
+}
+
+#[test]
+fn validate_utf8() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = CsvZstTableWriterConfig {
+        validate_utf8: true,
+        ..Default::default()
+    };
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    writer.write_record(["1", "foo"]).unwrap();
+    let err = writer
+        .write_record([b"2".as_slice(), b"\xff\xfe".as_slice()])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Invalid UTF-8 in column 1 of row 1 of {}",
+            tmp_dir.path().join("0.csv.zst").display()
+        )
+    );
+    let err = writer
+        .write_byte_record(&csv::ByteRecord::from(vec![b"\xff".as_slice()]))
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("Invalid UTF-8 in column 0 of row 1"));
+    writer.close().unwrap();
+
+    // Invalid records are not written
+    assert_eq!(read_zst(&tmp_dir.path().join("0.csv.zst")), b"1,foo\r\n");
+}
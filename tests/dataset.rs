@@ -0,0 +1,746 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+//! Tests of [`ParallelDatasetWriter`]
+
+#![cfg(feature = "parquet")]
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{ensure, Result};
+use dataset_writer::*;
+use rayon::prelude::*;
+
+mod common;
+use common::*;
+
+#[cfg(unix)]
+#[test]
+fn check_free_space() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().join("dataset"),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.check_free_space(1024).unwrap();
+    let err = dataset_writer.check_free_space(u64::MAX).unwrap_err();
+    assert!(
+        err.to_string().contains("bytes available"),
+        "unexpected error: {:#}",
+        err
+    );
+}
+
+#[test]
+fn flush_stats_returns_rows_written_since_last_flush() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().join("dataset"),
+        parquet_id_schema(),
+    )
+    .unwrap();
+
+    (0..1000).into_par_iter().for_each_init(
+        || dataset_writer.get_thread_writer().unwrap(),
+        |writer, id| writer.builder().unwrap().0.append_value(id),
+    );
+    let stats = dataset_writer.flush_stats().unwrap();
+    assert_eq!(stats.rows, 1000);
+    assert!(stats.bytes > 0);
+
+    (1000..1300).into_par_iter().for_each_init(
+        || dataset_writer.get_thread_writer().unwrap(),
+        |writer, id| writer.builder().unwrap().0.append_value(id),
+    );
+    assert_eq!(dataset_writer.flush_stats().unwrap().rows, 300);
+    assert_eq!(dataset_writer.flush_stats().unwrap(), Statistics::default());
+    dataset_writer.close().unwrap();
+}
+
+/// Fills the writer of a thread, then gets a writer from another thread, and returns
+/// the number of rows the first writer wrote by then.
+fn rows_written_by_filled_writer(max_buffered_bytes: Option<usize>) -> u64 {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().join("dataset"),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.max_buffered_bytes = max_buffered_bytes;
+    let filled = std::sync::Barrier::new(2);
+    let other_thread_got_writer = std::sync::Barrier::new(2);
+
+    let written_rows = std::thread::scope(|s| {
+        let large = s.spawn(|| {
+            {
+                let mut writer = dataset_writer.get_thread_writer().unwrap();
+                for id in 0..1000 {
+                    writer.builder().unwrap().0.append_value(id);
+                }
+                assert_eq!(writer.buffer_size(), 8000);
+            }
+            filled.wait();
+            other_thread_got_writer.wait();
+            dataset_writer.get_thread_writer().unwrap().written_rows()
+        });
+        s.spawn(|| {
+            filled.wait();
+            let mut writer = dataset_writer.get_thread_writer().unwrap();
+            writer.builder().unwrap().0.append_value(1000);
+            assert_eq!(writer.written_rows(), 0);
+            drop(writer);
+            other_thread_got_writer.wait();
+        });
+        large.join().unwrap()
+    });
+    dataset_writer.close().unwrap();
+    written_rows
+}
+
+#[test]
+fn max_buffered_bytes_flushes_largest_writer() {
+    assert_eq!(rows_written_by_filled_writer(None), 0);
+    assert_eq!(rows_written_by_filled_writer(Some(10000)), 0);
+    assert_eq!(rows_written_by_filled_writer(Some(4000)), 1000);
+}
+
+/// Writes an empty file, and fails to close the one with id 1
+struct FailingTableWriter {
+    path: PathBuf,
+}
+
+impl TableWriter for FailingTableWriter {
+    type Schema = ();
+    type CloseResult = PathBuf;
+    type Config = ();
+
+    fn new(path: PathBuf, _schema: (), _config: ()) -> Result<Self> {
+        std::fs::write(&path, "")?;
+        Ok(FailingTableWriter { path })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(self) -> Result<PathBuf> {
+        ensure!(
+            !self.path.ends_with("1"),
+            "Could not close {}",
+            self.path.display()
+        );
+        Ok(self.path)
+    }
+}
+
+/// Gets a writer from each of `num_threads` threads
+fn get_writers<W: TableWriter + Send>(dataset_writer: &ParallelDatasetWriter<W>, num_threads: usize)
+where
+    W::Config: Default,
+    ParallelDatasetWriter<W>: Sync,
+{
+    // Keeps threads alive until all got a writer, so none reuses the writer of another
+    let barrier = std::sync::Barrier::new(num_threads);
+    std::thread::scope(|s| {
+        for _ in 0..num_threads {
+            s.spawn(|| {
+                drop(dataset_writer.get_thread_writer().unwrap());
+                barrier.wait();
+            });
+        }
+    });
+}
+
+#[test]
+fn best_effort() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer =
+        ParallelDatasetWriter::<FailingTableWriter>::new(tmp_dir.path().to_owned()).unwrap();
+    get_writers(&dataset_writer, 3);
+    assert!(dataset_writer.close().is_err());
+
+    dataset_writer =
+        ParallelDatasetWriter::<FailingTableWriter>::new(tmp_dir.path().to_owned()).unwrap();
+    dataset_writer.best_effort = true;
+    get_writers(&dataset_writer, 3);
+    let mut paths = dataset_writer.close().unwrap();
+    paths.sort();
+    assert_eq!(paths, [tmp_dir.path().join("0"), tmp_dir.path().join("2")]);
+
+    dataset_writer =
+        ParallelDatasetWriter::<FailingTableWriter>::new(tmp_dir.path().to_owned()).unwrap();
+    dataset_writer.best_effort = true;
+    get_writers(&dataset_writer, 3);
+    // Does not panic
+    drop(dataset_writer);
+}
+
+/// Writes nothing, but panics when flushing or closing `1`
+struct PanickingTableWriter {
+    path: PathBuf,
+}
+
+impl TableWriter for PanickingTableWriter {
+    type Schema = ();
+    type CloseResult = PathBuf;
+    type Config = ();
+
+    fn new(path: PathBuf, _schema: (), _config: ()) -> Result<Self> {
+        Ok(PanickingTableWriter { path })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        assert!(!self.path.ends_with("1"), "flush failed on purpose");
+        Ok(())
+    }
+
+    fn close(self) -> Result<PathBuf> {
+        assert!(!self.path.ends_with("1"), "close failed on purpose");
+        Ok(self.path)
+    }
+}
+
+#[test]
+fn writer_panics_become_errors_naming_the_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer =
+        ParallelDatasetWriter::<PanickingTableWriter>::new(tmp_dir.path().to_owned()).unwrap();
+    get_writers(&dataset_writer, 3);
+    let path = tmp_dir.path().join("1");
+
+    let err = dataset_writer.flush().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Table writer of {} panicked while flushing: flush failed on purpose",
+            path.display()
+        )
+    );
+    let err = dataset_writer.close().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Table writer of {} panicked while closing: close failed on purpose",
+            path.display()
+        )
+    );
+
+    // Other writers are still closed with best_effort
+    let mut dataset_writer =
+        ParallelDatasetWriter::<PanickingTableWriter>::new(tmp_dir.path().to_owned()).unwrap();
+    dataset_writer.best_effort = true;
+    get_writers(&dataset_writer, 3);
+    let mut paths = dataset_writer.close().unwrap();
+    paths.sort();
+    assert_eq!(paths, [tmp_dir.path().join("0"), tmp_dir.path().join("2")]);
+}
+
+#[test]
+fn files_per_dir() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().join("dataset"),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.files_per_dir = Some(2);
+    get_writers(&dataset_writer, 5);
+    dataset_writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(&tmp_dir.path().join("dataset")),
+        [
+            "0000",
+            "0000/0.parquet",
+            "0000/1.parquet",
+            "0001",
+            "0001/2.parquet",
+            "0001/3.parquet",
+            "0002",
+            "0002/4.parquet"
+        ]
+    );
+}
+
+#[test]
+fn files_per_dir_is_rejected_for_partitioned_writers() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<
+        Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>,
+    >::with_schema(
+        tmp_dir.path().join("dataset"),
+        ("type".to_owned(), parquet_id_schema()),
+    )
+    .unwrap();
+    dataset_writer.files_per_dir = Some(2);
+    let Err(err) = dataset_writer.get_thread_writer() else {
+        panic!("files_per_dir was not rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "files_per_dir is not supported by partitioned writers"
+    );
+}
+
+#[test]
+fn file_name_prefix() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    // As if returned by unique_file_name_prefix() in two processes
+    let prefixes = [
+        unique_file_name_prefix(),
+        format!("{}x", unique_file_name_prefix()),
+    ];
+    for (ids, prefix) in [[1, 2], [3, 4]].iter().zip(&prefixes) {
+        let mut dataset_writer = ParallelDatasetWriter::<
+            Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>,
+        >::with_schema(
+            tmp_dir.path().to_owned(),
+            ("type".to_owned(), parquet_id_schema()),
+        )
+        .unwrap();
+        dataset_writer.file_name_prefix = Some(prefix.clone());
+        dataset_writer
+            .get_thread_writer()
+            .unwrap()
+            .partition("foo".to_owned())
+            .unwrap()
+            .builder()
+            .unwrap()
+            .0
+            .append_slice(ids);
+        dataset_writer.close().unwrap();
+    }
+
+    let mut expected_files = vec![
+        "type=foo".to_owned(),
+        format!("type=foo/{}-0.parquet", prefixes[0]),
+        format!("type=foo/{}-0.parquet", prefixes[1]),
+    ];
+    expected_files.sort();
+    assert_eq!(list_tree(tmp_dir.path()), expected_files);
+    for (ids, prefix) in [[1, 2], [3, 4]].iter().zip(&prefixes) {
+        assert_eq!(
+            &read_parquet_ids(
+                &tmp_dir
+                    .path()
+                    .join(format!("type=foo/{}-0.parquet", prefix))
+            ),
+            ids
+        );
+    }
+}
+
+#[test]
+fn dataset_size_on_disk_sums_all_files() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_path = tmp_dir.path().join("dataset");
+    let mut dataset_writer = ParallelDatasetWriter::<
+        Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>,
+    >::with_schema(
+        dataset_path.clone(),
+        ("type".to_owned(), parquet_id_schema()),
+    )
+    .unwrap();
+    dataset_writer.config.partition_config.row_groups_sidecar = true;
+    dataset_writer.write_common_metadata = true;
+    {
+        let mut writer = dataset_writer.get_thread_writer().unwrap();
+        for (partition, ids) in [("foo", [1, 2]), ("bar", [3, 4])] {
+            let partition_writer = writer.partition(partition.to_owned()).unwrap();
+            partition_writer.builder().unwrap().0.append_slice(&ids);
+            partition_writer.rotate().unwrap();
+            partition_writer.builder().unwrap().0.append_value(5);
+        }
+    }
+    dataset_writer.close().unwrap();
+
+    let files: Vec<_> = list_tree(&dataset_path)
+        .into_iter()
+        .map(|path| dataset_path.join(path))
+        .filter(|path| path.is_file())
+        .collect();
+    // 2 files per partition and their sidecars, and _common_metadata
+    assert_eq!(files.len(), 9);
+    let expected_size: u64 = files
+        .iter()
+        .map(|path| path.metadata().unwrap().len())
+        .sum();
+    assert_eq!(dataset_size_on_disk(&dataset_path).unwrap(), expected_size);
+}
+
+/// Records the size of its buffer when flushed
+struct FlushOrderRecordingTableWriter {
+    buffer_size: usize,
+    flushed_buffer_sizes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl TableWriter for FlushOrderRecordingTableWriter {
+    type Schema = Arc<Mutex<Vec<usize>>>;
+    type CloseResult = ();
+    type Config = ();
+
+    fn new(_path: PathBuf, flushed_buffer_sizes: Self::Schema, _config: ()) -> Result<Self> {
+        Ok(FlushOrderRecordingTableWriter {
+            buffer_size: 0,
+            flushed_buffer_sizes,
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.flushed_buffer_sizes
+            .lock()
+            .unwrap()
+            .push(self.buffer_size);
+        self.buffer_size = 0;
+        Ok(())
+    }
+
+    fn close(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+}
+
+#[test]
+fn flush_largest_first() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let flushed_buffer_sizes = Arc::new(Mutex::new(Vec::new()));
+    let mut dataset_writer = ParallelDatasetWriter::<FlushOrderRecordingTableWriter>::with_schema(
+        tmp_dir.path().to_owned(),
+        flushed_buffer_sizes.clone(),
+    )
+    .unwrap();
+    dataset_writer.flush_largest_first = true;
+    let buffer_sizes = [20, 50, 10, 40, 30];
+    // Keeps threads alive until all got a writer, so none reuses the writer of another
+    let barrier = std::sync::Barrier::new(buffer_sizes.len());
+    std::thread::scope(|s| {
+        for buffer_size in buffer_sizes {
+            let dataset_writer = &dataset_writer;
+            let barrier = &barrier;
+            s.spawn(move || {
+                dataset_writer.get_thread_writer().unwrap().buffer_size = buffer_size;
+                barrier.wait();
+            });
+        }
+    });
+
+    // With a single thread, rayon handles writers in the order they are scheduled
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap()
+        .install(|| dataset_writer.flush())
+        .unwrap();
+    assert_eq!(*flushed_buffer_sizes.lock().unwrap(), [50, 40, 30, 20, 10]);
+}
+
+#[test]
+fn write_common_metadata() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_path = tmp_dir.path().join("dataset");
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        dataset_path.clone(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.write_common_metadata = true;
+    dataset_writer
+        .get_thread_writer()
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_slice(&[1, 2, 3]);
+    dataset_writer.close().unwrap();
+
+    assert_eq!(list_tree(&dataset_path), ["0.parquet", "_common_metadata"]);
+    let reader = parquet_reader(&dataset_path.join("_common_metadata"));
+    assert_eq!(reader.schema().fields(), id_schema().fields());
+    assert_eq!(reader.metadata().num_row_groups(), 0);
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+}
+
+#[test]
+fn thread_diagnostics() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.track_threads = true;
+
+    // Keeps threads alive until both wrote, so none reuses the writer of the other
+    let barrier = std::sync::Barrier::new(2);
+    let thread_ids: Vec<_> = std::thread::scope(|s| {
+        [("small", 1), ("large", 3)]
+            .map(|(name, num_rows)| {
+                let (dataset_writer, barrier) = (&dataset_writer, &barrier);
+                std::thread::Builder::new()
+                    .name(name.to_owned())
+                    .spawn_scoped(s, move || {
+                        for id in 0..num_rows {
+                            dataset_writer
+                                .get_thread_writer()
+                                .unwrap()
+                                .builder()
+                                .unwrap()
+                                .0
+                                .append_value(id);
+                        }
+                        barrier.wait();
+                        std::thread::current().id()
+                    })
+                    .unwrap()
+            })
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let mut diagnostics = dataset_writer.thread_diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    let mut writer_ids: Vec<_> = diagnostics.iter().map(|d| d.writer_id).collect();
+    writer_ids.sort();
+    assert_eq!(writer_ids, [0, 1]);
+    diagnostics.sort_by_key(|d| d.get_thread_writer_calls);
+    let usage: Vec<_> = diagnostics
+        .iter()
+        .map(|d| {
+            (
+                d.thread_id,
+                d.thread_name.as_deref(),
+                d.get_thread_writer_calls,
+            )
+        })
+        .collect();
+    assert_eq!(
+        usage,
+        [
+            (thread_ids[0], Some("small"), 1),
+            (thread_ids[1], Some("large"), 3)
+        ]
+    );
+
+    // Each writer got the rows of the thread which used it
+    dataset_writer.close().unwrap();
+    let rows_by_writer: Vec<_> = diagnostics
+        .iter()
+        .map(|d| read_parquet_ids(&tmp_dir.path().join(format!("{}.parquet", d.writer_id))).len())
+        .collect();
+    assert_eq!(rows_by_writer, [1, 3]);
+}
+
+#[test]
+fn empty_dataset_behavior() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let new_dataset_writer = |name, empty_dataset_behavior| {
+        let mut dataset_writer =
+            ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+                tmp_dir.path().join(name),
+                parquet_id_schema(),
+            )
+            .unwrap();
+        dataset_writer.empty_dataset_behavior = empty_dataset_behavior;
+        dataset_writer
+    };
+
+    let dataset_writer = new_dataset_writer("leave", EmptyDatasetBehavior::LeaveEmpty);
+    assert!(dataset_writer.close().unwrap().is_empty());
+    assert!(list_tree(&tmp_dir.path().join("leave")).is_empty());
+
+    let dataset_writer = new_dataset_writer("error", EmptyDatasetBehavior::ErrorIfEmpty);
+    let err = dataset_writer.close().unwrap_err();
+    assert!(
+        err.to_string().starts_with("No table was written to"),
+        "unexpected error: {:#}",
+        err
+    );
+
+    let dataset_writer = new_dataset_writer("emit", EmptyDatasetBehavior::EmitEmptyFile);
+    assert_eq!(dataset_writer.close().unwrap().len(), 1);
+    assert_eq!(list_tree(&tmp_dir.path().join("emit")), ["0.parquet"]);
+    let reader = parquet_reader(&tmp_dir.path().join("emit/0.parquet"));
+    assert_eq!(reader.schema().fields(), id_schema().fields());
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 0);
+}
+
+#[test]
+fn finalizer() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let new_dataset_writer = |name| {
+        let calls = calls.clone();
+        ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+            tmp_dir.path().join(name),
+            parquet_id_schema(),
+        )
+        .unwrap()
+        .with_finalizer(move |results| {
+            calls.lock().unwrap().push(results.len());
+            Ok(())
+        })
+    };
+
+    let dataset_writer = new_dataset_writer("closed");
+    get_writers(&dataset_writer, 2);
+    assert_eq!(dataset_writer.close().unwrap().len(), 2);
+    assert_eq!(*calls.lock().unwrap(), [2]);
+
+    // Not called on drop
+    let dataset_writer = new_dataset_writer("dropped");
+    get_writers(&dataset_writer, 2);
+    drop(dataset_writer);
+    assert_eq!(*calls.lock().unwrap(), [2]);
+}
+
+#[test]
+fn new_parquet() {
+    use parquet::file::properties::WriterProperties;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(1)
+        .build();
+    let config = ParquetTableWriterConfig {
+        encode_row_count_in_name: true,
+        ..Default::default()
+    };
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::new_parquet(
+        tmp_dir.path().to_owned(),
+        Arc::new(id_schema()),
+        properties,
+        config,
+    )
+    .unwrap();
+    dataset_writer
+        .get_thread_writer()
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_slice(&[1, 2]);
+    let results = dataset_writer.close().unwrap();
+
+    // Written with both the properties and the config
+    assert_eq!(results[0].num_row_groups(), 2);
+    assert_eq!(list_tree(tmp_dir.path()), ["0.r2.parquet"]);
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.r2.parquet")),
+        [1, 2]
+    );
+}
+
+#[test]
+fn parallel_chunk_size() {
+    const NUM_WRITERS: u64 = 64;
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.parallel_chunk_size = Some(7);
+
+    // Each round, keeps all threads alive until they all got a writer, so each gets
+    // its own
+    for round in 0..2 {
+        let barrier = std::sync::Barrier::new(NUM_WRITERS as usize);
+        std::thread::scope(|s| {
+            for thread in 0..NUM_WRITERS {
+                let (dataset_writer, barrier) = (&dataset_writer, &barrier);
+                s.spawn(move || {
+                    let mut writer = dataset_writer.get_thread_writer().unwrap();
+                    writer
+                        .builder()
+                        .unwrap()
+                        .0
+                        .append_value(round * NUM_WRITERS + thread);
+                    barrier.wait();
+                    drop(writer);
+                });
+            }
+        });
+        dataset_writer.flush().unwrap();
+    }
+    dataset_writer.close().unwrap();
+
+    let files = list_tree(tmp_dir.path());
+    assert!(files.len() as u64 >= NUM_WRITERS, "{:?}", files);
+    let mut ids: Vec<_> = files
+        .iter()
+        .flat_map(|file| read_parquet_ids(&tmp_dir.path().join(file)))
+        .collect();
+    ids.sort();
+    assert_eq!(ids, (0..2 * NUM_WRITERS).collect::<Vec<_>>());
+}
+
+#[test]
+fn skip_arrow_schema() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    dataset_writer.write_common_metadata = true;
+    dataset_writer.config.skip_arrow_schema = true;
+    let barrier = std::sync::Barrier::new(2);
+    std::thread::scope(|s| {
+        for id in 0..2 {
+            let (dataset_writer, barrier) = (&dataset_writer, &barrier);
+            s.spawn(move || {
+                let mut writer = dataset_writer.get_thread_writer().unwrap();
+                writer.builder().unwrap().0.append_value(id);
+                barrier.wait();
+            });
+        }
+    });
+    dataset_writer.close().unwrap();
+
+    // The schema is written once, at the root
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        ["0.parquet", "1.parquet", "_common_metadata", "schema.json"]
+    );
+    let schema: arrow::datatypes::Schema =
+        serde_json::from_slice(&std::fs::read(tmp_dir.path().join("schema.json")).unwrap())
+            .unwrap();
+    assert_eq!(schema, id_schema());
+
+    let key_value_keys = |name: &str| {
+        parquet_reader(&tmp_dir.path().join(name))
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .into_iter()
+            .flatten()
+            .map(|key_value| key_value.key.clone())
+            .collect::<Vec<_>>()
+    };
+    assert!(key_value_keys("_common_metadata").contains(&"ARROW:schema".to_owned()));
+    for name in ["0.parquet", "1.parquet"] {
+        assert!(!key_value_keys(name).contains(&"ARROW:schema".to_owned()));
+        // Still valid Parquet, readable with the shared schema
+        let file = std::fs::File::open(tmp_dir.path().join(name)).unwrap();
+        let options = parquet::arrow::arrow_reader::ArrowReaderOptions::new()
+            .with_schema(std::sync::Arc::new(schema.clone()));
+        let reader =
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new_with_options(
+                file, options,
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        let num_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(num_rows, 1);
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "fault-injection")]
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use dataset_writer::*;
+
+/// Writes nothing, but counts calls to [`TableWriter::flush`] in its schema
+struct FlushCountingTableWriter {
+    num_flushes: Arc<AtomicU64>,
+}
+
+impl TableWriter for FlushCountingTableWriter {
+    type Schema = Arc<AtomicU64>;
+    type CloseResult = u64;
+    type Config = ();
+
+    fn new(_path: PathBuf, num_flushes: Self::Schema, _config: Self::Config) -> Result<Self> {
+        Ok(FlushCountingTableWriter { num_flushes })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.num_flushes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn close(self) -> Result<u64> {
+        Ok(self.num_flushes.load(Ordering::Relaxed))
+    }
+}
+
+#[test]
+fn fails_scheduled_operations_only() {
+    let num_flushes = Arc::new(AtomicU64::new(0));
+    let config = FaultInjectingTableWriterConfig {
+        inner: (),
+        failing_flushes: vec![1, 3],
+        fail_close: false,
+    };
+    let mut writer = FaultInjectingTableWriter::<FlushCountingTableWriter>::new(
+        PathBuf::from("unused"),
+        num_flushes.clone(),
+        config.clone(),
+    )
+    .unwrap();
+    let results: Vec<bool> = (0..5).map(|_| writer.flush().is_ok()).collect();
+    assert_eq!(results, [true, false, true, false, true]);
+    // Failing flushes do not reach the wrapped writer
+    assert_eq!(writer.close().unwrap(), 3);
+
+    let writer = FaultInjectingTableWriter::<FlushCountingTableWriter>::new(
+        PathBuf::from("unused"),
+        num_flushes,
+        FaultInjectingTableWriterConfig {
+            fail_close: true,
+            ..config
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        writer.close().unwrap_err().to_string(),
+        "Injected failure of close"
+    );
+}
@@ -0,0 +1,44 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "gzip")]
+
+use std::io::{Read, Write};
+
+use dataset_writer::*;
+
+#[test]
+fn each_flush_writes_a_gzip_member() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer =
+        GzipTableWriter::new(tmp_dir.path().join("0"), (), Default::default()).unwrap();
+    writer.write_all(b"first\n").unwrap();
+    TableWriter::flush(&mut writer).unwrap();
+    // Does not write an empty member
+    TableWriter::flush(&mut writer).unwrap();
+    writer.write_all(b"second\n").unwrap();
+    TableWriter::flush(&mut writer).unwrap();
+    writer.write_all(b"third\n").unwrap();
+    writer.close().unwrap();
+
+    let compressed = std::fs::read(tmp_dir.path().join("0.gz")).unwrap();
+
+    let mut whole = String::new();
+    flate2::read::MultiGzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut whole)
+        .unwrap();
+    assert_eq!(whole, "first\nsecond\nthird\n");
+
+    let mut members = Vec::new();
+    let mut remaining = compressed.as_slice();
+    while !remaining.is_empty() {
+        let mut decoder = flate2::bufread::GzDecoder::new(remaining);
+        let mut member = String::new();
+        decoder.read_to_string(&mut member).unwrap();
+        members.push(member);
+        remaining = decoder.into_inner();
+    }
+    assert_eq!(members, ["first\n", "second\n", "third\n"]);
+}
@@ -0,0 +1,49 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "iceberg")]
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn data_files_of_two_files() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut footers = Vec::new();
+    let mut paths = Vec::new();
+    for (name, num_rows) in [("a", 3), ("b", 5)] {
+        let mut writer = ParquetTableWriter::<IdBuilder>::new(
+            tmp_dir.path().join(name),
+            parquet_id_schema(),
+            Default::default(),
+        )
+        .unwrap();
+        for id in 0..num_rows {
+            writer.builder().unwrap().0.append_value(id);
+        }
+        footers.push(writer.close().unwrap());
+        paths.push(tmp_dir.path().join(format!("{}.parquet", name)));
+    }
+
+    let data_files = iceberg_data_files(&footers, &paths).unwrap();
+    assert_eq!(data_files.len(), 2);
+    for (data_file, (path, num_rows)) in data_files.iter().zip(paths.iter().zip([3, 5])) {
+        assert_eq!(data_file.content, 0);
+        assert_eq!(data_file.file_path, path.display().to_string());
+        assert_eq!(data_file.file_format, "PARQUET");
+        assert_eq!(data_file.record_count, num_rows);
+        assert_eq!(
+            data_file.file_size_in_bytes,
+            i64::try_from(std::fs::metadata(path).unwrap().len()).unwrap()
+        );
+        assert_eq!(data_file.value_counts, [(1, num_rows)].into());
+        assert_eq!(data_file.null_value_counts, [(1, 0)].into());
+        assert_eq!(data_file.split_offsets.len(), 1);
+    }
+
+    assert!(iceberg_data_files(&footers, &paths[..1]).is_err());
+}
@@ -0,0 +1,210 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "arrow-ipc")]
+
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+use arrow::array::AsArray;
+use arrow::datatypes::UInt64Type;
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+/// Returns the ids in an Arrow IPC file written with [`id_schema`], in order
+fn read_arrow_ids(path: &Path) -> Vec<u64> {
+    read_arrow_ids_from(std::fs::File::open(path).unwrap())
+}
+
+/// Returns the ids in Arrow IPC data written with [`id_schema`], in order
+fn read_arrow_ids_from(file: impl Read + Seek) -> Vec<u64> {
+    arrow::ipc::reader::FileReader::try_new(file, None)
+        .unwrap()
+        .flat_map(|batch| {
+            batch
+                .unwrap()
+                .column_by_name("id")
+                .unwrap()
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec()
+        })
+        .collect()
+}
+
+#[test]
+fn min_flush_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ArrowTableWriterConfig {
+        flush_threshold: Some(2),
+        min_flush_rows: Some(10),
+        ..Default::default()
+    };
+    let mut writer =
+        ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), id_schema(), config).unwrap();
+    for id in 0..9 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.builder().unwrap();
+    assert_eq!(writer.written_rows(), 0);
+    writer.close().unwrap();
+
+    assert_eq!(
+        read_arrow_ids(&tmp_dir.path().join("0.arrow")),
+        (0..9).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn compression_level() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let ids: Vec<u64> = (0..100_000).map(|id| id % 100).collect();
+    let write = |name: &str, compression_level| {
+        let config = ArrowTableWriterConfig {
+            compression_level,
+            ..Default::default()
+        };
+        let mut writer =
+            ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join(name), id_schema(), config)
+                .unwrap();
+        writer.builder().unwrap().0.append_slice(&ids);
+        writer.close().unwrap();
+    };
+    write("uncompressed", None);
+    write("fast", Some(1));
+    write("slow", Some(19));
+
+    let size = |file_name: &str| {
+        std::fs::metadata(tmp_dir.path().join(file_name))
+            .unwrap()
+            .len()
+    };
+    assert!(size("fast.arrow.zst") < size("uncompressed.arrow"));
+    assert!(size("slow.arrow.zst") < size("fast.arrow.zst"));
+
+    assert_eq!(
+        read_arrow_ids(&tmp_dir.path().join("uncompressed.arrow")),
+        ids
+    );
+    let decompressed =
+        zstd::decode_all(std::fs::File::open(tmp_dir.path().join("slow.arrow.zst")).unwrap())
+            .unwrap();
+    assert_eq!(read_arrow_ids_from(Cursor::new(decompressed)), ids);
+}
+
+#[test]
+fn abort_removes_the_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ArrowTableWriterConfig {
+        manual_commit: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), id_schema(), config).unwrap();
+    for id in 0..10 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_value(10);
+    writer.abort().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), Vec::<String>::new());
+}
+
+#[test]
+fn reset_dictionaries_per_flush() {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, DictionaryArray, StructArray};
+    use arrow::datatypes::{DataType, Field, Int32Type, Schema};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Schema::new(vec![Field::new(
+        "name",
+        DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+        false,
+    )]);
+    let config = ArrowTableWriterConfig {
+        reset_dictionaries_per_flush: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), schema.clone(), config)
+            .unwrap();
+    for flush in 0..20 {
+        // Distinct values on each flush
+        let names: Vec<String> = (0..100).map(|i| format!("name{}-{}", flush, i)).collect();
+        let names: DictionaryArray<Int32Type> = names.iter().map(String::as_str).collect();
+        let columns: Vec<ArrayRef> = vec![Arc::new(names)];
+        writer
+            .write_struct_array(StructArray::new(schema.fields().clone(), columns, None))
+            .unwrap();
+    }
+    writer.close().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), ["0.arrows"]);
+    let reader = arrow::ipc::reader::StreamReader::try_new(
+        std::fs::File::open(tmp_dir.path().join("0.arrows")).unwrap(),
+        None,
+    )
+    .unwrap();
+    let mut num_batches = 0;
+    for (flush, batch) in reader.enumerate() {
+        let names = batch
+            .unwrap()
+            .column(0)
+            .as_dictionary::<Int32Type>()
+            .clone();
+        // The dictionary only has the values of this flush
+        assert_eq!(names.values().len(), 100);
+        assert_eq!(
+            names.values().as_string::<i32>().value(0),
+            format!("name{}-0", flush)
+        );
+        num_batches += 1;
+    }
+    assert_eq!(num_batches, 20);
+}
+
+#[test]
+fn max_builder_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ArrowTableWriterConfig {
+        max_builder_rows: Some(10),
+        flush_threshold: Some(100),
+        ..Default::default()
+    };
+    let mut writer =
+        ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), id_schema(), config).unwrap();
+    writer.builder().unwrap().0.append_slice(&[0; 11]);
+    assert!(writer.builder().is_err());
+    writer.flush().unwrap();
+    writer.builder().unwrap();
+    writer.close().unwrap();
+}
+
+#[test]
+fn row_filter() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ArrowTableWriterConfig {
+        row_filter: Some(even_rows_filter()),
+        ..Default::default()
+    };
+    let mut writer =
+        ArrowTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), id_schema(), config).unwrap();
+    for id in 10..20 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    // Not filtered
+    writer.write_struct_array(id_array([1, 3])).unwrap();
+    writer.close().unwrap();
+
+    assert_eq!(
+        read_arrow_ids(&tmp_dir.path().join("0.arrow")),
+        [10, 12, 14, 16, 18, 1, 3]
+    );
+}
@@ -0,0 +1,136 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "json")]
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int64Array, StringArray, StructArray};
+use arrow::datatypes::{DataType, Field, Fields};
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+/// Returns an array with a nullable `name`, a `size`, and a nested nullable `origin`
+fn array() -> StructArray {
+    let origin_fields = Fields::from(vec![Field::new("url", DataType::Utf8, true)]);
+    let origin = StructArray::new(
+        origin_fields.clone(),
+        vec![Arc::new(StringArray::from(vec![Some("https://example.org"), None])) as ArrayRef],
+        None,
+    );
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(vec![Some("foo"), None])),
+        Arc::new(Int64Array::from(vec![1, 2])),
+        Arc::new(origin),
+    ];
+    StructArray::new(
+        Fields::from(vec![
+            Field::new("name", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, false),
+            Field::new("origin", DataType::Struct(origin_fields), true),
+        ]),
+        columns,
+        None,
+    )
+}
+
+fn write(config: JsonlTableWriterConfig) -> Vec<String> {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer =
+        JsonlTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), (), config).unwrap();
+    writer.write_struct_array(array()).unwrap();
+    writer.close().unwrap();
+    std::fs::read_to_string(tmp_dir.path().join("0.jsonl"))
+        .unwrap()
+        .lines()
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[test]
+fn project_columns_and_omit_nulls() {
+    let lines = write(JsonlTableWriterConfig {
+        project_columns: Some(vec!["origin".to_owned(), "name".to_owned()]),
+        render_nulls: NullMode::Omit,
+        ..Default::default()
+    });
+    assert_eq!(
+        lines,
+        [
+            r#"{"origin":{"url":"https://example.org"},"name":"foo"}"#,
+            r#"{"origin":{}}"#,
+        ]
+    );
+}
+
+#[test]
+fn explicit_nulls() {
+    let lines = write(JsonlTableWriterConfig {
+        render_nulls: NullMode::Explicit,
+        ..Default::default()
+    });
+    assert_eq!(
+        lines,
+        [
+            r#"{"name":"foo","size":1,"origin":{"url":"https://example.org"}}"#,
+            r#"{"name":null,"size":2,"origin":{"url":null}}"#,
+        ]
+    );
+}
+
+#[test]
+fn validate_utf8() {
+    use arrow::buffer::{Buffer, OffsetBuffer};
+
+    // "foo", then invalid UTF-8, which new() would reject
+    let names = unsafe {
+        StringArray::new_unchecked(
+            OffsetBuffer::new(vec![0, 3, 5].into()),
+            Buffer::from(b"foo\xff\xfe".as_slice()),
+            None,
+        )
+    };
+    let array = StructArray::new(
+        Fields::from(vec![Field::new("name", DataType::Utf8, false)]),
+        vec![Arc::new(names) as ArrayRef],
+        None,
+    );
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = JsonlTableWriterConfig {
+        validate_utf8: true,
+        ..Default::default()
+    };
+    let mut writer =
+        JsonlTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), (), config).unwrap();
+    let err = writer.write_struct_array(array).unwrap_err();
+    assert!(
+        format!("{:#}", err).ends_with("Invalid UTF-8 in column name of row 1"),
+        "unexpected error: {:#}",
+        err
+    );
+}
+
+#[test]
+fn row_filter() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = JsonlTableWriterConfig {
+        row_filter: Some(even_rows_filter()),
+        ..Default::default()
+    };
+    let mut writer =
+        JsonlTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), (), config).unwrap();
+    for id in 10..15 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.close().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("0.jsonl")).unwrap(),
+        "{\"id\":10}\n{\"id\":12}\n{\"id\":14}\n"
+    );
+}
@@ -0,0 +1,93 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "arrow")]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::*;
+use arrow::datatypes::{DataType, Field};
+use dataset_writer::*;
+
+/// Builder of rows with a mandatory `id` and optional `name` and `flag`
+#[derive(Debug, Default)]
+struct PartialRowsBuilder {
+    id: UInt64Builder,
+    name: StringBuilder,
+    flag: BooleanBuilder,
+}
+
+impl StructArrayBuilder for PartialRowsBuilder {
+    fn len(&self) -> usize {
+        self.id.len()
+    }
+
+    fn buffer_size(&self) -> usize {
+        0
+    }
+
+    fn finish(&mut self) -> Result<StructArray> {
+        let fields = vec![
+            Field::new("id", DataType::UInt64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("flag", DataType::Boolean, true),
+        ];
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.id.finish()),
+            Arc::new(self.name.finish()),
+            Arc::new(self.flag.finish()),
+        ];
+        Ok(StructArray::try_new(fields.into(), columns, None)?)
+    }
+
+    fn finish_row(&mut self) -> Result<()> {
+        pad_with_nulls(&mut [&mut self.id, &mut self.name, &mut self.flag])
+    }
+}
+
+#[test]
+fn finish_row_pads_with_nulls() {
+    let mut builder = PartialRowsBuilder::default();
+    builder.id.append_value(0);
+    builder.name.append_value("zero");
+    builder.finish_row().unwrap();
+    builder.id.append_value(1);
+    builder.flag.append_value(true);
+    builder.finish_row().unwrap();
+    builder.id.append_value(2);
+    builder.finish_row().unwrap();
+    builder.id.append_value(3);
+    builder.name.append_value("three");
+    builder.flag.append_value(false);
+    builder.finish_row().unwrap();
+
+    let array = builder.finish().unwrap();
+    assert_eq!(array.len(), 4);
+    assert_eq!(
+        array
+            .column(1)
+            .as_string::<i32>()
+            .iter()
+            .collect::<Vec<_>>(),
+        [Some("zero"), None, None, Some("three")]
+    );
+    assert_eq!(
+        array.column(2).as_boolean().iter().collect::<Vec<_>>(),
+        [None, Some(true), None, Some(false)]
+    );
+}
+
+#[test]
+fn pad_with_nulls_unsupported_builder() {
+    let mut ids = UInt64Builder::new();
+    let mut lists = ListBuilder::new(UInt64Builder::new());
+    ids.append_value(0);
+    assert!(pad_with_nulls(&mut [&mut ids, &mut lists]).is_err());
+
+    // Builders which do not need padding are not a problem
+    lists.append_null();
+    pad_with_nulls(&mut [&mut ids, &mut lists]).unwrap();
+}
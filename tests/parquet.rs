@@ -0,0 +1,1568 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::StructArray;
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+/// [`IdBuilder`] which records calls to [`StructArrayBuilder::reserve`]
+#[derive(Debug, Default)]
+struct ReserveRecordingBuilder {
+    ids: IdBuilder,
+    reserve_calls: Vec<(usize, usize)>,
+}
+
+impl StructArrayBuilder for ReserveRecordingBuilder {
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.ids.buffer_size()
+    }
+
+    fn finish(&mut self) -> Result<StructArray> {
+        self.ids.finish()
+    }
+
+    fn reserve(&mut self, rows: usize, bytes: usize) {
+        self.reserve_calls.push((rows, bytes));
+    }
+}
+
+#[test]
+fn reserve_is_called_with_the_size_of_the_previous_flush() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = ParquetTableWriter::<ReserveRecordingBuilder>::new(
+        tmp_dir.path().join("0"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    for id in 0..3 {
+        writer.builder().unwrap().ids.0.append_value(id);
+    }
+    writer.flush().unwrap();
+    assert_eq!(writer.builder().unwrap().reserve_calls, [(3, 24)]);
+    for id in 3..8 {
+        writer.builder().unwrap().ids.0.append_value(id);
+    }
+    writer.flush().unwrap();
+    assert_eq!(writer.builder().unwrap().reserve_calls, [(3, 24), (5, 40)]);
+    writer.close().unwrap();
+}
+
+#[test]
+fn min_flush_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        autoflush_row_group_len: Some(2),
+        min_flush_rows: Some(10),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    for id in 0..9 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.builder().unwrap();
+    assert_eq!(writer.written_rows(), 0);
+    let metadata = writer.close().unwrap();
+
+    assert_eq!(metadata.num_row_groups(), 1);
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        (0..9).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn statistics_truncate_length() {
+    use arrow::array::{ArrayRef, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::file::statistics::Statistics;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![Field::new("name", DataType::Utf8, false)]));
+    let config = ParquetTableWriterConfig {
+        statistics_truncate_length: Some(8),
+        ..Default::default()
+    };
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), Default::default()),
+        config,
+    )
+    .unwrap();
+    let names = |prefix: char| -> StructArray {
+        let columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(
+            ('a'..='c').map(|c| format!("{}{}", prefix, c.to_string().repeat(100))),
+        ))];
+        StructArray::new(schema.fields().clone(), columns, None)
+    };
+    writer.write_struct_array(names('x')).unwrap();
+    let first_file = writer.rotate().unwrap().unwrap();
+    writer.write_struct_array(names('y')).unwrap();
+    let second_file = writer.close().unwrap();
+
+    for (metadata, prefix) in [(first_file, b'x'), (second_file, b'y')] {
+        let Some(Statistics::ByteArray(statistics)) = metadata.row_group(0).column(0).statistics()
+        else {
+            panic!("Missing statistics")
+        };
+        let min = statistics.min_bytes_opt().unwrap();
+        let max = statistics.max_bytes_opt().unwrap();
+        assert_eq!(min.len(), 8);
+        assert_eq!(max.len(), 8);
+        assert_eq!(min, [&[prefix][..], &[b'a'; 7]].concat());
+        assert!(!statistics.min_is_exact());
+        assert!(!statistics.max_is_exact());
+    }
+}
+
+#[test]
+fn abort_removes_all_files() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        manual_commit: true,
+        row_groups_sidecar: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    for id in 0..10 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.rotate().unwrap();
+    for id in 10..20 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_value(20);
+    assert_eq!(writer.created_files().len(), 2);
+    assert!(!list_tree(tmp_dir.path()).is_empty());
+    writer.abort().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), Vec::<String>::new());
+}
+
+#[test]
+fn abort_ignores_files_removed_by_on_file_closed() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        file_pool_size: Some(1),
+        on_file_closed: Some(Arc::new(|path, _slot| std::fs::remove_file(path).unwrap())),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    for id in 0..3 {
+        writer.builder().unwrap().0.append_value(id);
+        writer.rotate().unwrap();
+    }
+    writer.builder().unwrap().0.append_value(3);
+    writer.abort().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), Vec::<String>::new());
+}
+
+#[test]
+fn sort_within_flush() {
+    use arrow::compute::SortOptions;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        sort_within_flush: vec![(
+            "id".to_owned(),
+            SortOptions {
+                descending: false,
+                nulls_first: false,
+            },
+        )],
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[5, 3, 9, 1, 7]);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_slice(&[4, 0, 2]);
+    let metadata = writer.close().unwrap();
+
+    assert_eq!(metadata.num_row_groups(), 2);
+    for row_group in metadata.row_groups() {
+        let sorting_columns = row_group.sorting_columns().unwrap();
+        assert_eq!(sorting_columns.len(), 1);
+        assert_eq!(sorting_columns[0].column_idx, 0);
+        assert!(!sorting_columns[0].descending);
+    }
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        [1, 3, 5, 7, 9, 0, 2, 4]
+    );
+}
+
+#[test]
+fn max_files_per_writer() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        autoflush_row_group_len: Some(1),
+        // Rotate after every row group
+        max_footer_estimate_bytes: Some(1),
+        max_files_per_writer: Some(2),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_value(0);
+    assert!(!writer.rotation_imminent());
+    writer.builder().unwrap().0.append_value(1);
+    assert_eq!(writer.files_rotated(), 1);
+    // Fills the last file
+    writer.builder().unwrap().0.append_value(2);
+    assert_eq!(writer.files_rotated(), 1);
+    let err = writer.builder().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("max_files_per_writer (Some(2)) is reached"),
+        "unexpected error: {:#}",
+        err
+    );
+    assert!(writer.flush().is_err());
+    assert_eq!(writer.created_files().len(), 2);
+    // Closes the last file without the rows which do not fit
+    drop(writer);
+
+    assert_eq!(list_tree(tmp_dir.path()), ["0.parquet", "0_1.parquet"]);
+    assert_eq!(read_parquet_ids(&tmp_dir.path().join("0.parquet")), [0]);
+    assert_eq!(read_parquet_ids(&tmp_dir.path().join("0_1.parquet")), [1]);
+}
+
+#[test]
+fn rotation_imminent() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        max_footer_estimate_bytes: Some(2000),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    assert!(!writer.rotation_imminent());
+    let mut num_row_groups = 0;
+    while !writer.rotation_imminent() {
+        writer.builder().unwrap().0.append_value(num_row_groups);
+        writer.flush().unwrap();
+        assert_eq!(writer.files_rotated(), 0);
+        num_row_groups += 1;
+        assert!(num_row_groups < 1000, "rotation never became imminent");
+    }
+    assert!(num_row_groups > 1);
+    writer.builder().unwrap().0.append_value(num_row_groups);
+    writer.flush().unwrap();
+    assert_eq!(writer.files_rotated(), 1);
+    assert!(!writer.rotation_imminent());
+    writer.close().unwrap();
+
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        (0..=num_row_groups).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn uuid_logical_type() {
+    use arrow::array::{ArrayRef, FixedSizeBinaryArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::basic::LogicalType;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "swhid",
+        DataType::FixedSizeBinary(16),
+        false,
+    )]));
+    let config = ParquetTableWriterConfig {
+        column_logical_types: vec![("swhid".to_owned(), LogicalType::Uuid)],
+        ..Default::default()
+    };
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), Default::default()),
+        config.clone(),
+    )
+    .unwrap();
+    let columns: Vec<ArrayRef> = vec![Arc::new(
+        FixedSizeBinaryArray::try_from_iter([[1u8; 16], [2u8; 16]].into_iter()).unwrap(),
+    )];
+    writer
+        .write_struct_array(StructArray::new(schema.fields().clone(), columns, None))
+        .unwrap();
+    writer.close().unwrap();
+
+    let reader = parquet_reader(&tmp_dir.path().join("0.parquet"));
+    let column = reader.parquet_schema().column(0);
+    assert_eq!(column.logical_type_ref(), Some(&LogicalType::Uuid));
+    let batch = reader.build().unwrap().next().unwrap().unwrap();
+    assert_eq!(batch.num_rows(), 2);
+
+    // UUIDs must be 16 bytes long
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "swhid",
+        DataType::Int64,
+        false,
+    )]));
+    assert!(ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("1"),
+        (schema, Default::default()),
+        config,
+    )
+    .is_err());
+}
+
+#[test]
+fn float_nan_stats() {
+    use arrow::array::{ArrayRef, Float64Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::file::statistics::Statistics;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("float", DataType::Float64, false),
+        Field::new("int", DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Float64Array::from(vec![1.5, f64::NAN, -2.0])),
+        Arc::new(Int64Array::from(vec![3, 1, 2])),
+    ];
+    let array = StructArray::new(schema.fields().clone(), columns, None);
+    let write = |policy| {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config = ParquetTableWriterConfig {
+            float_nan_stats: policy,
+            ..Default::default()
+        };
+        let mut writer = ParquetTableWriter::<IdBuilder>::new(
+            tmp_dir.path().join("0"),
+            (schema.clone(), Default::default()),
+            config,
+        )
+        .unwrap();
+        writer.write_struct_array(array.clone()).unwrap();
+        writer.close().unwrap()
+    };
+
+    let metadata = write(NanStatsPolicy::Ignore);
+    let row_group = metadata.row_group(0);
+    let Some(Statistics::Double(float_statistics)) = row_group.column(0).statistics() else {
+        panic!("Missing statistics of float column")
+    };
+    assert_eq!(float_statistics.min_opt(), Some(&-2.0));
+    assert_eq!(float_statistics.max_opt(), Some(&1.5));
+    assert!(row_group.column(1).statistics().is_some());
+
+    let metadata = write(NanStatsPolicy::DisableFloatStatistics);
+    let row_group = metadata.row_group(0);
+    assert!(row_group.column(0).statistics().is_none());
+    let Some(Statistics::Int64(int_statistics)) = row_group.column(1).statistics() else {
+        panic!("Missing statistics of int column")
+    };
+    assert_eq!(int_statistics.min_opt(), Some(&1));
+    assert_eq!(int_statistics.max_opt(), Some(&3));
+}
+
+#[test]
+fn row_group_boundary_column() {
+    use parquet::file::statistics::Statistics;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        row_group_boundary_column: Some("id".to_owned()),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 1, 1, 2, 2]);
+    writer.flush().unwrap();
+    // The run of 2s continues across flushes, but row groups do not
+    writer.builder().unwrap().0.append_slice(&[2, 3, 5, 5]);
+    let metadata = writer.close().unwrap();
+
+    let row_groups: Vec<_> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| {
+            let Some(Statistics::Int64(statistics)) = row_group.column(0).statistics() else {
+                panic!("Missing statistics")
+            };
+            assert_eq!(statistics.min_opt(), statistics.max_opt());
+            (*statistics.min_opt().unwrap(), row_group.num_rows())
+        })
+        .collect();
+    assert_eq!(row_groups, [(1, 3), (2, 2), (2, 1), (3, 1), (5, 2)]);
+}
+
+#[test]
+fn compression_for_file() {
+    use parquet::basic::{Compression, ZstdLevel};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        compression_for_file: Some(Arc::new(|file_index| match file_index {
+            0 => Compression::UNCOMPRESSED,
+            _ => Compression::ZSTD(ZstdLevel::try_new(19).unwrap()),
+        })),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 2, 3]);
+    writer.rotate().unwrap();
+    writer.builder().unwrap().0.append_slice(&[4, 5, 6]);
+    writer.close().unwrap();
+
+    for (file_name, compression) in [
+        ("0.parquet", Compression::UNCOMPRESSED),
+        // The level is not stored in files
+        ("0_1.parquet", Compression::ZSTD(Default::default())),
+    ] {
+        let reader = parquet_reader(&tmp_dir.path().join(file_name));
+        assert_eq!(
+            reader.metadata().row_group(0).column(0).compression(),
+            compression,
+            "unexpected compression of {}",
+            file_name
+        );
+    }
+}
+
+#[test]
+fn set_file_metadata() {
+    /// Uses the metadata API common to all formats
+    fn set_provenance<W: TableWriter>(writer: &mut W) {
+        writer.set_file_metadata("provenance", "test");
+    }
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 2]);
+    writer.flush().unwrap();
+    // Applies to the current file, even if rows were already written to it
+    set_provenance(&mut writer);
+    writer.rotate().unwrap();
+    // And to the next ones
+    writer.builder().unwrap().0.append_slice(&[3]);
+    writer.close().unwrap();
+
+    for file_name in ["0.parquet", "0_1.parquet"] {
+        let reader = parquet_reader(&tmp_dir.path().join(file_name));
+        let key_values = reader
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap();
+        assert!(
+            key_values
+                .iter()
+                .any(|kv| kv.key == "provenance" && kv.value.as_deref() == Some("test")),
+            "missing metadata in {}: {:?}",
+            file_name,
+            key_values
+        );
+    }
+}
+
+#[test]
+fn flush_and_mark() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 2, 3]);
+    writer.flush_and_mark("first".to_owned()).unwrap();
+    writer.builder().unwrap().0.append_slice(&[4, 5]);
+    writer.flush_and_mark("second".to_owned()).unwrap();
+    writer.builder().unwrap().0.append_slice(&[6]);
+    assert_eq!(
+        writer.marks(),
+        [("first".to_owned(), 3), ("second".to_owned(), 5)]
+    );
+    let metadata = writer.close().unwrap();
+
+    assert_eq!(metadata.num_row_groups(), 3);
+    let marks: Vec<_> = metadata
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .filter_map(|kv| {
+            let label = kv.key.strip_prefix(MARK_METADATA_KEY_PREFIX)?;
+            Some((label.to_owned(), kv.value.clone().unwrap()))
+        })
+        .collect();
+    assert_eq!(
+        marks,
+        [
+            ("first".to_owned(), "3".to_owned()),
+            ("second".to_owned(), "5".to_owned())
+        ]
+    );
+}
+
+#[test]
+fn bloom_filter_position() {
+    use parquet::file::properties::{BloomFilterPosition, ReaderProperties, WriterProperties};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::file::serialized_reader::ReadOptionsBuilder;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        bloom_filter_position: Some(BloomFilterPosition::AfterRowGroup),
+        ..Default::default()
+    };
+    let properties = WriterProperties::builder()
+        .set_bloom_filter_enabled(true)
+        .build();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (parquet_id_schema().0, properties),
+        config,
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 2, 3]);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_slice(&[4, 5]);
+    writer.close().unwrap();
+
+    let path = tmp_dir.path().join("0.parquet");
+    assert_eq!(read_parquet_ids(&path), [1, 2, 3, 4, 5]);
+    let reader = SerializedFileReader::new_with_options(
+        std::fs::File::open(&path).unwrap(),
+        ReadOptionsBuilder::new()
+            .with_reader_properties(
+                ReaderProperties::builder()
+                    .set_read_bloom_filter(true)
+                    .build(),
+            )
+            .build(),
+    )
+    .unwrap();
+    // Bloom filters are written after the first row group, before the second one
+    let first_bloom_filter_offset = reader
+        .metadata()
+        .row_group(0)
+        .column(0)
+        .bloom_filter_offset();
+    let second_row_group_offset = reader.metadata().row_group(1).column(0).data_page_offset();
+    assert!(first_bloom_filter_offset.unwrap() < second_row_group_offset);
+    for (row_group, ids) in [(0, &[1i64, 2, 3][..]), (1, &[4, 5])] {
+        let row_group_reader = reader.get_row_group(row_group).unwrap();
+        let bloom_filter = row_group_reader.get_column_bloom_filter(0).unwrap();
+        for id in ids {
+            assert!(bloom_filter.check(id));
+        }
+    }
+}
+
+#[test]
+fn dedup_consecutive() {
+    use arrow::array::AsArray;
+    use arrow::datatypes::{DataType, Field, Schema, UInt64Type};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = DedupConsecutiveTableWriter::<IdBuilder, ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("plain"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 1, 2]);
+    writer.flush().unwrap();
+    // The run of 2s continues across flushes
+    writer.builder().unwrap().0.append_slice(&[2, 2, 1]);
+    writer.close().unwrap();
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("plain.parquet")),
+        [1, 2, 1]
+    );
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("count", DataType::UInt64, false),
+    ]));
+    let config = DedupConsecutiveTableWriterConfig {
+        count_column: Some("count".to_owned()),
+        ..Default::default()
+    };
+    let mut writer = DedupConsecutiveTableWriter::<IdBuilder, ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("counted"),
+        (schema, Default::default()),
+        config,
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 1, 2]);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_slice(&[2, 2, 1]);
+    writer.close().unwrap();
+    let batches: Vec<_> = parquet_reader(&tmp_dir.path().join("counted.parquet"))
+        .build()
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    let column = |name| -> Vec<u64> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name(name)
+                    .unwrap()
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    };
+    assert_eq!(column("id"), [1, 2, 1]);
+    assert_eq!(column("count"), [2, 3, 1]);
+}
+
+#[test]
+fn flush_with_metadata() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 2]);
+    writer
+        .flush_with_metadata(vec![
+            ("batch".to_owned(), "a".to_owned()),
+            ("source".to_owned(), "foo".to_owned()),
+        ])
+        .unwrap();
+    writer.builder().unwrap().0.append_slice(&[3]);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_slice(&[4]);
+    writer
+        .flush_with_metadata(vec![("batch".to_owned(), "b".to_owned())])
+        .unwrap();
+    assert!(writer
+        .flush_with_metadata(vec![("batch".to_owned(), "c\td".to_owned())])
+        .is_err());
+    let metadata = writer.close().unwrap();
+
+    assert_eq!(metadata.num_row_groups(), 3);
+    let sidecar_path = tmp_dir
+        .path()
+        .join(format!("0.parquet{}", ROW_GROUP_METADATA_SIDECAR_SUFFIX));
+    assert_eq!(
+        std::fs::read_to_string(sidecar_path).unwrap(),
+        "0\tbatch\ta\n0\tsource\tfoo\n2\tbatch\tb\n"
+    );
+}
+
+#[test]
+fn max_builder_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        max_builder_rows: Some(10),
+        manual_commit: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[0; 10]);
+    writer.builder().unwrap().0.append_slice(&[0; 3]);
+    let err = writer.builder().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("has 13 rows, 3 more than max_builder_rows (10)"),
+        "unexpected error: {:#}",
+        err
+    );
+    // Flushing makes room again
+    writer.flush().unwrap();
+    writer.builder().unwrap();
+    writer.close().unwrap();
+}
+
+#[test]
+fn rotate_only_closes_files_with_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    assert!(writer.rotate().unwrap().is_none());
+    writer.builder().unwrap().0.append_slice(&[1, 2]);
+    let metadata = writer.rotate().unwrap().unwrap();
+    assert_eq!(metadata.file_metadata().num_rows(), 2);
+    assert!(writer.rotate().unwrap().is_none());
+    assert_eq!(writer.files_rotated(), 1);
+    writer.close().unwrap();
+    assert_eq!(read_parquet_ids(&tmp_dir.path().join("0.parquet")), [1, 2]);
+
+    // The flush closes the file because its footer is full, so rotate() returns its
+    // metadata instead of closing the new empty file
+    let config = ParquetTableWriterConfig {
+        max_footer_estimate_bytes: Some(1),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("1"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[3, 4, 5]);
+    let metadata = writer.rotate().unwrap().unwrap();
+    assert_eq!(metadata.file_metadata().num_rows(), 3);
+    assert_eq!(writer.files_rotated(), 1);
+    assert!(writer.rotate().unwrap().is_none());
+    assert_eq!(writer.files_rotated(), 1);
+    writer.close().unwrap();
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("1.parquet")),
+        [3, 4, 5]
+    );
+    assert!(read_parquet_ids(&tmp_dir.path().join("1_1.parquet")).is_empty());
+}
+
+#[test]
+fn statistics_enabled_per_column() {
+    use arrow::array::{ArrayRef, BinaryArray, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::file::properties::{EnabledStatistics, WriterProperties};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("blob", DataType::Binary, false),
+        Field::new("id", DataType::UInt64, false),
+    ]));
+    let properties = WriterProperties::builder()
+        .set_statistics_enabled(EnabledStatistics::Chunk)
+        .build();
+    let config = ParquetTableWriterConfig {
+        statistics_enabled_per_column: vec![("blob".to_owned(), EnabledStatistics::None)],
+        ..Default::default()
+    };
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), properties),
+        config,
+    )
+    .unwrap();
+    let array = |id: u64| {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(BinaryArray::from_vec(vec![b"large blob".as_slice()])),
+            Arc::new(UInt64Array::from(vec![id])),
+        ];
+        StructArray::new(schema.fields().clone(), columns, None)
+    };
+    writer.write_struct_array(array(1)).unwrap();
+    let first_file = writer.rotate().unwrap().unwrap();
+    writer.write_struct_array(array(2)).unwrap();
+    let second_file = writer.close().unwrap();
+
+    // Applies to all files, not only the first one
+    for metadata in [first_file, second_file] {
+        let row_group = metadata.row_group(0);
+        assert!(row_group.column(0).statistics().is_none());
+        assert!(row_group.column(1).statistics().is_some());
+    }
+
+    let config = ParquetTableWriterConfig {
+        statistics_enabled_per_column: vec![("unknown".to_owned(), EnabledStatistics::None)],
+        ..Default::default()
+    };
+    assert!(ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("1"),
+        (schema, Default::default()),
+        config,
+    )
+    .is_err());
+}
+
+#[test]
+fn write_batch_size() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        write_batch_size: Some(3),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer
+        .builder()
+        .unwrap()
+        .0
+        .append_slice(&(0..100).collect::<Vec<_>>());
+    writer.rotate().unwrap();
+    writer
+        .builder()
+        .unwrap()
+        .0
+        .append_slice(&(100..150).collect::<Vec<_>>());
+    writer.close().unwrap();
+
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        (0..100).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0_1.parquet")),
+        (100..150).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn flush_splits_row_groups() {
+    use parquet::file::properties::WriterProperties;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(10)
+        .build();
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (Arc::new(id_schema()), properties),
+        Default::default(),
+    )
+    .unwrap();
+    writer
+        .builder()
+        .unwrap()
+        .0
+        .append_slice(&(0..25).collect::<Vec<_>>());
+    let metadata = writer.close().unwrap();
+
+    let row_group_sizes: Vec<_> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    assert_eq!(row_group_sizes, [10, 10, 5]);
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        (0..25).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn index_column() {
+    use arrow::array::AsArray;
+    use arrow::datatypes::UInt64Type;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        index_column: Some("id".to_owned()),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_slice(&[5, 3, 9]);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_slice(&[1, 7]);
+    writer.rotate().unwrap();
+    writer.builder().unwrap().0.append_slice(&[4, 2]);
+    writer.close().unwrap();
+
+    let mut index_path = tmp_dir.path().join("0.parquet").into_os_string();
+    index_path.push(INDEX_SIDECAR_SUFFIX);
+    let index: Vec<_> = parquet_reader(std::path::Path::new(&index_path))
+        .build()
+        .unwrap()
+        .flat_map(|batch| {
+            let batch = batch.unwrap();
+            let keys = batch.column(0).as_primitive::<UInt64Type>().clone();
+            let files = batch.column(1).as_string::<i32>().clone();
+            let row_groups = batch.column(2).as_primitive::<UInt64Type>().clone();
+            let row_offsets = batch.column(3).as_primitive::<UInt64Type>().clone();
+            (0..batch.num_rows())
+                .map(|i| {
+                    (
+                        keys.value(i),
+                        files.value(i).to_owned(),
+                        row_groups.value(i),
+                        row_offsets.value(i),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let entry =
+        |key, file: &str, row_group, row_offset| (key, file.to_owned(), row_group, row_offset);
+    assert_eq!(
+        index,
+        [
+            entry(1, "0.parquet", 1, 0),
+            entry(2, "0_1.parquet", 0, 1),
+            entry(3, "0.parquet", 0, 1),
+            entry(4, "0_1.parquet", 0, 0),
+            entry(5, "0.parquet", 0, 0),
+            entry(7, "0.parquet", 1, 1),
+            entry(9, "0.parquet", 0, 2),
+        ]
+    );
+
+    // Each entry resolves to the row with its key
+    for (key, file, row_group, row_offset) in index {
+        let batch = parquet_reader(&tmp_dir.path().join(file))
+            .with_row_groups(vec![row_group as usize])
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let ids = batch.column(0).as_primitive::<UInt64Type>();
+        assert_eq!(ids.value(row_offset as usize), key);
+    }
+}
+
+#[test]
+fn estimate_parquet_size_is_close_to_actual_size() {
+    use arrow::array::{ArrayRef, StringArray, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let num_rows = 10000;
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(0..num_rows)),
+        Arc::new(StringArray::from_iter_values(
+            (0..num_rows).map(|i| format!("{:032}", i)),
+        )),
+    ];
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), Default::default()),
+        Default::default(),
+    )
+    .unwrap();
+    writer
+        .write_struct_array(StructArray::new(schema.fields().clone(), columns, None))
+        .unwrap();
+    writer.close().unwrap();
+
+    let actual_size = std::fs::metadata(tmp_dir.path().join("0.parquet"))
+        .unwrap()
+        .len();
+    let estimate = estimate_parquet_size(&schema, num_rows, 1.);
+    assert!(
+        estimate / 2 < actual_size && actual_size < estimate * 2,
+        "estimated {} bytes, wrote {} bytes",
+        estimate,
+        actual_size
+    );
+    assert_eq!(estimate_parquet_size(&schema, num_rows, 2.), estimate / 2);
+}
+
+#[test]
+fn row_groups_sidecar() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        row_groups_sidecar: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    let data_path = tmp_dir.path().join("0.parquet");
+    let mut sidecar_path = data_path.clone().into_os_string();
+    sidecar_path.push(ROW_GROUPS_SIDECAR_SUFFIX);
+    let read_sidecar = || -> Vec<Vec<u64>> {
+        std::fs::read_to_string(&sidecar_path)
+            .unwrap()
+            .lines()
+            .map(|line| line.split('\t').map(|n| n.parse().unwrap()).collect())
+            .collect()
+    };
+
+    assert!(read_sidecar().is_empty());
+    for num_rows in 1..=3 {
+        writer
+            .builder()
+            .unwrap()
+            .0
+            .append_slice(&vec![42; num_rows]);
+        writer.flush().unwrap();
+        let entries = read_sidecar();
+        assert_eq!(entries.len(), num_rows);
+        let [row_group, rows, start, length] = entries.last().unwrap()[..] else {
+            panic!("Unexpected entry {:?}", entries.last());
+        };
+        assert_eq!((row_group, rows), (num_rows as u64 - 1, num_rows as u64));
+        // Already written to the data file
+        assert!(start + length <= std::fs::metadata(&data_path).unwrap().len());
+    }
+    let entries = read_sidecar();
+    let metadata = writer.close().unwrap();
+
+    // Same as in the footer
+    let footer_entries: Vec<Vec<u64>> = metadata
+        .row_groups()
+        .iter()
+        .enumerate()
+        .map(|(i, row_group)| {
+            let (start, length) = row_group.column(0).byte_range();
+            vec![i as u64, row_group.num_rows() as u64, start, length]
+        })
+        .collect();
+    assert_eq!(entries, footer_entries);
+}
+
+#[test]
+fn poisoned_writer_is_not_flushed_on_drop() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let num_filter_calls = Arc::new(AtomicUsize::new(0));
+    let num_closed_files = Arc::new(AtomicUsize::new(0));
+    let config = ParquetTableWriterConfig {
+        row_filter: Some({
+            let num_filter_calls = num_filter_calls.clone();
+            Arc::new(move |_array| {
+                num_filter_calls.fetch_add(1, Ordering::SeqCst);
+                anyhow::bail!("Failing on purpose")
+            })
+        }),
+        on_file_closed: Some({
+            let num_closed_files = num_closed_files.clone();
+            Arc::new(move |_path, _slot| {
+                num_closed_files.fetch_add(1, Ordering::SeqCst);
+            })
+        }),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    writer.builder().unwrap().0.append_value(1);
+    assert!(writer.flush().is_err());
+    assert!(writer.is_poisoned());
+    assert_eq!(num_filter_calls.load(Ordering::SeqCst), 1);
+
+    // Does not panic, nor flush or close the file again
+    drop(writer);
+    assert_eq!(num_filter_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(num_closed_files.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn max_footer_estimate_bytes() {
+    // Returns the number of row groups of each file written with 1-row row groups
+    let row_groups_per_file = |max_footer_estimate_bytes| {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config = ParquetTableWriterConfig {
+            max_footer_estimate_bytes: Some(max_footer_estimate_bytes),
+            ..Default::default()
+        };
+        let mut writer = ParquetTableWriter::<IdBuilder>::new(
+            tmp_dir.path().join("0"),
+            parquet_id_schema(),
+            config,
+        )
+        .unwrap();
+        for id in 0..100 {
+            writer.builder().unwrap().0.append_value(id);
+            writer.flush().unwrap();
+        }
+        let paths = writer.created_files().to_vec();
+        writer.close().unwrap();
+        paths
+            .iter()
+            .map(|path| parquet_reader(path).metadata().num_row_groups())
+            .collect::<Vec<_>>()
+    };
+
+    let small_footers = row_groups_per_file(2000);
+    assert!(small_footers.len() > 2, "{:?}", small_footers);
+    assert_eq!(small_footers.iter().sum::<usize>(), 100);
+    // All files but the last one are full
+    let full_files = &small_footers[..small_footers.len() - 1];
+    assert!(full_files.iter().all(|&n| n == full_files[0]));
+
+    // Files have about twice as many row groups with twice as large a cap
+    let large_footers = row_groups_per_file(4000);
+    assert_eq!(large_footers.iter().sum::<usize>(), 100);
+    assert!(
+        large_footers[0].abs_diff(2 * small_footers[0]) <= 2,
+        "{:?} vs {:?}",
+        large_footers,
+        small_footers
+    );
+}
+
+/// Builder of a single `id` column, which is nullable only when it contains a null
+#[derive(Debug, Default)]
+struct UnstableSchemaBuilder(arrow::array::UInt64Builder);
+
+impl StructArrayBuilder for UnstableSchemaBuilder {
+    fn len(&self) -> usize {
+        arrow::array::ArrayBuilder::len(&self.0)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.len() * 8
+    }
+
+    fn finish(&mut self) -> Result<StructArray> {
+        let column = self.0.finish();
+        let nullable = arrow::array::Array::null_count(&column) > 0;
+        Ok(StructArray::new(
+            vec![arrow::datatypes::Field::new(
+                "id",
+                arrow::datatypes::DataType::UInt64,
+                nullable,
+            )]
+            .into(),
+            vec![Arc::new(column)],
+            None,
+        ))
+    }
+}
+
+#[test]
+fn check_schema_stability() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        check_schema_stability: Some(true),
+        ..Default::default()
+    };
+    let schema = Arc::new(arrow::datatypes::Schema::new(vec![
+        arrow::datatypes::Field::new("id", arrow::datatypes::DataType::UInt64, true),
+    ]));
+    let mut writer = ParquetTableWriter::<UnstableSchemaBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema, Default::default()),
+        config,
+    )
+    .unwrap();
+    writer.builder().unwrap().0.append_value(1);
+    writer.flush().unwrap();
+    writer.builder().unwrap().0.append_null();
+    let err = writer.flush().unwrap_err();
+    assert!(writer.is_poisoned());
+    let message = format!("{:#}", err);
+    assert!(
+        message.contains("different fields than on the first flush"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("field 0: expected id: UInt64, got id: UInt64 (nullable)"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn bitmap_index_columns() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        bitmap_index_columns: vec!["id".to_owned()],
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    // Positions are tracked across flushes within a file
+    for ids in [[1, 2, 1], [3, 1, 2]] {
+        for id in ids {
+            writer.builder().unwrap().0.append_value(id);
+        }
+        writer.flush().unwrap();
+    }
+    // ... and start over in the next file
+    writer.rotate().unwrap().unwrap();
+    for id in [2, 2, 1] {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    let paths = writer.created_files().to_vec();
+    writer.close().unwrap();
+
+    assert_eq!(paths.len(), 2);
+    let sidecar_path = |path: &std::path::Path| {
+        let mut sidecar_path = path.as_os_str().to_owned();
+        sidecar_path.push(".id");
+        sidecar_path.push(BITMAP_INDEX_SIDECAR_SUFFIX);
+        std::path::PathBuf::from(sidecar_path)
+    };
+    assert_eq!(
+        read_bitmap_index(&sidecar_path(&paths[0])),
+        [
+            (Some("1".to_owned()), vec![0, 2, 4]),
+            (Some("2".to_owned()), vec![1, 5]),
+            (Some("3".to_owned()), vec![3]),
+        ]
+    );
+    assert_eq!(
+        read_bitmap_index(&sidecar_path(&paths[1])),
+        [
+            (Some("1".to_owned()), vec![2]),
+            (Some("2".to_owned()), vec![0, 1]),
+        ]
+    );
+    // Rows at the positions of a value have that value
+    let ids = read_parquet_ids(&paths[0]);
+    assert!([0, 2, 4].iter().all(|&position| ids[position] == 1));
+}
+
+#[test]
+fn encode_row_count_in_name() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        encode_row_count_in_name: true,
+        row_groups_sidecar: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    for id in 0..5 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.rotate().unwrap().unwrap();
+    for id in 5..8 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.close().unwrap();
+
+    let sidecar = |name: &str| format!("{}{}", name, ROW_GROUPS_SIDECAR_SUFFIX);
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        [
+            "0.r5.parquet".to_owned(),
+            sidecar("0.r5.parquet"),
+            "0_1.r3.parquet".to_owned(),
+            sidecar("0_1.r3.parquet"),
+        ]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.r5.parquet")),
+        [0, 1, 2, 3, 4]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0_1.r3.parquet")),
+        [5, 6, 7]
+    );
+}
+
+#[test]
+#[tracing_test::traced_test]
+fn warn_small_row_groups() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, row_group_len: u64| {
+        let config = ParquetTableWriterConfig {
+            warn_small_row_groups: Some(10),
+            ..Default::default()
+        };
+        let mut writer = ParquetTableWriter::<IdBuilder>::new(
+            tmp_dir.path().join(name),
+            parquet_id_schema(),
+            config,
+        )
+        .unwrap();
+        for id in 0..100 {
+            writer.builder().unwrap().0.append_value(id);
+            if (id + 1) % row_group_len == 0 {
+                writer.flush().unwrap();
+            }
+        }
+        writer.close().unwrap();
+    };
+
+    write("large", 50);
+    assert!(!logs_contain("warn_small_row_groups"));
+
+    write("small", 2);
+    assert!(logs_contain("warn_small_row_groups"));
+    assert!(logs_contain("small.parquet (median: 2 rows)"));
+    assert!(!logs_contain("large.parquet"));
+}
+
+#[test]
+fn file_pool_size() {
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let opened_files = Arc::new(Mutex::new(Vec::new()));
+    // Uploads then deletes each closed file in another thread, which frees its slot
+    let (closed_files_tx, closed_files_rx) = std::sync::mpsc::channel::<(PathBuf, FileSlot)>();
+    let uploader = std::thread::spawn({
+        let dir = tmp_dir.path().to_owned();
+        move || {
+            let mut uploads = Vec::new();
+            for (path, slot) in closed_files_rx {
+                // Gives the writer time to reach the slot, to check it blocks
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                assert!(list_tree(&dir).len() <= 2, "{:?}", list_tree(&dir));
+                uploads.push((
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    slot.index(),
+                    read_parquet_ids(&path),
+                ));
+                std::fs::remove_file(&path).unwrap();
+                drop(slot);
+            }
+            uploads
+        }
+    });
+    let config = ParquetTableWriterConfig {
+        file_pool_size: Some(2),
+        on_file_opened: Some({
+            let opened_files = opened_files.clone();
+            Arc::new(move |path| {
+                opened_files
+                    .lock()
+                    .unwrap()
+                    .push(path.file_name().unwrap().to_string_lossy().into_owned())
+            })
+        }),
+        on_file_closed: Some(Arc::new(move |path, slot| {
+            closed_files_tx.send((path.to_owned(), slot)).unwrap()
+        })),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    for file in 0..4 {
+        if file > 0 {
+            writer.rotate().unwrap().unwrap();
+        }
+        writer.builder().unwrap().0.append_value(file);
+    }
+    writer.close().unwrap();
+
+    assert_eq!(
+        *opened_files.lock().unwrap(),
+        ["0.parquet", "0_1.parquet", "0.parquet", "0_1.parquet"]
+    );
+    assert_eq!(
+        uploader.join().unwrap(),
+        [
+            ("0.parquet".to_owned(), 0, vec![0]),
+            ("0_1.parquet".to_owned(), 1, vec![1]),
+            ("0.parquet".to_owned(), 0, vec![2]),
+            ("0_1.parquet".to_owned(), 1, vec![3]),
+        ]
+    );
+    assert!(list_tree(tmp_dir.path()).is_empty());
+}
+
+#[test]
+fn column_compression() {
+    use arrow::array::{ArrayRef, BinaryArray, StringArray, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::basic::Compression;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("text", DataType::Utf8, false),
+        Field::new("hash", DataType::Binary, false),
+    ]));
+    let array = |ids: std::ops::Range<u64>| {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(ids.clone())),
+            Arc::new(StringArray::from_iter_values(
+                ids.clone().map(|id| format!("text {}", id)),
+            )),
+            Arc::new(BinaryArray::from_iter_values(
+                ids.map(|id| id.to_le_bytes()),
+            )),
+        ];
+        StructArray::new(schema.fields().clone(), columns, None)
+    };
+    let mk_config = |column_compression| ParquetTableWriterConfig {
+        column_compression,
+        ..Default::default()
+    };
+    let properties = parquet::file::properties::WriterProperties::builder()
+        .set_compression(Compression::ZSTD(Default::default()))
+        .build();
+
+    let config = mk_config(vec![
+        ("text".to_owned(), Compression::ZSTD(Default::default())),
+        ("hash".to_owned(), Compression::UNCOMPRESSED),
+    ]);
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), properties.clone()),
+        config,
+    )
+    .unwrap();
+    writer.write_struct_array(array(0..10)).unwrap();
+    writer.rotate().unwrap().unwrap();
+    writer.write_struct_array(array(10..20)).unwrap();
+    let paths = writer.created_files().to_vec();
+    writer.close().unwrap();
+
+    // Applies to all files, and other columns keep the codec of the properties
+    assert_eq!(paths.len(), 2);
+    for path in paths {
+        let metadata = parquet_reader(&path).metadata().clone();
+        for row_group in metadata.row_groups() {
+            let codecs: Vec<_> = row_group
+                .columns()
+                .iter()
+                .map(|column| column.compression())
+                .collect();
+            assert_eq!(
+                codecs,
+                [
+                    Compression::ZSTD(Default::default()),
+                    Compression::ZSTD(Default::default()),
+                    Compression::UNCOMPRESSED
+                ]
+            );
+        }
+    }
+
+    let config = mk_config(vec![("nope".to_owned(), Compression::UNCOMPRESSED)]);
+    let err = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("1"),
+        (schema, properties),
+        config,
+    )
+    .err()
+    .unwrap();
+    assert!(format!("{:#}", err).contains("Unknown top-level column nope"));
+}
+
+#[test]
+fn eager_file_creation() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        eager_file_creation: true,
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+
+    // The schema is discoverable before any row is written
+    let sidecar_name = format!("0.parquet{}", SCHEMA_SIDECAR_SUFFIX);
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        ["0.parquet".to_owned(), sidecar_name.clone()]
+    );
+    let sidecar = parquet_reader(&tmp_dir.path().join(&sidecar_name));
+    assert_eq!(**sidecar.schema(), id_schema());
+    assert_eq!(sidecar.metadata().file_metadata().num_rows(), 0);
+
+    writer.builder().unwrap().0.append_value(1);
+    writer.flush().unwrap();
+    writer.close().unwrap();
+
+    // Removed once the first file has a footer
+    assert_eq!(list_tree(tmp_dir.path()), ["0.parquet"]);
+    assert_eq!(read_parquet_ids(&tmp_dir.path().join("0.parquet")), [1]);
+}
+
+#[test]
+fn row_filter() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = ParquetTableWriterConfig {
+        row_filter: Some(even_rows_filter()),
+        ..Default::default()
+    };
+    let mut writer =
+        ParquetTableWriter::<IdBuilder>::new(tmp_dir.path().join("0"), parquet_id_schema(), config)
+            .unwrap();
+    // Positions start over on each flush
+    for ids in [10..15, 20..25] {
+        for id in ids {
+            writer.builder().unwrap().0.append_value(id);
+        }
+        writer.flush().unwrap();
+    }
+    assert_eq!(writer.written_rows(), 6);
+    writer.close().unwrap();
+
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        [10, 12, 14, 20, 22, 24]
+    );
+}
+
+#[test]
+fn auto_bloom_filter_cardinality() {
+    use arrow::array::{ArrayRef, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("category", DataType::UInt64, false),
+    ]));
+    let array = |ids: std::ops::Range<u64>| {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(ids.clone())),
+            Arc::new(UInt64Array::from_iter_values(ids.map(|id| id % 3))),
+        ];
+        StructArray::new(schema.fields().clone(), columns, None)
+    };
+    let config = ParquetTableWriterConfig {
+        auto_bloom_filter_cardinality: Some(50),
+        ..Default::default()
+    };
+    let mut writer = ParquetTableWriter::<IdBuilder>::new(
+        tmp_dir.path().join("0"),
+        (schema.clone(), Default::default()),
+        config,
+    )
+    .unwrap();
+    writer.write_struct_array(array(0..100)).unwrap();
+    writer.rotate().unwrap().unwrap();
+    writer.write_struct_array(array(100..200)).unwrap();
+    let paths = writer.created_files().to_vec();
+    writer.close().unwrap();
+
+    // Returns whether each column of the file has a bloom filter
+    let has_bloom_filters = |path: &std::path::Path| {
+        let metadata = parquet_reader(path).metadata().clone();
+        assert_eq!(metadata.num_row_groups(), 1);
+        metadata
+            .row_group(0)
+            .columns()
+            .iter()
+            .map(|column| column.bloom_filter_offset().is_some())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(paths.len(), 2);
+    // Cardinalities are not known yet when the first file is created
+    assert_eq!(has_bloom_filters(&paths[0]), [false, false]);
+    assert_eq!(has_bloom_filters(&paths[1]), [true, false]);
+}
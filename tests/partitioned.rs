@@ -0,0 +1,641 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::num::NonZeroU16;
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn written_rows_and_bytes_are_summed_across_partitions() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+
+    let mut writer = U16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("u16").join("0"),
+        ("bucket".to_owned(), NonZeroU16::new(2), parquet_id_schema()),
+        Default::default(),
+    )
+    .unwrap();
+    for id in 0..3 {
+        writer.partitions()[0].builder().unwrap().0.append_value(id);
+    }
+    for id in 3..10 {
+        writer.partitions()[1].builder().unwrap().0.append_value(id);
+    }
+    writer.flush().unwrap();
+    let partitions = writer.partitions();
+    assert_eq!(partitions[0].written_rows(), 3);
+    assert_eq!(partitions[1].written_rows(), 7);
+    let partition_bytes = partitions[0].written_bytes() + partitions[1].written_bytes();
+    assert!(partitions[0].written_bytes() > 0);
+    assert!(partitions[1].written_bytes() > 0);
+    assert_eq!(writer.written_rows(), 10);
+    assert_eq!(writer.written_bytes(), partition_bytes);
+
+    let mut writer = Utf8PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("utf8").join("0"),
+        ("type".to_owned(), parquet_id_schema()),
+        Default::default(),
+    )
+    .unwrap();
+    for id in 0..4 {
+        writer
+            .partition("foo".to_owned())
+            .unwrap()
+            .builder()
+            .unwrap()
+            .0
+            .append_value(id);
+    }
+    writer
+        .partition("bar".to_owned())
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(4);
+    writer.flush().unwrap();
+    let partitions = writer.partitions();
+    assert_eq!(partitions["foo"].written_rows(), 4);
+    assert_eq!(partitions["bar"].written_rows(), 1);
+    let partition_bytes = partitions["foo"].written_bytes() + partitions["bar"].written_bytes();
+    assert_eq!(writer.written_rows(), 5);
+    assert_eq!(writer.written_bytes(), partition_bytes);
+}
+
+#[test]
+fn embed_partition_metadata() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut dataset_writer = ParallelDatasetWriter::<
+        U16PartitionedTableWriter<Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>>,
+    >::with_schema(
+        tmp_dir.path().join("dataset"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(2),
+            ("type".to_owned(), parquet_id_schema()),
+        ),
+    )
+    .unwrap();
+    dataset_writer.config.embed_partition_metadata = true;
+    dataset_writer
+        .config
+        .partition_config
+        .embed_partition_metadata = true;
+    dataset_writer.get_thread_writer().unwrap().partitions()[1]
+        .partition("foo".to_owned())
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(42);
+    dataset_writer.close().unwrap();
+
+    let reader = parquet_reader(&tmp_dir.path().join("dataset/bucket=1/type=foo/0.parquet"));
+    let metadata = reader.schema().metadata();
+    assert_eq!(metadata.get("bucket").map(String::as_str), Some("1"));
+    assert_eq!(metadata.get("type").map(String::as_str), Some("foo"));
+}
+
+#[test]
+fn nested_partitioned_writers() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_writer = ParallelDatasetWriter::<
+        U16PartitionedTableWriter<Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>>,
+    >::with_schema(
+        tmp_dir.path().join("dataset"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(2),
+            ("type".to_owned(), parquet_id_schema()),
+        ),
+    )
+    .unwrap();
+    dataset_writer.get_thread_writer().unwrap().partitions()[1]
+        .partition("foo".to_owned())
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(42);
+    dataset_writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(&tmp_dir.path().join("dataset")),
+        [
+            "bucket=0",
+            "bucket=1",
+            "bucket=1/type=foo",
+            "bucket=1/type=foo/0.parquet"
+        ]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("dataset/bucket=1/type=foo/0.parquet")),
+        [42]
+    );
+}
+
+#[test]
+fn derived_partition_writer() {
+    use arrow::array::{AsArray, StructArray};
+    use arrow::datatypes::UInt64Type;
+
+    fn parity(array: &StructArray) -> Vec<String> {
+        let ids = array.column(0).as_primitive::<UInt64Type>();
+        (0..ids.len())
+            .map(|i| {
+                if ids.value(i) % 2 == 0 {
+                    "even".to_owned()
+                } else {
+                    "odd".to_owned()
+                }
+            })
+            .collect()
+    }
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = DerivedPartitionWriter::<IdBuilder, ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        ("parity".to_owned(), parity, parquet_id_schema()),
+        Default::default(),
+    )
+    .unwrap();
+    for id in 0..7 {
+        writer.builder().unwrap().0.append_value(id);
+    }
+    writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        [
+            "parity=even",
+            "parity=even/0.parquet",
+            "parity=odd",
+            "parity=odd/0.parquet"
+        ]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("parity=even/0.parquet")),
+        [0, 2, 4, 6]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("parity=odd/0.parquet")),
+        [1, 3, 5]
+    );
+}
+
+#[test]
+fn many_u16_partitions() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let num_partitions = 300;
+    let mut writer = U16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(num_partitions),
+            parquet_id_schema(),
+        ),
+        Default::default(),
+    )
+    .unwrap();
+    for partition in 0..num_partitions {
+        assert!(tmp_dir
+            .path()
+            .join(format!("bucket={}", partition))
+            .is_dir());
+    }
+    for (id, partition) in writer.partitions().iter_mut().enumerate() {
+        partition.builder().unwrap().0.append_value(id as u64);
+    }
+    writer.close().unwrap();
+
+    for partition in 0..num_partitions {
+        assert_eq!(
+            read_parquet_ids(
+                &tmp_dir
+                    .path()
+                    .join(format!("bucket={}", partition))
+                    .join("0.parquet")
+            ),
+            [u64::from(partition)]
+        );
+    }
+}
+
+#[test]
+fn u16_partition_creation_error() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    // Prevents creating the directory of a partition
+    std::fs::write(tmp_dir.path().join("bucket=7"), "").unwrap();
+    let result = U16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(300),
+            parquet_id_schema(),
+        ),
+        Default::default(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn lazy_u16_create_dirs_eagerly() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for create_dirs_eagerly in [false, true] {
+        let path = tmp_dir.path().join(create_dirs_eagerly.to_string());
+        let config = LazyU16PartitionedTableWriterConfig {
+            create_dirs_eagerly,
+            ..Default::default()
+        };
+        let mut writer = LazyU16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+            path.join("0"),
+            ("bucket".to_owned(), NonZeroU16::new(3), parquet_id_schema()),
+            config,
+        )
+        .unwrap();
+        if create_dirs_eagerly {
+            assert_eq!(list_tree(&path), ["bucket=0", "bucket=1", "bucket=2"]);
+        } else {
+            assert!(!path.exists());
+        }
+        writer
+            .partition(1)
+            .unwrap()
+            .builder()
+            .unwrap()
+            .0
+            .append_value(42);
+        writer.close().unwrap();
+    }
+
+    assert_eq!(
+        list_tree(&tmp_dir.path().join("false")),
+        ["bucket=1", "bucket=1/0.parquet"]
+    );
+    assert_eq!(
+        list_tree(&tmp_dir.path().join("true")),
+        ["bucket=0", "bucket=1", "bucket=1/0.parquet", "bucket=2"]
+    );
+}
+
+#[test]
+fn write_partitioned_batch() {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let batch = |partition_field: Field, partition_column: ArrayRef| {
+        let schema = Schema::new(vec![
+            partition_field,
+            Field::new("id", DataType::UInt64, false),
+        ]);
+        let ids = Arc::new(UInt64Array::from_iter_values(0..5));
+        RecordBatch::try_new(Arc::new(schema), vec![partition_column, ids]).unwrap()
+    };
+
+    let batch_by_type = batch(
+        Field::new("type", DataType::Utf8, false),
+        Arc::new(StringArray::from(vec!["foo", "bar", "foo", "bar", "foo"])),
+    );
+    let mut writer = Utf8PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("utf8").join("0"),
+        (
+            "type".to_owned(),
+            (batch_by_type.schema(), Default::default()),
+        ),
+        Default::default(),
+    )
+    .unwrap();
+    writer
+        .write_partitioned_batch(batch_by_type, "type")
+        .unwrap();
+    assert_eq!(writer.partitions()["foo"].written_rows(), 3);
+    assert_eq!(writer.partitions()["bar"].written_rows(), 2);
+    writer.close().unwrap();
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("utf8/type=foo/0.parquet")),
+        [0, 2, 4]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("utf8/type=bar/0.parquet")),
+        [1, 3]
+    );
+
+    let batch_by_bucket = batch(
+        Field::new("bucket", DataType::UInt64, false),
+        Arc::new(UInt64Array::from(vec![1, 1, 0, 1, 0])),
+    );
+    let mut writer = U16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("u16").join("0"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(2),
+            (batch_by_bucket.schema(), Default::default()),
+        ),
+        Default::default(),
+    )
+    .unwrap();
+    writer
+        .write_partitioned_batch(batch_by_bucket.clone(), "bucket")
+        .unwrap();
+    writer.close().unwrap();
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("u16/bucket=0/0.parquet")),
+        [2, 4]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("u16/bucket=1/0.parquet")),
+        [0, 1, 3]
+    );
+
+    // Partition 1 is out of range
+    let mut writer = U16PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("u16-small").join("0"),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(1),
+            (batch_by_bucket.schema(), Default::default()),
+        ),
+        Default::default(),
+    )
+    .unwrap();
+    assert!(writer
+        .write_partitioned_batch(batch_by_bucket, "bucket")
+        .is_err());
+}
+
+#[test]
+fn null_partition() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = Utf8PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        ("type".to_owned(), parquet_id_schema()),
+        Default::default(),
+    )
+    .unwrap();
+    writer
+        .partition_opt(None)
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(1);
+    writer
+        .partition_opt(Some("foo".to_owned()))
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(2);
+    writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        [
+            "type=__HIVE_DEFAULT_PARTITION__",
+            "type=__HIVE_DEFAULT_PARTITION__/0.parquet",
+            "type=foo",
+            "type=foo/0.parquet"
+        ]
+    );
+    assert_eq!(
+        read_parquet_ids(
+            &tmp_dir
+                .path()
+                .join(format!("type={}", HIVE_DEFAULT_PARTITION))
+                .join("0.parquet")
+        ),
+        [1]
+    );
+}
+
+#[test]
+fn layout_marker() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_path = tmp_dir.path().join("dataset");
+    let mut dataset_writer = ParallelDatasetWriter::<
+        U16PartitionedTableWriter<Utf8PartitionedTableWriter<ParquetTableWriter<IdBuilder>>>,
+    >::with_schema(
+        dataset_path.clone(),
+        (
+            "bucket".to_owned(),
+            NonZeroU16::new(2),
+            ("type \"quoted\"".to_owned(), parquet_id_schema()),
+        ),
+    )
+    .unwrap();
+    dataset_writer.write_layout_marker = true;
+    dataset_writer.file_name_prefix = Some("run1".to_owned());
+    dataset_writer.get_thread_writer().unwrap().partitions()[1]
+        .partition("foo".to_owned())
+        .unwrap()
+        .builder()
+        .unwrap()
+        .0
+        .append_value(42);
+    dataset_writer.close().unwrap();
+
+    let marker: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(dataset_path.join("_layout_version")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        marker,
+        serde_json::json!({
+            "crate": "dataset-writer",
+            "crate_version": env!("CARGO_PKG_VERSION"),
+            "partitioning": [
+                {"column": "bucket", "key_type": "u16", "scheme": "hive"},
+                {"column": "type \"quoted\"", "key_type": "utf8", "scheme": "hive"},
+            ],
+            "file_naming": {"scheme": "sequential", "prefix": "run1", "files_per_dir": null},
+        })
+    );
+}
+
+#[test]
+fn reopened_utf8_partition() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = Utf8PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        ("type".to_owned(), parquet_id_schema()),
+        Default::default(),
+    )
+    .unwrap();
+    for ids in [[1, 2], [3, 4]] {
+        writer
+            .partition("foo".to_owned())
+            .unwrap()
+            .builder()
+            .unwrap()
+            .0
+            .append_slice(&ids);
+        writer.close_partition("foo").unwrap().unwrap();
+    }
+    writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        ["type=foo", "type=foo/0.parquet", "type=foo/0_1.parquet"]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("type=foo/0.parquet")),
+        [1, 2]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("type=foo/0_1.parquet")),
+        [3, 4]
+    );
+}
+
+#[test]
+fn config_for_partition() {
+    use std::sync::Arc;
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = PartitionedTableWriterConfig {
+        config_for_partition: Some(Arc::new(|partition_key: &str| ParquetTableWriterConfig {
+            autoflush_row_group_len: Some(if partition_key == "hot" { 10 } else { 2 }),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+    let mut writer = Utf8PartitionedTableWriter::<ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        ("temperature".to_owned(), parquet_id_schema()),
+        config,
+    )
+    .unwrap();
+    for id in 0..20 {
+        for partition_key in ["hot", "cold"] {
+            writer
+                .partition(partition_key.to_owned())
+                .unwrap()
+                .builder()
+                .unwrap()
+                .0
+                .append_value(id);
+        }
+    }
+    writer.close().unwrap();
+
+    let num_row_groups = |partition_key| {
+        parquet_reader(
+            &tmp_dir
+                .path()
+                .join(format!("temperature={}", partition_key))
+                .join("0.parquet"),
+        )
+        .metadata()
+        .num_row_groups()
+    };
+    assert_eq!(num_row_groups("hot"), 2);
+    assert_eq!(num_row_groups("cold"), 10);
+}
+
+#[test]
+fn time_bucketed_writer() {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use arrow::array::{
+        ArrayBuilder, ArrayRef, StructArray, TimestampSecondBuilder, UInt64Builder,
+    };
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Second, None),
+                true,
+            ),
+            Field::new("id", DataType::UInt64, false),
+        ])
+    }
+
+    #[derive(Default)]
+    struct EventBuilder {
+        timestamps: TimestampSecondBuilder,
+        ids: UInt64Builder,
+    }
+
+    impl StructArrayBuilder for EventBuilder {
+        fn len(&self) -> usize {
+            self.ids.len()
+        }
+
+        fn buffer_size(&self) -> usize {
+            self.ids.len() * 16
+        }
+
+        fn finish(&mut self) -> Result<StructArray> {
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(self.timestamps.finish()),
+                Arc::new(self.ids.finish()),
+            ];
+            Ok(StructArray::new(schema().fields().clone(), columns, None))
+        }
+    }
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut writer = TimeBucketedTableWriter::<EventBuilder, ParquetTableWriter<IdBuilder>>::new(
+        tmp_dir.path().join("0"),
+        (
+            "timestamp".to_owned(),
+            TimeBucket::Day,
+            (Arc::new(schema()), Default::default()),
+        ),
+        Default::default(),
+    )
+    .unwrap();
+    let jan_1 = 1735689600; // 2025-01-01T00:00:00Z
+    for (id, timestamp) in [
+        (0, Some(jan_1 + 23 * 3600)),
+        (1, Some(jan_1 + 25 * 3600)),
+        (2, None),
+        (3, Some(jan_1)),
+    ] {
+        let builder = writer.builder().unwrap();
+        builder.timestamps.append_option(timestamp);
+        builder.ids.append_value(id);
+    }
+    writer.close().unwrap();
+
+    assert_eq!(
+        list_tree(tmp_dir.path()),
+        [
+            "date=2025-01-01",
+            "date=2025-01-01/0.parquet",
+            "date=2025-01-02",
+            "date=2025-01-02/0.parquet",
+            "date=__HIVE_DEFAULT_PARTITION__",
+            "date=__HIVE_DEFAULT_PARTITION__/0.parquet",
+        ]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("date=2025-01-01/0.parquet")),
+        [0, 3]
+    );
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("date=2025-01-02/0.parquet")),
+        [1]
+    );
+    assert_eq!(
+        read_parquet_ids(
+            &tmp_dir
+                .path()
+                .join("date=__HIVE_DEFAULT_PARTITION__/0.parquet")
+        ),
+        [2]
+    );
+}
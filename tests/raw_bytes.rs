@@ -0,0 +1,45 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "zstd")]
+
+use dataset_writer::*;
+
+fn write_two_flushes(writer: &mut RawBytesTableWriter) {
+    writer.builder().unwrap().extend(b"\x00\x01first");
+    writer.flush().unwrap();
+    writer.builder().unwrap().extend(b"second\xff");
+}
+
+#[test]
+fn raw_bytes_across_flushes() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+
+    let config = RawBytesTableWriterConfig {
+        extension: "dat".to_owned(),
+        ..Default::default()
+    };
+    let mut writer = RawBytesTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    write_two_flushes(&mut writer);
+    assert_eq!(writer.written_bytes(), 7);
+    writer.close().unwrap();
+    assert_eq!(
+        std::fs::read(tmp_dir.path().join("0.dat")).unwrap(),
+        b"\x00\x01firstsecond\xff"
+    );
+
+    let config = RawBytesTableWriterConfig {
+        compression_level: Some(3),
+        ..Default::default()
+    };
+    let mut writer = RawBytesTableWriter::new(tmp_dir.path().join("1"), (), config).unwrap();
+    write_two_flushes(&mut writer);
+    writer.close().unwrap();
+    let compressed = std::fs::read(tmp_dir.path().join("1.bin.zst")).unwrap();
+    assert_eq!(
+        zstd::decode_all(compressed.as_slice()).unwrap(),
+        b"\x00\x01firstsecond\xff"
+    );
+}
@@ -0,0 +1,103 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn resume_appends_rows() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("0");
+
+    let mut writer = ResumableParquetWriter::<IdBuilder>::new(
+        path.clone(),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(writer.resumed_rows(), 0);
+    for ids in [0..3, 3..5] {
+        for id in ids {
+            writer.inner().builder().unwrap().0.append_value(id);
+        }
+        writer.flush().unwrap();
+    }
+    writer.close().unwrap();
+    assert_eq!(
+        read_parquet_ids(&path.with_extension("parquet")),
+        [0, 1, 2, 3, 4]
+    );
+
+    let mut writer = ResumableParquetWriter::<IdBuilder>::new(
+        path.clone(),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(writer.resumed_rows(), 5);
+    for id in 5..7 {
+        writer.inner().builder().unwrap().0.append_value(id);
+    }
+    writer.close().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), ["0.parquet"]);
+    let path = path.with_extension("parquet");
+    assert_eq!(read_parquet_ids(&path), [0, 1, 2, 3, 4, 5, 6]);
+    // Row groups of the previous file are kept
+    let row_group_sizes: Vec<_> = parquet_reader(&path)
+        .metadata()
+        .row_groups()
+        .iter()
+        .map(|row_group| row_group.num_rows())
+        .collect();
+    assert_eq!(row_group_sizes, [3, 2, 2]);
+}
+
+#[test]
+fn interrupted_resume_is_retried() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("0");
+
+    let mut writer = ResumableParquetWriter::<IdBuilder>::new(
+        path.clone(),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    for id in 0..3 {
+        writer.inner().builder().unwrap().0.append_value(id);
+    }
+    writer.close().unwrap();
+
+    // Resume, then get interrupted before closing the new file
+    let mut writer = ResumableParquetWriter::<IdBuilder>::new(
+        path.clone(),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    writer.inner().builder().unwrap().0.append_value(100);
+    std::mem::forget(writer);
+
+    let mut writer = ResumableParquetWriter::<IdBuilder>::new(
+        path.clone(),
+        parquet_id_schema(),
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(writer.resumed_rows(), 3);
+    writer.inner().builder().unwrap().0.append_value(3);
+    writer.close().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), ["0.parquet"]);
+    assert_eq!(
+        read_parquet_ids(&path.with_extension("parquet")),
+        [0, 1, 2, 3]
+    );
+}
@@ -0,0 +1,45 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::sync::Arc;
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn batches_from_many_threads_go_to_a_single_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("shared.parquet");
+    let writer =
+        SharedParquetWriter::new(path.clone(), Arc::new(id_schema()), Default::default()).unwrap();
+    std::thread::scope(|s| {
+        for thread in 0..8u64 {
+            let writer = &writer;
+            s.spawn(move || {
+                for batch in 0..10 {
+                    let first_id = (thread * 10 + batch) * 100;
+                    writer
+                        .write_batch(id_array(first_id..first_id + 100))
+                        .unwrap();
+                }
+            });
+        }
+    });
+    let metadata = writer.close().unwrap();
+
+    assert_eq!(metadata.file_metadata().num_rows(), 8000);
+    assert_eq!(list_tree(tmp_dir.path()), ["shared.parquet"]);
+    let mut ids = read_parquet_ids(&path);
+    // Batches are not split
+    for batch in ids.chunks(100) {
+        assert_eq!(batch[99], batch[0] + 99);
+    }
+    ids.sort();
+    assert_eq!(ids, (0..8000).collect::<Vec<_>>());
+}
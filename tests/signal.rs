@@ -0,0 +1,85 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(all(feature = "signal", feature = "parquet"))]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn close_on_signal() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    let flag = Arc::new(AtomicBool::new(false));
+    let writer = CloseOnSignal::new(dataset_writer, flag.clone()).unwrap();
+    writer
+        .with_writer(|dataset_writer| {
+            let mut thread_writer = dataset_writer.get_thread_writer()?;
+            for id in 0..10 {
+                thread_writer.builder()?.0.append_value(id);
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert!(!writer.is_closed());
+    assert_eq!(list_tree(tmp_dir.path()), ["0.parquet"]);
+
+    // Simulates the flag set by the signal handler
+    flag.store(true, Ordering::Relaxed);
+    let start = Instant::now();
+    while !writer.is_closed() {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "Writer was not closed"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // Buffered rows were flushed and the footer written
+    assert_eq!(
+        read_parquet_ids(&tmp_dir.path().join("0.parquet")),
+        (0..10).collect::<Vec<_>>()
+    );
+    assert!(writer.with_writer(|_| Ok(())).is_err());
+
+    // Returns the results of closing from the background thread
+    let results = writer.close().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].file_metadata().num_rows(), 10);
+}
+
+#[test]
+fn close_without_signal() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        tmp_dir.path().to_owned(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    let writer = CloseOnSignal::new(dataset_writer, Arc::new(AtomicBool::new(false))).unwrap();
+    writer
+        .with_writer(|dataset_writer| {
+            dataset_writer
+                .get_thread_writer()?
+                .builder()?
+                .0
+                .append_value(1);
+            Ok(())
+        })
+        .unwrap();
+
+    let results = writer.close().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(read_parquet_ids(&tmp_dir.path().join("0.parquet")), [1]);
+}
@@ -0,0 +1,91 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(any(
+    feature = "arrow-ipc",
+    feature = "json",
+    feature = "parquet",
+    feature = "csv"
+))]
+
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use dataset_writer::*;
+
+/// Returns a batch with an `id` and a nullable `name`
+fn batch() -> RecordBatch {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, true),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from(vec![1, 2, 3])),
+        Arc::new(StringArray::from(vec![Some("foo"), None, Some("bar, baz")])),
+    ];
+    RecordBatch::try_new(schema, columns).unwrap()
+}
+
+/// Returns the concatenation of all batches
+fn concat(batches: impl IntoIterator<Item = RecordBatch>) -> RecordBatch {
+    let batches: Vec<_> = batches.into_iter().collect();
+    arrow::compute::concat_batches(&batch().schema(), &batches).unwrap()
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let metadata =
+        write_parquet_file(tmp_dir.path().join("0"), batch(), Default::default()).unwrap();
+    assert_eq!(metadata.file_metadata().num_rows(), 3);
+
+    let file = std::fs::File::open(tmp_dir.path().join("0.parquet")).unwrap();
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(concat(reader.map(Result::unwrap)), batch());
+}
+
+#[cfg(feature = "arrow-ipc")]
+#[test]
+fn arrow_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    write_arrow_file(tmp_dir.path().join("0"), batch()).unwrap();
+
+    let file = std::fs::File::open(tmp_dir.path().join("0.arrow")).unwrap();
+    let reader = arrow::ipc::reader::FileReader::try_new(file, None).unwrap();
+    assert_eq!(concat(reader.map(Result::unwrap)), batch());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn jsonl_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    write_jsonl_file(tmp_dir.path().join("0"), batch()).unwrap();
+
+    let file = std::fs::File::open(tmp_dir.path().join("0.jsonl")).unwrap();
+    let reader = arrow::json::ReaderBuilder::new(batch().schema())
+        .build(std::io::BufReader::new(file))
+        .unwrap();
+    assert_eq!(concat(reader.map(Result::unwrap)), batch());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_zst_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let result = write_csv_zst_file(tmp_dir.path().join("0"), batch()).unwrap();
+    assert_eq!(result.rows_written, 3);
+
+    let file = std::fs::File::open(tmp_dir.path().join("0.csv.zst")).unwrap();
+    let reader = arrow::csv::ReaderBuilder::new(batch().schema())
+        .with_header(true)
+        .build(zstd::stream::read::Decoder::new(file).unwrap())
+        .unwrap();
+    assert_eq!(concat(reader.map(Result::unwrap)), batch());
+}
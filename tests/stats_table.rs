@@ -0,0 +1,121 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use std::path::{Path, PathBuf};
+
+use arrow::array::{AsArray, RecordBatch};
+use arrow::datatypes::UInt64Type;
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+/// Writes ids 0..3 to a file, and 10..15 to another
+fn two_file_dataset(path: PathBuf) -> ParallelDatasetWriter<ParquetTableWriter<IdBuilder>> {
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        path,
+        parquet_id_schema(),
+    )
+    .unwrap();
+    {
+        let mut writer = dataset_writer.get_thread_writer().unwrap();
+        writer.builder().unwrap().0.append_slice(&[0, 1, 2]);
+        writer.rotate().unwrap();
+        writer
+            .builder()
+            .unwrap()
+            .0
+            .append_slice(&[10, 11, 12, 13, 14]);
+    }
+    dataset_writer
+}
+
+fn read_table(path: &Path) -> RecordBatch {
+    let mut batches = parquet_reader(path).build().unwrap();
+    let batch = batches.next().unwrap().unwrap();
+    assert!(batches.next().is_none());
+    batch
+}
+
+fn u64_column(batch: &RecordBatch, name: &str) -> Vec<u64> {
+    batch
+        .column_by_name(name)
+        .unwrap_or_else(|| panic!("Missing column {}", name))
+        .as_primitive::<UInt64Type>()
+        .values()
+        .to_vec()
+}
+
+#[test]
+fn close_with_stats_table() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_path = tmp_dir.path().join("dataset");
+    let footers = two_file_dataset(dataset_path.clone())
+        .close_with_stats_table()
+        .unwrap();
+    assert_eq!(footers.len(), 1);
+
+    let stats = read_table(&dataset_path.join("_stats.parquet"));
+    assert_eq!(stats.num_rows(), 2);
+    let paths: Vec<_> = stats
+        .column_by_name("path")
+        .unwrap()
+        .as_string::<i32>()
+        .iter()
+        .map(Option::unwrap)
+        .collect();
+    assert_eq!(paths, ["0.parquet", "0_1.parquet"]);
+    assert_eq!(u64_column(&stats, "rows"), [3, 5]);
+    assert_eq!(u64_column(&stats, "id.min"), [0, 10]);
+    assert_eq!(u64_column(&stats, "id.max"), [2, 14]);
+    assert_eq!(u64_column(&stats, "id.null_count"), [0, 0]);
+}
+
+#[test]
+fn close_with_zonemap() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dataset_path = tmp_dir.path().join("dataset");
+    let dataset_writer = ParallelDatasetWriter::<ParquetTableWriter<IdBuilder>>::with_schema(
+        dataset_path.clone(),
+        parquet_id_schema(),
+    )
+    .unwrap();
+    {
+        let mut writer = dataset_writer.get_thread_writer().unwrap();
+        writer.builder().unwrap().0.append_slice(&[0, 1, 2]);
+        writer.flush().unwrap();
+        writer.builder().unwrap().0.append_slice(&[6, 5]);
+        writer.rotate().unwrap();
+        writer
+            .builder()
+            .unwrap()
+            .0
+            .append_slice(&[10, 11, 12, 13, 14]);
+    }
+    dataset_writer
+        .close_with_zonemap(&["id".to_owned()])
+        .unwrap();
+
+    let zonemap = read_table(&dataset_path.join("_zonemap.parquet"));
+    let paths: Vec<_> = zonemap
+        .column_by_name("path")
+        .unwrap()
+        .as_string::<i32>()
+        .iter()
+        .map(Option::unwrap)
+        .collect();
+    assert_eq!(paths, ["0.parquet", "0.parquet", "0_1.parquet"]);
+    assert_eq!(u64_column(&zonemap, "row_group"), [0, 1, 0]);
+    assert_eq!(u64_column(&zonemap, "rows"), [3, 2, 5]);
+    assert_eq!(u64_column(&zonemap, "id.min"), [0, 5, 10]);
+    assert_eq!(u64_column(&zonemap, "id.max"), [2, 6, 14]);
+
+    let dataset_writer = two_file_dataset(tmp_dir.path().join("unknown"));
+    assert!(dataset_writer
+        .close_with_zonemap(&["unknown".to_owned()])
+        .is_err());
+}
@@ -0,0 +1,79 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "tar")]
+
+use std::io::{Read, Write};
+
+use dataset_writer::*;
+
+/// Returns the name and content of each entry of a tar archive
+fn read_tar(archive: impl Read) -> Vec<(String, Vec<u8>)> {
+    tar::Archive::new(archive)
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            (name, content)
+        })
+        .collect()
+}
+
+#[test]
+fn tar_dataset_writer() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for compression_level in [None, Some(3)] {
+        let path = tmp_dir
+            .path()
+            .join(format!("dataset-{:?}.tar", compression_level));
+        let dataset_writer = TarDatasetWriter::<PlainZstTableWriter>::new(
+            path.clone(),
+            (),
+            Default::default(),
+            compression_level,
+        )
+        .unwrap();
+
+        // Keeps threads alive until both wrote, so none reuses the writer of the other
+        let barrier = std::sync::Barrier::new(2);
+        std::thread::scope(|s| {
+            for content in ["first thread\n", "second thread\n"] {
+                let (dataset_writer, barrier) = (&dataset_writer, &barrier);
+                s.spawn(move || {
+                    dataset_writer
+                        .get_thread_writer()
+                        .unwrap()
+                        .inner()
+                        .write_all(content.as_bytes())
+                        .unwrap();
+                    barrier.wait();
+                });
+            }
+        });
+        dataset_writer.close().unwrap();
+
+        let mut spool_path = path.clone().into_os_string();
+        spool_path.push(".spool");
+        assert!(!std::path::Path::new(&spool_path).exists());
+        let file = std::fs::File::open(&path).unwrap();
+        let mut entries = match compression_level {
+            None => read_tar(file),
+            Some(_) => read_tar(zstd::stream::read::Decoder::new(file).unwrap()),
+        };
+        entries.sort();
+        let names: Vec<_> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["0.zst", "1.zst"]);
+        let mut contents: Vec<_> = entries
+            .iter()
+            .map(|(_, content)| String::from_utf8(zstd::decode_all(content.as_slice()).unwrap()))
+            .map(Result::unwrap)
+            .collect();
+        contents.sort();
+        assert_eq!(contents, ["first thread\n", "second thread\n"]);
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "parquet")]
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn valid_and_truncated_files() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(tmp_dir.path().join("dir")).unwrap();
+    for name in ["good", "truncated"] {
+        let mut writer = ParquetTableWriter::<IdBuilder>::new(
+            tmp_dir.path().join("dir").join(name),
+            parquet_id_schema(),
+            Default::default(),
+        )
+        .unwrap();
+        writer.builder().unwrap().0.append_slice(&[1, 2, 3]);
+        writer.close().unwrap();
+    }
+    let truncated_path = tmp_dir.path().join("dir/truncated.parquet");
+    let content = std::fs::read(&truncated_path).unwrap();
+    std::fs::write(&truncated_path, &content[..content.len() - 10]).unwrap();
+    // Not a Parquet file, so it is ignored
+    std::fs::write(tmp_dir.path().join("dir/notes.txt"), "foo").unwrap();
+
+    let statuses = verify_dataset(tmp_dir.path()).unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(
+        statuses[0],
+        (
+            tmp_dir.path().join("dir/good.parquet"),
+            FileStatus::Valid { rows: 3 }
+        )
+    );
+    assert_eq!(statuses[1].0, truncated_path);
+    assert!(
+        matches!(statuses[1].1, FileStatus::Corrupt { .. }),
+        "unexpected status: {:?}",
+        statuses[1].1
+    );
+}
@@ -0,0 +1,126 @@
+// Copyright (C) 2025  The Software Heritage developers
+// See the AUTHORS file at the top-level directory of this distribution
+// License: GNU General Public License version 3, or any later version
+// See top-level LICENSE file for more information
+
+#![cfg(feature = "zstd")]
+
+use std::io::Write;
+
+use dataset_writer::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn size_sidecar() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = PlainZstTableWriterConfig {
+        write_size_sidecar: true,
+        max_frame_content_size: Some(1000),
+        ..Default::default()
+    };
+    let mut writer = PlainZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    for _ in 0..100 {
+        writer.write_all(&[b'a'; 123]).unwrap();
+    }
+    let result = writer.close().unwrap();
+
+    assert_eq!(list_tree(tmp_dir.path()), ["0.zst", "0.zst.size"]);
+    assert_eq!(result.uncompressed_bytes, 12300);
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("0.zst.size")).unwrap(),
+        "12300\n"
+    );
+    let content = zstd::decode_all(std::fs::File::open(tmp_dir.path().join("0.zst")).unwrap());
+    assert_eq!(content.unwrap().len(), 12300);
+
+    let mut writer =
+        PlainZstTableWriter::new(tmp_dir.path().join("1"), (), Default::default()).unwrap();
+    writer.write_all(b"foo").unwrap();
+    writer.close().unwrap();
+    assert!(!tmp_dir.path().join("1.zst.size").exists());
+}
+
+#[test]
+fn max_frame_content_size() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = PlainZstTableWriterConfig {
+        max_frame_content_size: Some(1000),
+        ..Default::default()
+    };
+    let mut writer = PlainZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    for i in 0..25 {
+        writer.write_all(&[b'a' + i; 123]).unwrap();
+    }
+    writer.close().unwrap();
+
+    let compressed = std::fs::read(tmp_dir.path().join("0.zst")).unwrap();
+    let mut remaining = compressed.as_slice();
+    let mut frame_sizes = Vec::new();
+    let mut content = Vec::new();
+    while !remaining.is_empty() {
+        let frame_len = zstd::zstd_safe::find_frame_compressed_size(remaining).unwrap();
+        let frame = zstd::decode_all(&remaining[..frame_len]).unwrap();
+        frame_sizes.push(frame.len());
+        content.extend(frame);
+        remaining = &remaining[frame_len..];
+    }
+    assert_eq!(frame_sizes, [1000, 1000, 1000, 75]);
+    assert_eq!(
+        content,
+        (0..25).flat_map(|i| [b'a' + i; 123]).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn include_checksum() {
+    /// Returns whether the first frame of a zstd file has a content checksum
+    fn has_checksum(compressed: &[u8]) -> bool {
+        assert_eq!(compressed[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+        // Content_Checksum_flag of the Frame_Header_Descriptor
+        compressed[4] & 0b100 != 0
+    }
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, include_checksum| {
+        let config = PlainZstTableWriterConfig {
+            include_checksum,
+            ..Default::default()
+        };
+        let mut writer = PlainZstTableWriter::new(tmp_dir.path().join(name), (), config).unwrap();
+        writer.write_all(b"some content").unwrap();
+        writer.close().unwrap();
+        std::fs::read(tmp_dir.path().join(name).with_extension("zst")).unwrap()
+    };
+
+    assert!(!has_checksum(&write("0", false)));
+
+    let mut compressed = write("1", true);
+    assert!(has_checksum(&compressed));
+    assert_eq!(
+        zstd::decode_all(compressed.as_slice()).unwrap(),
+        b"some content"
+    );
+    // The checksum is the last 4 bytes of the frame
+    *compressed.last_mut().unwrap() ^= 1;
+    assert!(zstd::decode_all(compressed.as_slice()).is_err());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_include_checksum() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = CsvZstTableWriterConfig {
+        include_checksum: true,
+        ..Default::default()
+    };
+    let mut writer = CsvZstTableWriter::new(tmp_dir.path().join("0"), (), config).unwrap();
+    writer.write_record(["a", "b"]).unwrap();
+    writer.close().unwrap();
+
+    let mut compressed = std::fs::read(tmp_dir.path().join("0.csv.zst")).unwrap();
+    assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), b"a,b\r\n");
+    *compressed.last_mut().unwrap() ^= 1;
+    assert!(zstd::decode_all(compressed.as_slice()).is_err());
+}